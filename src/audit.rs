@@ -0,0 +1,83 @@
+//! Append-only audit log of droid invocations (`audit_log_path` config)
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+
+/// One audit log line, written as JSON
+#[derive(Debug, Serialize)]
+pub struct AuditRecord {
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub cwd: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto: Option<String>,
+    /// Hex SHA-256 of the assembled prompt, for correlating runs without storing it
+    ///
+    /// The prompt fields are absent for runs rejected before the prompt was assembled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_sha256: Option<String>,
+    /// Assembled prompt (DROID.md included); only with `log_full_prompt`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_len: Option<usize>,
+    pub args: Vec<String>,
+    pub session_id: String,
+    pub success: bool,
+    /// Why the run failed or was rejected
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Append `record` as one JSON line to `path`
+///
+/// Failures are logged to stderr and never fail the run.
+pub(crate) async fn append(path: &Path, record: &AuditRecord) {
+    let mut line = match serde_json::to_string(record) {
+        Ok(line) => line,
+        Err(e) => {
+            eprintln!("droid-mcp-rs: failed to serialize audit record: {}", e);
+            return;
+        }
+    };
+    line.push('\n');
+
+    let written = async {
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        file.write_all(line.as_bytes()).await?;
+        file.flush().await
+    }
+    .await;
+
+    if let Err(e) = written {
+        eprintln!(
+            "droid-mcp-rs: failed to write audit log {}: {}",
+            path.display(),
+            e
+        );
+    }
+}
+
+/// Lowercase hex SHA-256 digest of `prompt`
+pub fn prompt_sha256(prompt: &str) -> String {
+    Sha256::digest(prompt.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Current time as seconds since the Unix epoch
+pub(crate) fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,196 @@
+//! Multi-file prompt context selected by glob patterns (`context_globs` argument)
+
+use glob::{MatchOptions, Pattern};
+use std::collections::BTreeSet;
+use std::path::{Component, Path, PathBuf};
+
+/// Name of the ignore file read from the working directory
+pub const IGNORE_FILE: &str = ".droidignore";
+
+/// Caps on how much `context_globs` may pull into one prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GlobLimits {
+    /// Most files included
+    pub max_files: usize,
+    /// Largest combined size in bytes of the included file contents
+    pub max_bytes: usize,
+}
+
+impl Default for GlobLimits {
+    fn default() -> Self {
+        GlobLimits {
+            max_files: 50,
+            max_bytes: 1024 * 1024,
+        }
+    }
+}
+
+/// Check that `pattern` is a valid glob relative to the working directory
+///
+/// Absolute patterns and `..` components are rejected so a glob cannot reach outside it.
+pub fn validate_glob(pattern: &str) -> Result<(), String> {
+    if pattern.trim().is_empty() {
+        return Err("context_globs entries must not be empty".to_string());
+    }
+    let path = Path::new(pattern);
+    if path.is_absolute() || pattern.starts_with('/') {
+        return Err(format!(
+            "context_globs entry '{}' must be relative to the working directory",
+            pattern
+        ));
+    }
+    if path.components().any(|c| matches!(c, Component::ParentDir)) {
+        return Err(format!(
+            "context_globs entry '{}' must not contain '..'",
+            pattern
+        ));
+    }
+    Pattern::new(pattern)
+        .map_err(|e| format!("Invalid context_globs entry '{}': {}", pattern, e))?;
+    Ok(())
+}
+
+/// Read `.droidignore` patterns (one glob per line, `#` comments) from `working_dir`
+fn read_ignore_patterns(working_dir: &Path) -> (Vec<Pattern>, Vec<String>) {
+    let Ok(raw) = std::fs::read_to_string(working_dir.join(IGNORE_FILE)) else {
+        return (Vec::new(), Vec::new());
+    };
+
+    let mut patterns = Vec::new();
+    let mut warnings = Vec::new();
+    for line in raw.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match Pattern::new(line.trim_matches('/')) {
+            Ok(pattern) => patterns.push(pattern),
+            Err(e) => warnings.push(format!(
+                "Ignoring invalid {} pattern '{}': {}",
+                IGNORE_FILE, line, e
+            )),
+        }
+    }
+    (patterns, warnings)
+}
+
+/// A path is ignored when a pattern matches it or any of its parent directories
+fn is_ignored(relative: &Path, patterns: &[Pattern]) -> bool {
+    relative
+        .ancestors()
+        .filter(|p| !p.as_os_str().is_empty())
+        .any(|p| patterns.iter().any(|pattern| pattern.matches_path(p)))
+}
+
+/// Concatenate the files matching `globs` under `working_dir`, each behind a
+/// `// file: <path>` header
+///
+/// Files listed in `.droidignore` or resolving outside the working directory are skipped.
+/// Matches are taken in path order until `limits` is reached. Returns the concatenated
+/// text (empty when nothing matched) and warnings about skipped or omitted files.
+///
+/// Does blocking filesystem I/O; async callers should run it on a blocking thread.
+pub fn collect(working_dir: &Path, globs: &[String], limits: GlobLimits) -> (String, Vec<String>) {
+    let mut warnings = Vec::new();
+    let root = match working_dir.canonicalize() {
+        Ok(root) => root,
+        Err(e) => {
+            warnings.push(format!(
+                "Failed to resolve working directory for context_globs: {}",
+                e
+            ));
+            return (String::new(), warnings);
+        }
+    };
+
+    let (ignore, ignore_warnings) = read_ignore_patterns(&root);
+    warnings.extend(ignore_warnings);
+
+    let options = MatchOptions::new();
+    let mut matched: BTreeSet<PathBuf> = BTreeSet::new();
+    for pattern in globs {
+        let full = format!("{}/{}", Pattern::escape(&root.to_string_lossy()), pattern);
+        let paths = match glob::glob_with(&full, options) {
+            Ok(paths) => paths,
+            Err(e) => {
+                warnings.push(format!("Invalid context_globs entry '{}': {}", pattern, e));
+                continue;
+            }
+        };
+        for entry in paths {
+            let Ok(path) = entry else { continue };
+            if !path.is_file() {
+                continue;
+            }
+            // Symlinks may still point outside the working directory
+            match path.canonicalize() {
+                Ok(resolved) if resolved.starts_with(&root) => {
+                    if let Ok(relative) = resolved.strip_prefix(&root) {
+                        matched.insert(relative.to_path_buf());
+                    }
+                }
+                _ => warnings.push(format!(
+                    "Skipping {}: resolves outside the working directory",
+                    path.display()
+                )),
+            }
+        }
+    }
+    matched.retain(|relative| !is_ignored(relative, &ignore));
+
+    let total = matched.len();
+    let mut context = String::new();
+    let mut included = 0;
+    let mut bytes = 0;
+    for relative in &matched {
+        if included >= limits.max_files {
+            warnings.push(format!(
+                "context_globs matched {} files; only the first {} were included",
+                total, limits.max_files
+            ));
+            break;
+        }
+        let path = root.join(relative);
+        // Check the size first so an oversized match is never read into memory
+        let size = match std::fs::metadata(&path) {
+            Ok(metadata) => metadata.len(),
+            Err(e) => {
+                warnings.push(format!("Skipping {}: {}", relative.display(), e));
+                continue;
+            }
+        };
+        if size > (limits.max_bytes - bytes) as u64 {
+            warnings.push(format!(
+                "context_globs reached the {}-byte limit; included {} of {} matched files",
+                limits.max_bytes, included, total
+            ));
+            break;
+        }
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) => {
+                warnings.push(format!("Skipping {}: {}", relative.display(), e));
+                continue;
+            }
+        };
+        if bytes + content.len() > limits.max_bytes {
+            warnings.push(format!(
+                "context_globs reached the {}-byte limit; included {} of {} matched files",
+                limits.max_bytes, included, total
+            ));
+            break;
+        }
+        if !context.is_empty() {
+            context.push('\n');
+        }
+        context.push_str(&format!("// file: {}\n", relative.display()));
+        context.push_str(&content);
+        if !content.ends_with('\n') {
+            context.push('\n');
+        }
+        bytes += content.len();
+        included += 1;
+    }
+
+    (context.trim_end_matches('\n').to_string(), warnings)
+}
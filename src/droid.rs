@@ -1,24 +1,44 @@
 use anyhow::{Context, Result};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
 use std::path::PathBuf;
 use std::process::Stdio;
-use std::sync::OnceLock;
-use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError, RwLock};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
 use tokio::process::Command;
 
 // Constants
 const DEFAULT_TIMEOUT_SECS: u64 = 600; // 10 minutes
 const MAX_TIMEOUT_SECS: u64 = 3600; // 1 hour
-const MAX_AGENT_MESSAGES_SIZE: usize = 10 * 1024 * 1024; // 10MB
+pub(crate) const MAX_AGENT_MESSAGES_SIZE: usize = 10 * 1024 * 1024; // 10MB
 const MAX_ALL_MESSAGES_SIZE: usize = 50 * 1024 * 1024; // 50MB
 const MAX_DROID_MD_SIZE: usize = 1024 * 1024; // 1MB
+const MAX_CONTEXT_FILE_SIZE: usize = 1024 * 1024; // 1MB
 const ABSOLUTE_MAX_SIZE: u64 = 10 * 1024 * 1024; // 10MB absolute max
 const MAX_STDERR_SIZE: usize = 100_000; // 100KB
+const DEFAULT_MAX_LINE_BYTES: usize = 16 * 1024 * 1024; // 16MB
+const DEFAULT_RATE_LIMIT_MAX_RETRIES: u32 = 3;
+const DEFAULT_RATE_LIMIT_BACKOFF_SECS: u64 = 5;
+const DEFAULT_POST_RUN_TIMEOUT_SECS: u64 = 120; // 2 minutes
+const MAX_POST_RUN_OUTPUT_SIZE: usize = 100_000; // 100KB
+const DEFAULT_EXIT_GRACE_SECS: u64 = 5;
+const STDERR_DRAIN_SECS: u64 = 2;
+const DEFAULT_PROGRESS_INTERVAL_SECS: u64 = 10;
+const TOUCH_SESSION_PROMPT: &str = "Reply with OK. Do not use any tools.";
+const TOUCH_SESSION_TIMEOUT_SECS: u64 = 120;
+/// Key stamped into each `all_messages` entry: milliseconds since parsing started
+pub const RECEIVED_MS_KEY: &str = "_mcp_received_ms";
+const DEFAULT_TRUNCATION_MARKER: &str = "\n[... Agent messages truncated due to size limit ...]";
+const GIT_DIFF_TIMEOUT_SECS: u64 = 30;
+const MAX_DIFF_SIZE: usize = 200_000; // 200KB
+const DROID_TOOLS_TIMEOUT_SECS: u64 = 30;
+const DROID_TOOLS_CACHE_TTL_SECS: u64 = 300; // 5 minutes
 
 /// Droid CLI execution options
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Options {
     pub prompt: Option<String>,
     pub file: Option<PathBuf>,
@@ -26,29 +46,302 @@ pub struct Options {
     pub session_id: Option<String>,
     pub auto: Option<String>,
     pub model: Option<String>,
+    pub raw_model: Option<String>,
     pub enabled_tools: Option<String>,
     pub disabled_tools: Option<String>,
     pub additional_args: Vec<String>,
+    pub extra_args: Vec<String>,
+    /// Validated `(key, value)` pairs rendered as `--key value` after `extra_args`
+    pub passthrough_flags: Vec<(String, String)>,
     pub timeout_secs: Option<u64>,
     pub reasoning_effort: Option<String>,
     pub use_spec: bool,
     pub spec_model: Option<String>,
+    pub spec_auto: Option<String>,
     pub skip_permissions_unsafe: bool,
     pub output_format: Option<String>,
+    pub max_turns: Option<u32>,
+    /// Prepend DROID.md from the working directory (default: true)
+    pub use_droid_md: Option<bool>,
+    /// Also write droid's raw stdout stream to this file
+    pub output_file: Option<PathBuf>,
+    /// Reference material appended to the prompt as a `<context>` block
+    pub context_file: Option<PathBuf>,
+    /// Globs (relative to `working_dir`) of files concatenated into a `<context>` block
+    pub context_globs: Vec<String>,
+    /// Server-generated id tagging this run's log lines
+    pub request_id: Option<String>,
+    /// Fail before spawning droid when the working directory has no DROID.md
+    pub require_droid_md: Option<bool>,
+    /// After a successful run, capture `git diff` and untracked files of `working_dir`
+    pub return_diff: bool,
+    /// Receives a heartbeat every `progress_interval_secs` while droid runs
+    pub progress: Option<tokio::sync::mpsc::UnboundedSender<Progress>>,
+    /// Attach droid's stderr to a failed result as `stderr` (None = true)
+    pub include_stderr_on_error: Option<bool>,
+    /// Ad-hoc system prompt, placed after DROID.md inside the same system prompt block
+    pub system_prompt: Option<String>,
+    /// Absolute path of a droid binary used instead of the resolved one for this run
+    /// (only with `allow_client_binary_override`)
+    pub droid_bin: Option<PathBuf>,
+    /// Paths relative to `working_dir` that droid is restricted to, rendered with `scope_flag`
+    pub scope_paths: Vec<String>,
+    /// droid flag passed once per `scope_paths` entry; set by `run` from the config
+    pub scope_flag: Option<String>,
+}
+
+/// Heartbeat sent to [`Options::progress`] while a run is in flight
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Progress {
+    /// Seconds since droid was started
+    pub elapsed_secs: u64,
+    /// `type` of the most recent stream-json line, if any was parsed yet
+    pub last_line_type: Option<String>,
+    /// Effective timeout of the run, the basis of `estimated_percent`
+    pub timeout_secs: u64,
+    /// Rough completion estimate from elapsed time vs. timeout, see [`estimated_percent`]
+    pub estimated_percent: u8,
+}
+
+/// Elapsed share of the timeout as a percentage, capped at 99 while the run is going
+///
+/// Only an estimate: most runs finish well before their timeout.
+pub fn estimated_percent(elapsed_secs: u64, timeout_secs: u64) -> u8 {
+    if timeout_secs == 0 {
+        return 99;
+    }
+    (elapsed_secs.saturating_mul(100) / timeout_secs).min(99) as u8
 }
 
 /// Droid execution result
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct DroidResult {
     pub success: bool,
     pub session_id: String,
     pub agent_messages: String,
+    /// `agent_messages` hit the size cap; the text itself carries no marker
     pub agent_messages_truncated: bool,
     pub all_messages: Vec<HashMap<String, Value>>,
     pub all_messages_truncated: bool,
+    /// Serialized bytes of `all_messages` counted against its size cap (without the
+    /// receive-time stamps)
+    pub all_messages_bytes: usize,
+    /// droid reported a rate limit (eligible for retry_on_rate_limit backoff)
+    pub rate_limited: bool,
     pub error: Option<String>,
-    pub warnings: Option<String>,
+    /// Category of `error`, for clients that branch on the failure kind
+    pub error_code: Option<ErrorCode>,
+    /// Fields of the last droid error line, as droid reported them
+    pub droid_error: Option<DroidError>,
+    /// Captured droid stderr of a failed run, kept out of `error`
+    pub stderr: Option<String>,
+    /// Discrete warnings (DROID.md, model resolution, timeouts, retries, ...)
+    pub warnings: Vec<String>,
     pub model_info: Option<String>,
+    /// Model droid ran with, after alias expansion and the default model (`raw_model` as given)
+    pub model: Option<String>,
+    /// Autonomy level actually passed to droid after defaults (None = read-only or
+    /// skip_permissions_unsafe)
+    pub effective_auto: Option<String>,
+    /// Working directory droid ran in
+    pub working_dir: PathBuf,
+    pub post_run_output: Option<String>,
+    /// Working tree changes after the run (`return_diff`), size-limited
+    pub diff: Option<String>,
+    pub spec: Option<String>,
+    /// Final status droid reported on a trailing `result`/`summary` line; overrides the
+    /// exit code and error lines when present
+    pub reported_success: Option<bool>,
+    /// Summary text from the `result`/`summary` line
+    pub summary: Option<String>,
+    /// Token usage from the `result`/`summary` line, as reported by droid
+    pub usage: Option<Value>,
+    pub duration_ms: Option<u64>,
+    /// Every `completion` line in emission order (`agent_messages` holds them joined)
+    pub completions: Vec<Completion>,
+    /// Counts of stdout line types seen by the parser
+    pub line_stats: LineStats,
+}
+
+/// A request `run` refused before starting droid, because it breaks the server policy or
+/// is malformed
+///
+/// The MCP server reports these as invalid params; every other `run` error is an
+/// execution failure.
+#[derive(Debug)]
+pub struct Rejected(pub String);
+
+impl std::fmt::Display for Rejected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Rejected {}
+
+fn rejected(message: impl Into<String>) -> anyhow::Error {
+    anyhow::Error::new(Rejected(message.into()))
+}
+
+/// Machine-readable category of a failed run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The droid binary does not exist (check DROID_BIN / PATH)
+    DroidNotFound,
+    /// The droid binary exists but could not be started (permissions, ...)
+    SpawnFailed,
+    /// The run exceeded its timeout
+    Timeout,
+    /// droid exited with a non-zero status
+    NonZeroExit,
+    /// droid was terminated by a signal (crash, OOM killer, ...) instead of exiting (Unix)
+    KilledBySignal,
+    /// droid emitted an error line or reported a failed final status
+    DroidError,
+    /// droid reported a rate limit
+    RateLimited,
+    /// droid finished without reporting a session id
+    NoSession,
+    /// droid finished without agent messages while `require_agent_messages` is set
+    NoAgentMessages,
+    /// The run was terminated by `cancel_all`
+    Cancelled,
+}
+
+/// An `error` line emitted by droid, kept structured instead of flattened into `error`
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct DroidError {
+    /// droid's own error code or type, e.g. `rate_limit_exceeded`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+}
+
+impl DroidError {
+    /// Read an error line; fields may sit on the line itself or under an `error` object
+    fn from_line(line: &Value) -> Option<Self> {
+        let source = line.get("error").filter(|v| v.is_object()).unwrap_or(line);
+        let message = source.get("message").and_then(|v| v.as_str())?.to_string();
+        let code = source
+            .get("code")
+            .or_else(|| source.get("type").filter(|v| v.as_str() != Some("error")))
+            .and_then(|v| match v {
+                Value::String(s) => Some(s.clone()),
+                Value::Number(n) => Some(n.to_string()),
+                _ => None,
+            });
+        Some(DroidError {
+            code,
+            message,
+            details: source.get("details").filter(|v| !v.is_null()).cloned(),
+        })
+    }
+
+    /// The crate's category for this error, from droid's code where it is recognizable
+    pub fn error_code(&self) -> ErrorCode {
+        match self.code.as_deref() {
+            Some(code) if is_rate_limit_message(code) => ErrorCode::RateLimited,
+            Some(code) if code.to_lowercase().contains("timeout") => ErrorCode::Timeout,
+            _ => ErrorCode::DroidError,
+        }
+    }
+}
+
+/// A single `completion` line emitted by droid
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Completion {
+    pub text: String,
+    /// Step (sub-task) the completion belongs to, when droid reports one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub step: Option<String>,
+}
+
+/// Per-run counters of the stream-json line types droid emitted
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineStats {
+    pub system: usize,
+    pub message: usize,
+    pub completion: usize,
+    pub error: usize,
+    pub warning: usize,
+    /// `spec` / `plan` lines
+    pub spec: usize,
+    /// `result` / `summary` lines carrying the final status
+    pub summary: usize,
+    /// Lines with a missing or unrecognized `type`
+    pub unknown: usize,
+    /// Lines that were not valid JSON
+    pub parse_failures: usize,
+    /// Lines longer than `max_line_bytes`, skipped without being buffered
+    pub oversized: usize,
+}
+
+impl LineStats {
+    fn record(&mut self, line_type: Option<&str>) {
+        match line_type {
+            Some("system") => self.system += 1,
+            Some("message") => self.message += 1,
+            Some("completion") => self.completion += 1,
+            Some("error") => self.error += 1,
+            Some("warning") => self.warning += 1,
+            Some("spec" | "plan") => self.spec += 1,
+            Some("result" | "summary") => self.summary += 1,
+            _ => self.unknown += 1,
+        }
+    }
+}
+
+impl std::fmt::Display for LineStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "system={} message={} completion={} error={} warning={} spec={} summary={} unknown={} parse_failures={} oversized={}",
+            self.system,
+            self.message,
+            self.completion,
+            self.error,
+            self.warning,
+            self.spec,
+            self.summary,
+            self.unknown,
+            self.parse_failures,
+            self.oversized
+        )
+    }
+}
+
+impl DroidResult {
+    /// Warnings joined into a single newline-separated string, for text renderings
+    pub fn joined_warnings(&self) -> Option<String> {
+        if self.warnings.is_empty() {
+            None
+        } else {
+            Some(self.warnings.join("\n"))
+        }
+    }
+
+    /// `all_messages` as newline-delimited JSON in stream order, ending with a
+    /// `{"type":"truncated"}` line when the size cap dropped later lines
+    pub fn transcript_ndjson(&self) -> String {
+        let mut lines: Vec<String> = self
+            .all_messages
+            .iter()
+            .filter_map(|message| serde_json::to_string(message).ok())
+            .collect();
+        if self.all_messages_truncated {
+            lines.push(
+                serde_json::json!({
+                    "type": "truncated",
+                    "message": "[... All messages truncated due to size limit ...]",
+                })
+                .to_string(),
+            );
+        }
+        lines.join("\n")
+    }
 }
 
 /// Custom model configuration from Factory config
@@ -68,82 +361,441 @@ struct FactoryConfig {
 }
 
 /// Server configuration loaded from droid-mcp.config.json
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct ServerConfig {
     #[serde(default)]
     additional_args: Vec<String>,
     timeout_secs: Option<u64>,
     default_auto: Option<String>,
     max_timeout_secs: Option<u64>,
-    #[serde(default)]
+    #[serde(default = "default_true")]
     allow_high_autonomy: bool,
+    min_session_interval_ms: Option<u64>,
+    default_cwd: Option<PathBuf>,
+    #[serde(default)]
+    enable_post_run: bool,
+    #[serde(default)]
+    post_run_command: Vec<String>,
+    post_run_timeout_secs: Option<u64>,
+    #[serde(default)]
+    allowed_models: Vec<String>,
+    #[serde(default)]
+    denied_models: Vec<String>,
+    #[serde(default)]
+    require_agent_messages: bool,
+    #[serde(default)]
+    allow_client_timeout_override: bool,
+    #[serde(default)]
+    allowed_extra_args: Vec<String>,
+    #[serde(default)]
+    allow_any_extra_args: bool,
+    #[serde(default)]
+    retry_on_rate_limit: bool,
+    rate_limit_max_retries: Option<u32>,
+    rate_limit_backoff_secs: Option<u64>,
+    audit_log_path: Option<PathBuf>,
+    #[serde(default)]
+    log_full_prompt: bool,
+    /// Fall back to `low` instead of `high` when `default_auto` is unset
+    #[serde(default)]
+    safe_default_auto: bool,
+    /// Remove ANSI escape sequences from agent messages and droid stderr
+    #[serde(default = "default_true")]
+    strip_ansi: bool,
+    /// Models that accept --reasoning-effort (empty = pass it to every model)
+    #[serde(default)]
+    reasoning_effort_models: Vec<String>,
+    /// Most input files (`file`, `context_file`) one call may attach
+    max_attachments: Option<usize>,
+    /// Largest combined size in bytes of one call's input files
+    max_attachment_bytes: Option<u64>,
+    /// Default for the `require_droid_md` argument
+    #[serde(default)]
+    require_droid_md: bool,
+    /// Lowest autonomy a run may use; lower requests are raised to it
+    min_auto: Option<String>,
+    /// Highest autonomy a run may use; higher defaults are lowered, requests rejected
+    max_auto: Option<String>,
+    /// Set NO_COLOR / FORCE_COLOR=0 for droid so it never emits color codes
+    #[serde(default = "default_true")]
+    force_no_color: bool,
+    /// How long droid may keep running after closing stdout before it is killed
+    exit_grace_secs: Option<u64>,
+    /// Seconds between progress heartbeats for calls that supplied a progress token
+    progress_interval_secs: Option<u64>,
+    /// Appended to truncated agent messages in text output mode
+    truncation_marker: Option<String>,
+    /// Short names clients may pass as `model`, mapped to full model references
+    #[serde(default)]
+    model_aliases: HashMap<String, String>,
+    /// Exact working directories calls may run in (empty = any directory)
+    #[serde(default)]
+    allowed_cwds: Vec<PathBuf>,
+    /// Run every call read-only (DEFAULT), dropping `auto` and `skip_permissions_unsafe`
+    #[serde(default)]
+    force_readonly: bool,
+    /// Reasoning effort used when a call omits `reasoning_effort` and no per-model default applies
+    default_reasoning_effort: Option<String>,
+    /// Per-model reasoning effort used when a call omits `reasoning_effort`, keyed by model
+    /// reference, display name or model id
+    #[serde(default)]
+    model_reasoning_defaults: HashMap<String, String>,
+    /// Fail a modifying run while another is active in the same directory instead of
+    /// waiting for it
+    #[serde(default)]
+    reject_busy_cwd: bool,
+    /// Write `.droid-mcp.lock` into the working directory during modifying runs so that
+    /// other server processes refuse to run there concurrently
+    #[serde(default)]
+    use_lockfile: bool,
+    /// Longest droid stdout line buffered; longer lines are skipped with a warning
+    max_line_bytes: Option<usize>,
+    /// Collapse excess blank lines and trailing whitespace in agent messages
+    #[serde(default = "default_true")]
+    normalize_messages: bool,
+    /// Let clients pick the droid binary per call with the `droid_bin` argument
+    #[serde(default)]
+    allow_client_binary_override: bool,
+    /// droid flag restricting the files it may touch (e.g. "--allow-path"), passed once per
+    /// `scope_paths` entry; without it `scope_paths` is not enforced
+    scope_flag: Option<String>,
+    /// Server environment variables clients may read a prompt from with `prompt_env`
+    /// (empty = `prompt_env` is disabled)
+    #[serde(default)]
+    allowed_prompt_env: Vec<String>,
 }
 
-fn resolve_config_path() -> Option<PathBuf> {
-    if let Ok(env_path) = std::env::var("DROID_MCP_CONFIG_PATH") {
+/// How much the server logs to stderr (stdout carries the MCP protocol)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Only errors and warnings
+    Quiet,
+    /// Per-run model info and droid stderr
+    Normal,
+    /// Additionally parsed line counts and droid argument dumps
+    Verbose,
+}
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+/// Set the stderr logging level (the `--quiet` / `--verbose` CLI flags)
+pub fn set_verbosity(verbosity: Verbosity) {
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+fn verbosity() -> Verbosity {
+    match VERBOSITY.load(Ordering::Relaxed) {
+        0 => Verbosity::Quiet,
+        1 => Verbosity::Normal,
+        _ => Verbosity::Verbose,
+    }
+}
+
+static CONFIG_PATH_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Use `path` as the server config file (the `--config` CLI flag)
+///
+/// Must be called before the config is first read; later calls are ignored.
+pub fn set_config_path(path: PathBuf) {
+    let _ = CONFIG_PATH_OVERRIDE.set(path);
+}
+
+/// Per-user server config file: `$XDG_CONFIG_HOME/droid-mcp/config.json` when
+/// `XDG_CONFIG_HOME` is set, otherwise under the platform config directory
+/// (`~/.config` on Linux, `~/Library/Application Support` on macOS, `%APPDATA%` on Windows)
+pub fn user_config_path() -> Option<PathBuf> {
+    let xdg = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute());
+    let home = || std::env::var_os("HOME").map(PathBuf::from);
+    let dir = if xdg.is_some() {
+        xdg
+    } else if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home().map(|home| home.join("Library").join("Application Support"))
+    } else {
+        home().map(|home| home.join(".config"))
+    };
+    dir.map(|dir| dir.join("droid-mcp").join("config.json"))
+}
+
+/// Pick the server config path: CLI flag, then `DROID_MCP_CONFIG_PATH`, then
+/// `droid-mcp.config.json` in `cwd`, then `user_config` when only that one exists
+pub fn select_config_path(
+    cli_path: Option<&std::path::Path>,
+    env_path: Option<&str>,
+    cwd: Option<&std::path::Path>,
+    user_config: Option<&std::path::Path>,
+) -> Option<PathBuf> {
+    if let Some(path) = cli_path.filter(|p| !p.as_os_str().is_empty()) {
+        return Some(path.to_path_buf());
+    }
+
+    if let Some(env_path) = env_path {
         let trimmed = env_path.trim();
         if !trimmed.is_empty() {
             return Some(PathBuf::from(trimmed));
         }
     }
 
-    std::env::current_dir()
-        .ok()
-        .map(|cwd| cwd.join("droid-mcp.config.json"))
+    let cwd_path = cwd.map(|cwd| cwd.join("droid-mcp.config.json"));
+    if cwd_path.as_deref().is_some_and(std::path::Path::is_file) {
+        return cwd_path;
+    }
+    match user_config.filter(|path| path.is_file()) {
+        Some(path) => Some(path.to_path_buf()),
+        // Neither exists: keep the working directory path so defaults apply
+        None => cwd_path,
+    }
 }
 
-fn load_server_config() -> ServerConfig {
-    let mut cfg = ServerConfig {
-        additional_args: Vec::new(),
-        timeout_secs: None,
-        default_auto: None,
-        max_timeout_secs: None,
-        allow_high_autonomy: true,  // Default to true for high autonomy
-    };
+fn resolve_config_path() -> Option<PathBuf> {
+    let env_path = std::env::var("DROID_MCP_CONFIG_PATH").ok();
+    let cwd = std::env::current_dir().ok();
+    let user_config = user_config_path();
+    select_config_path(
+        CONFIG_PATH_OVERRIDE.get().map(PathBuf::as_path),
+        env_path.as_deref(),
+        cwd.as_deref(),
+        user_config.as_deref(),
+    )
+}
 
+fn default_true() -> bool {
+    true
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            additional_args: Vec::new(),
+            timeout_secs: None,
+            default_auto: None,
+            max_timeout_secs: None,
+            allow_high_autonomy: true, // Default to true for high autonomy
+            min_session_interval_ms: None,
+            default_cwd: None,
+            enable_post_run: false,
+            post_run_command: Vec::new(),
+            post_run_timeout_secs: None,
+            allowed_models: Vec::new(),
+            denied_models: Vec::new(),
+            require_agent_messages: false,
+            allow_client_timeout_override: false,
+            allowed_extra_args: Vec::new(),
+            allow_any_extra_args: false,
+            retry_on_rate_limit: false,
+            rate_limit_max_retries: None,
+            rate_limit_backoff_secs: None,
+            audit_log_path: None,
+            log_full_prompt: false,
+            safe_default_auto: false,
+            strip_ansi: true,
+            reasoning_effort_models: Vec::new(),
+            max_attachments: None,
+            max_attachment_bytes: None,
+            require_droid_md: false,
+            min_auto: None,
+            max_auto: None,
+            force_no_color: true,
+            exit_grace_secs: None,
+            progress_interval_secs: None,
+            truncation_marker: None,
+            model_aliases: HashMap::new(),
+            allowed_cwds: Vec::new(),
+            force_readonly: false,
+            default_reasoning_effort: None,
+            model_reasoning_defaults: HashMap::new(),
+            reject_busy_cwd: false,
+            use_lockfile: false,
+            max_line_bytes: None,
+            normalize_messages: true,
+            allow_client_binary_override: false,
+            scope_flag: None,
+            allowed_prompt_env: Vec::new(),
+        }
+    }
+}
+
+/// Read the server config file; a missing file yields the defaults
+fn read_server_config() -> Result<ServerConfig, String> {
     let Some(config_path) = resolve_config_path() else {
-        return cfg;
+        return Ok(ServerConfig::default());
     };
 
     if !config_path.is_file() {
-        return cfg;
-    }
-
-    match std::fs::read_to_string(&config_path) {
-        Ok(raw) => match serde_json::from_str::<ServerConfig>(&raw) {
-            Ok(parsed) => {
-                let mut cleaned = parsed;
-                cleaned.additional_args = cleaned
-                    .additional_args
-                    .into_iter()
-                    .map(|s| s.trim().to_string())
-                    .filter(|s| !s.is_empty())
-                    .collect();
-                cfg = cleaned;
-            }
-            Err(err) => {
-                eprintln!(
-                    "droid-mcp-rs: failed to parse config {}: {}",
-                    config_path.display(),
-                    err
-                );
-            }
-        },
-        Err(err) => {
-            eprintln!(
-                "droid-mcp-rs: failed to read config {}: {}",
-                config_path.display(),
-                err
-            );
-        }
+        return Ok(ServerConfig::default());
+    }
+
+    let raw = std::fs::read_to_string(&config_path)
+        .map_err(|err| format!("failed to read config {}: {}", config_path.display(), err))?;
+    let mut cfg = serde_json::from_str::<ServerConfig>(&raw)
+        .map_err(|err| format!("failed to parse config {}: {}", config_path.display(), err))?;
+    cfg.additional_args = cfg
+        .additional_args
+        .into_iter()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+    Ok(cfg)
+}
+
+/// Default config with the effective built-in limits spelled out, for `--generate-config`
+fn generated_server_config() -> ServerConfig {
+    ServerConfig {
+        timeout_secs: Some(DEFAULT_TIMEOUT_SECS),
+        max_timeout_secs: Some(MAX_TIMEOUT_SECS),
+        post_run_timeout_secs: Some(DEFAULT_POST_RUN_TIMEOUT_SECS),
+        exit_grace_secs: Some(DEFAULT_EXIT_GRACE_SECS),
+        max_line_bytes: Some(DEFAULT_MAX_LINE_BYTES),
+        rate_limit_max_retries: Some(DEFAULT_RATE_LIMIT_MAX_RETRIES),
+        rate_limit_backoff_secs: Some(DEFAULT_RATE_LIMIT_BACKOFF_SECS),
+        ..ServerConfig::default()
+    }
+}
+
+/// Pretty JSON of a default config listing every recognized field
+pub fn default_config_json() -> String {
+    serde_json::to_string_pretty(&generated_server_config())
+        .expect("server config serializes to JSON")
+}
+
+/// Pretty JSON of the server config currently in effect
+pub fn effective_config_json() -> String {
+    serde_json::to_string_pretty(&*server_config()).expect("server config serializes to JSON")
+}
+
+/// Write the default config to `path`, refusing to replace an existing file unless `force`
+pub fn write_default_config(path: &std::path::Path, force: bool) -> Result<()> {
+    use std::io::Write;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true);
+    if force {
+        options.create(true).truncate(true);
+    } else {
+        options.create_new(true);
     }
+    let mut file = options.open(path).with_context(|| {
+        if path.exists() && !force {
+            format!(
+                "{} already exists; pass --force to overwrite it",
+                path.display()
+            )
+        } else {
+            format!("Failed to create {}", path.display())
+        }
+    })?;
+    writeln!(file, "{}", default_config_json())
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
 
+fn load_server_config() -> ServerConfig {
+    let cfg = read_server_config().unwrap_or_else(|err| {
+        eprintln!("droid-mcp-rs: {}", err);
+        ServerConfig::default()
+    });
+    for warning in dangling_aliases(&cfg, &factory_config()) {
+        eprintln!("droid-mcp-rs: {}", warning);
+    }
     cfg
 }
 
-fn server_config() -> &'static ServerConfig {
-    static SERVER_CONFIG: OnceLock<ServerConfig> = OnceLock::new();
-    SERVER_CONFIG.get_or_init(load_server_config)
+static SERVER_CONFIG: RwLock<Option<Arc<ServerConfig>>> = RwLock::new(None);
+static FACTORY_CONFIG: RwLock<Option<Arc<FactoryConfig>>> = RwLock::new(None);
+
+/// Return the cached value in `slot`, loading it on first use
+fn cached<T>(slot: &RwLock<Option<Arc<T>>>, load: fn() -> T) -> Arc<T> {
+    if let Some(value) = slot.read().unwrap_or_else(PoisonError::into_inner).as_ref() {
+        return Arc::clone(value);
+    }
+    let mut slot = slot.write().unwrap_or_else(PoisonError::into_inner);
+    Arc::clone(slot.get_or_insert_with(|| Arc::new(load())))
+}
+
+/// Current server config; callers hold on to the returned snapshot for a whole run
+fn server_config() -> Arc<ServerConfig> {
+    cached(&SERVER_CONFIG, load_server_config)
+}
+
+/// What changed in a [`reload_config`]
+#[derive(Debug, Clone, Serialize)]
+pub struct ReloadSummary {
+    pub custom_models_before: usize,
+    pub custom_models_after: usize,
+    pub server_config_changed: bool,
+}
+
+impl std::fmt::Display for ReloadSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "custom models {} -> {}, server config {}",
+            self.custom_models_before,
+            self.custom_models_after,
+            if self.server_config_changed {
+                "changed"
+            } else {
+                "unchanged"
+            }
+        )
+    }
+}
+
+/// Re-read the server and Factory configs and swap them in for subsequent runs
+///
+/// On any read or parse error both previous configs are kept. In-flight runs keep
+/// the snapshot they started with.
+pub fn reload_config() -> Result<ReloadSummary, String> {
+    let server = read_server_config()?;
+    let factory = read_factory_config()?;
+
+    let previous_server = server_config();
+    let summary = ReloadSummary {
+        custom_models_before: factory_config().custom_models.len(),
+        custom_models_after: factory.custom_models.len(),
+        server_config_changed: serde_json::to_value(&*previous_server).ok()
+            != serde_json::to_value(&server).ok(),
+    };
+
+    for warning in dangling_aliases(&server, &factory) {
+        eprintln!("droid-mcp-rs: {}", warning);
+    }
+
+    *SERVER_CONFIG
+        .write()
+        .unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(server));
+    *FACTORY_CONFIG
+        .write()
+        .unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(factory));
+    FACTORY_CONFIG_WARNING
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take();
+    Ok(summary)
+}
+
+/// Reload the configuration whenever the process receives SIGHUP
+///
+/// Must be called from within a Tokio runtime.
+#[cfg(unix)]
+pub fn spawn_reload_on_sighup() -> std::io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = signal(SignalKind::hangup())?;
+    tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            match reload_config() {
+                Ok(summary) => eprintln!("droid-mcp-rs: configuration reloaded ({})", summary),
+                Err(err) => eprintln!(
+                    "droid-mcp-rs: configuration reload failed, keeping previous config: {}",
+                    err
+                ),
+            }
+        }
+    });
+    Ok(())
 }
 
 pub fn default_additional_args() -> Vec<String> {
@@ -151,22 +803,80 @@ pub fn default_additional_args() -> Vec<String> {
 }
 
 pub fn default_timeout_secs() -> u64 {
-    static CACHED_TIMEOUT: OnceLock<u64> = OnceLock::new();
-    *CACHED_TIMEOUT.get_or_init(|| {
-        let cfg = server_config();
-        match cfg.timeout_secs {
-            Some(t) if t > 0 && t <= MAX_TIMEOUT_SECS => t,
-            Some(t) if t > MAX_TIMEOUT_SECS => MAX_TIMEOUT_SECS,
-            _ => DEFAULT_TIMEOUT_SECS,
-        }
-    })
+    configured_timeout_secs(&server_config())
+}
+
+fn configured_timeout_secs(cfg: &ServerConfig) -> u64 {
+    match cfg.timeout_secs {
+        Some(t) if t > 0 && t <= MAX_TIMEOUT_SECS => t,
+        Some(t) if t > MAX_TIMEOUT_SECS => MAX_TIMEOUT_SECS,
+        _ => DEFAULT_TIMEOUT_SECS,
+    }
+}
+
+/// Inline marker appended to truncated agent messages in text output mode
+///
+/// Structured output reports truncation through `message_truncated` instead.
+pub fn truncation_marker() -> String {
+    server_config()
+        .truncation_marker
+        .clone()
+        .unwrap_or_else(|| DEFAULT_TRUNCATION_MARKER.to_string())
+}
+
+/// Minimum interval between calls that reuse the same session (None = disabled)
+pub fn min_session_interval() -> Option<std::time::Duration> {
+    server_config()
+        .min_session_interval_ms
+        .filter(|ms| *ms > 0)
+        .map(std::time::Duration::from_millis)
+}
+
+/// Autonomy levels clients may request given `allow_high_autonomy` and `max_auto`
+pub fn allowed_auto_levels() -> Vec<&'static str> {
+    let cfg = server_config();
+    let max_rank =
+        configured_auto_level(&cfg.max_auto).map_or(usize::MAX, |max| auto_rank(Some(max)));
+    AUTO_LEVELS
+        .into_iter()
+        .filter(|level| cfg.allow_high_autonomy || *level != "high")
+        .filter(|level| auto_rank(Some(level)) <= max_rank)
+        .collect()
+}
+
+/// Limits on the input files a single call may attach (None = unlimited)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AttachmentLimits {
+    pub max_files: Option<usize>,
+    pub max_bytes: Option<u64>,
+}
+
+/// Attachment limits from `max_attachments` / `max_attachment_bytes`
+pub fn attachment_limits() -> AttachmentLimits {
+    let cfg = server_config();
+    AttachmentLimits {
+        max_files: cfg.max_attachments,
+        max_bytes: cfg.max_attachment_bytes,
+    }
+}
+
+/// Working directory to use when the client omits `cwd`
+pub fn default_working_dir() -> Option<PathBuf> {
+    server_config()
+        .default_cwd
+        .clone()
+        .filter(|p| !p.as_os_str().is_empty())
 }
 
 fn resolve_factory_config_path() -> Option<PathBuf> {
     #[cfg(windows)]
     {
         if let Ok(user_profile) = std::env::var("USERPROFILE") {
-            return Some(PathBuf::from(user_profile).join(".factory").join("config.json"));
+            return Some(
+                PathBuf::from(user_profile)
+                    .join(".factory")
+                    .join("config.json"),
+            );
         }
     }
 
@@ -180,76 +890,222 @@ fn resolve_factory_config_path() -> Option<PathBuf> {
     None
 }
 
-fn load_factory_config() -> FactoryConfig {
-    let mut cfg = FactoryConfig {
+/// Read the Factory config file; a missing file yields an empty config
+fn read_factory_config() -> Result<FactoryConfig, String> {
+    let empty = || FactoryConfig {
         custom_models: Vec::new(),
     };
 
     let Some(config_path) = resolve_factory_config_path() else {
-        return cfg;
+        return Ok(empty());
     };
 
     if !config_path.is_file() {
-        return cfg;
+        return Ok(empty());
     }
 
-    match std::fs::read_to_string(&config_path) {
-        Ok(raw) => match serde_json::from_str::<FactoryConfig>(&raw) {
-            Ok(parsed) => {
-                cfg = parsed;
-            }
-            Err(err) => {
-                eprintln!(
-                    "droid-mcp-rs: failed to parse Factory config {}: {}",
-                    config_path.display(),
-                    err
-                );
-            }
-        },
-        Err(err) => {
-            eprintln!(
-                "droid-mcp-rs: failed to read Factory config {}: {}",
-                config_path.display(),
-                err
-            );
+    let raw = std::fs::read_to_string(&config_path).map_err(|err| {
+        format!(
+            "failed to read Factory config {}: {}",
+            config_path.display(),
+            err
+        )
+    })?;
+    serde_json::from_str::<FactoryConfig>(&raw).map_err(|err| {
+        format!(
+            "failed to parse Factory config {}: {}",
+            config_path.display(),
+            err
+        )
+    })
+}
+
+/// Why the Factory config failed to load, until the next run reports it
+static FACTORY_CONFIG_WARNING: Mutex<Option<String>> = Mutex::new(None);
+
+fn load_factory_config() -> FactoryConfig {
+    read_factory_config().unwrap_or_else(|err| {
+        eprintln!("droid-mcp-rs: {}", err);
+        *FACTORY_CONFIG_WARNING
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(format!(
+            "{}; no custom models are available and droid falls back to its own default model",
+            err
+        ));
+        FactoryConfig {
+            custom_models: Vec::new(),
         }
-    }
+    })
+}
 
-    cfg
+fn factory_config() -> Arc<FactoryConfig> {
+    cached(&FACTORY_CONFIG, load_factory_config)
 }
 
-fn factory_config() -> &'static FactoryConfig {
-    static FACTORY_CONFIG: OnceLock<FactoryConfig> = OnceLock::new();
-    FACTORY_CONFIG.get_or_init(load_factory_config)
+/// A custom model from Factory config as presented to clients
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelEntry {
+    /// Display name, suffixed with `#n` when several models share it
+    pub label: String,
+    /// Canonical reference to pass as `model` ("custom:Display-Name-index")
+    pub reference: String,
 }
 
-/// List all available custom models from Factory config
-pub fn list_custom_models() -> Vec<String> {
+/// Canonical `custom:` reference for the custom model at `idx`
+fn custom_model_reference(model: &CustomModel, idx: usize) -> String {
+    format!(
+        "custom:{}-{}",
+        model.model_display_name.replace(' ', "-"),
+        idx
+    )
+}
+
+/// List all available custom models from Factory config, in config file order
+pub fn list_custom_models() -> Vec<ModelEntry> {
     let cfg = factory_config();
+
+    let mut name_counts: HashMap<&str, usize> = HashMap::new();
+    for model in &cfg.custom_models {
+        *name_counts
+            .entry(model.model_display_name.as_str())
+            .or_default() += 1;
+    }
+
+    let mut occurrences: HashMap<&str, usize> = HashMap::new();
     cfg.custom_models
         .iter()
         .enumerate()
         .map(|(idx, model)| {
-            format!(
-                "{} (custom:{}-{})",
-                model.model_display_name,
-                model.model_display_name.replace(' ', "-"),
-                idx
-            )
+            let name = model.model_display_name.as_str();
+            let label = if name_counts[name] > 1 {
+                let occurrence = occurrences.entry(name).or_default();
+                *occurrence += 1;
+                format!("{} #{}", name, occurrence)
+            } else {
+                name.to_string()
+            };
+            ModelEntry {
+                label,
+                reference: custom_model_reference(model, idx),
+            }
         })
         .collect()
 }
 
 /// Get the default autonomy level to use
-fn get_default_auto() -> Option<String> {
-    let cfg = server_config();
+fn get_default_auto(cfg: &ServerConfig) -> Option<String> {
     if let Some(ref default_auto) = cfg.default_auto {
         if !default_auto.trim().is_empty() {
             return Some(default_auto.clone());
         }
     }
-    // Default to high if not configured
-    Some("high".to_string())
+    // Default to high if not configured, unless the safe default is requested
+    Some(if cfg.safe_default_auto { "low" } else { "high" }.to_string())
+}
+
+const AUTO_LEVELS: [&str; 3] = ["low", "medium", "high"];
+
+/// Rank of an autonomy level: DEFAULT (read-only) < low < medium < high
+fn auto_rank(level: Option<&str>) -> usize {
+    level
+        .and_then(|level| AUTO_LEVELS.iter().position(|l| *l == level))
+        .map_or(0, |idx| idx + 1)
+}
+
+/// Drop all autonomy from `opts` under `force_readonly`, so droid runs at DEFAULT (read-only)
+///
+/// Takes precedence over `min_auto` / `max_auto`. Only overriding a client's explicit
+/// choice is reported; a dropped server default is not.
+fn apply_force_readonly(opts: &mut Options, auto_from_default: bool, warnings: &mut Vec<String>) {
+    let mut overridden = Vec::new();
+    if let Some(auto) = opts.auto.take().filter(|_| !auto_from_default) {
+        overridden.push(format!("auto '{}'", auto));
+    }
+    if let Some(spec_auto) = opts.spec_auto.take() {
+        overridden.push(format!("spec_auto '{}'", spec_auto));
+    }
+    if std::mem::take(&mut opts.skip_permissions_unsafe) {
+        overridden.push("skip_permissions_unsafe".to_string());
+    }
+    if !overridden.is_empty() {
+        warnings.push(format!(
+            "force_readonly is set; {} overridden to DEFAULT (read-only)",
+            overridden.join(", ")
+        ));
+    }
+}
+
+/// A `min_auto` / `max_auto` config value, ignoring unset or unknown levels
+fn configured_auto_level(level: &Option<String>) -> Option<&str> {
+    level
+        .as_deref()
+        .map(str::trim)
+        .filter(|level| AUTO_LEVELS.contains(level))
+}
+
+/// Keep `opts.auto` within the configured `min_auto` / `max_auto` range
+///
+/// A server default above `max_auto` is lowered to it, while an explicit client request
+/// above it is rejected; anything below `min_auto` is raised. `spec_auto` above `max_auto`
+/// is rejected as well.
+fn apply_auto_bounds(
+    cfg: &ServerConfig,
+    opts: &mut Options,
+    auto_from_default: bool,
+    warnings: &mut Vec<String>,
+) -> Result<()> {
+    if let Some(max) = configured_auto_level(&cfg.max_auto) {
+        if opts.skip_permissions_unsafe {
+            return Err(rejected(format!(
+                "skip_permissions_unsafe exceeds the server max_auto of '{}'",
+                max
+            )));
+        }
+        if auto_rank(opts.auto.as_deref()) > auto_rank(Some(max)) {
+            let requested = opts.auto.clone().unwrap_or_default();
+            if !auto_from_default {
+                return Err(rejected(format!(
+                    "Autonomy level '{}' exceeds the server max_auto of '{}'",
+                    requested, max
+                )));
+            }
+            warnings.push(format!(
+                "Default autonomy '{}' exceeds max_auto; lowered to '{}'",
+                requested, max
+            ));
+            opts.auto = Some(max.to_string());
+        }
+        // Only ever client-supplied, so never silently lowered
+        if let Some(ref spec_auto) = opts.spec_auto {
+            if auto_rank(Some(spec_auto.as_str())) > auto_rank(Some(max)) {
+                return Err(rejected(format!(
+                    "spec_auto level '{}' exceeds the server max_auto of '{}'",
+                    spec_auto, max
+                )));
+            }
+        }
+    }
+
+    if let Some(min) = configured_auto_level(&cfg.min_auto) {
+        if !opts.skip_permissions_unsafe && auto_rank(opts.auto.as_deref()) < auto_rank(Some(min)) {
+            warnings.push(format!(
+                "Autonomy '{}' is below min_auto; raised to '{}'",
+                opts.auto.as_deref().unwrap_or("default"),
+                min
+            ));
+            opts.auto = Some(min.to_string());
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether calls that omit `auto` run at high autonomy
+pub fn default_auto_is_high() -> bool {
+    let cfg = server_config();
+    !cfg.force_readonly
+        && get_default_auto(&cfg).as_deref() == Some("high")
+        && configured_auto_level(&cfg.max_auto).map_or(true, |max| max == "high")
 }
 
 /// Get the default model to use (prefer GPT models, fallback to first custom model)
@@ -262,42 +1118,266 @@ fn get_default_model() -> Option<String> {
         let model_lower = model.model.to_lowercase();
 
         if name_lower.contains("gpt") || model_lower.contains("gpt") {
-            let model_ref = format!(
-                "custom:{}-{}",
-                model.model_display_name.replace(' ', "-"),
-                idx
-            );
-            return Some(model_ref);
+            return Some(custom_model_reference(model, idx));
         }
     }
 
     // Priority 2: Fallback to first custom model
     if let Some(first_model) = cfg.custom_models.first() {
-        let model_ref = format!(
-            "custom:{}-0",
-            first_model.model_display_name.replace(' ', "-")
-        );
-        return Some(model_ref);
+        return Some(custom_model_reference(first_model, 0));
     }
 
     // Priority 3: No custom models - use Factory default
     None
 }
 
-/// Get model display name and details for logging and display
-/// Returns: (model_info for result field, warning for user display)
-fn get_model_info(model_param: &Option<String>) -> (Option<String>, Option<String>) {
-    let cfg = factory_config();
+/// Whether `model` may be sent --reasoning-effort according to `reasoning_effort_models`
+///
+/// An empty list passes every model through, as does `None` (the Factory default),
+/// which cannot be identified.
+pub fn supports_reasoning_effort(model: Option<&str>) -> bool {
+    reasoning_effort_allowed(&server_config(), model)
+}
 
-    let Some(model) = model_param else {
-        // This shouldn't happen since we apply defaults, but handle it anyway
-        if let Some(first_model) = cfg.custom_models.first() {
-            let display = format!(
-                "{} [{}] ({})",
-                first_model.model_display_name, first_model.provider, first_model.model
-            );
-            // No warning - silent default
-            return (Some(display), None);
+fn reasoning_effort_allowed(cfg: &ServerConfig, model: Option<&str>) -> bool {
+    let Some(model) = model else {
+        return true;
+    };
+    cfg.reasoning_effort_models.is_empty()
+        || model_match_names(model)
+            .iter()
+            .any(|name| cfg.reasoning_effort_models.contains(name))
+}
+
+/// Reasoning effort applied when a call omits `reasoning_effort`
+///
+/// Precedence: the per-call value (handled by the caller), then the `model_reasoning_defaults`
+/// entry for `model`, then `default_reasoning_effort`.
+fn configured_reasoning_effort(cfg: &ServerConfig, model: Option<&str>) -> Option<String> {
+    model
+        .and_then(|model| {
+            model_match_names(model)
+                .iter()
+                .find_map(|name| cfg.model_reasoning_defaults.get(name))
+        })
+        .or(cfg.default_reasoning_effort.as_ref())
+        .cloned()
+}
+
+/// Resolve a custom model reference ("custom:Display-Name-0") to its Factory config entry
+fn lookup_custom_model<'a>(cfg: &'a FactoryConfig, model: &str) -> Option<&'a CustomModel> {
+    let rest = model.strip_prefix("custom:")?;
+    // Extract index from the end
+    let (_display_part, idx_str) = rest.rsplit_once('-')?;
+    let idx = idx_str.parse::<usize>().ok()?;
+    cfg.custom_models.get(idx)
+}
+
+/// Names a model can be matched by in config lists: the reference itself,
+/// plus the display name and provider model id for custom models
+fn model_match_names(model: &str) -> Vec<String> {
+    let mut names = vec![model.to_string()];
+    if let Some(custom_model) = lookup_custom_model(&factory_config(), model) {
+        names.push(custom_model.model_display_name.clone());
+        names.push(custom_model.model.clone());
+    }
+    names
+}
+
+/// Replace a `model_aliases` short name with its target; other models pass through
+fn resolve_alias(cfg: &ServerConfig, model: String) -> String {
+    match cfg.model_aliases.get(&model) {
+        Some(target) => target.clone(),
+        None => model,
+    }
+}
+
+/// Warnings for `model_aliases` entries whose `custom:` target is not in the Factory config
+///
+/// Other targets are passed to droid as-is and cannot be checked here.
+fn dangling_aliases(cfg: &ServerConfig, factory: &FactoryConfig) -> Vec<String> {
+    let mut warnings: Vec<String> = cfg
+        .model_aliases
+        .iter()
+        .filter(|(_, target)| {
+            target.starts_with("custom:") && lookup_custom_model(factory, target).is_none()
+        })
+        .map(|(alias, target)| {
+            format!(
+                "model alias '{}' points to '{}', which is not a configured custom model",
+                alias, target
+            )
+        })
+        .collect();
+    warnings.sort();
+    warnings
+}
+
+/// Warnings about `model_aliases` targets that do not resolve with the current config
+pub fn model_alias_warnings() -> Vec<String> {
+    dangling_aliases(&server_config(), &factory_config())
+}
+
+/// Expand a model alias, then apply the default model when the client did not pick one
+pub fn resolve_model(model: Option<String>) -> Option<String> {
+    let cfg = server_config();
+    model
+        .map(|model| resolve_alias(&cfg, model))
+        .or_else(get_default_model)
+}
+
+const SKIP_PERMISSIONS_DISABLED: &str = "skip_permissions_unsafe is disabled because allow_high_autonomy is false in configuration; skipping permission checks grants even more access than high autonomy. Set allow_high_autonomy=true to enable.";
+
+/// Whether a call may pass `skip_permissions_unsafe` under the server config
+///
+/// Skipping permission checks is at least as powerful as `auto=high`, so it is only
+/// allowed while high autonomy is. Under `force_readonly` it is dropped with a warning instead.
+pub fn check_skip_permissions_allowed() -> Result<(), String> {
+    let cfg = server_config();
+    if cfg.allow_high_autonomy || cfg.force_readonly {
+        Ok(())
+    } else {
+        Err(SKIP_PERMISSIONS_DISABLED.to_string())
+    }
+}
+
+const BINARY_OVERRIDE_DISABLED: &str = "droid_bin is disabled because allow_client_binary_override is false in configuration; a client-chosen binary runs arbitrary code. Set allow_client_binary_override=true to enable.";
+
+/// `check_binary_override_allowed` against a config snapshot
+fn check_binary_override_with(
+    cfg: &ServerConfig,
+    droid_bin: &std::path::Path,
+) -> Result<(), String> {
+    if !cfg.allow_client_binary_override {
+        return Err(BINARY_OVERRIDE_DISABLED.to_string());
+    }
+    if !droid_bin.is_absolute() {
+        return Err(format!(
+            "droid_bin must be an absolute path, got '{}'",
+            droid_bin.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Whether a call may run `droid_bin` instead of the resolved droid binary
+///
+/// Off by default: picking the binary lets a client run any executable on the server.
+pub fn check_binary_override_allowed(droid_bin: &std::path::Path) -> Result<(), String> {
+    check_binary_override_with(&server_config(), droid_bin)
+}
+
+/// Check `scope_paths` entries: relative to `working_dir`, without `..`, and (for paths that
+/// already exist) not resolving outside it through symlinks
+pub fn validate_scope_paths(working_dir: &std::path::Path, paths: &[String]) -> Result<(), String> {
+    let root = working_dir
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve working directory for scope_paths: {}", e))?;
+    for entry in paths {
+        if entry.trim().is_empty() {
+            return Err("scope_paths entries must not be empty".to_string());
+        }
+        let path = std::path::Path::new(entry);
+        if path.is_absolute() || entry.starts_with('/') {
+            return Err(format!(
+                "scope_paths entry '{}' must be relative to the working directory",
+                entry
+            ));
+        }
+        if path
+            .components()
+            .any(|c| matches!(c, std::path::Component::ParentDir))
+        {
+            return Err(format!(
+                "scope_paths entry '{}' must not contain '..'",
+                entry
+            ));
+        }
+        if let Ok(resolved) = root.join(path).canonicalize() {
+            if !resolved.starts_with(&root) {
+                return Err(format!(
+                    "scope_paths entry '{}' resolves outside the working directory",
+                    entry
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check a canonicalized working directory against the `allowed_cwds` config list
+///
+/// Entries must match exactly after canonicalization; subdirectories of an entry are
+/// not allowed. Entries that cannot be canonicalized (e.g. removed directories) never match.
+pub fn check_cwd_allowed(working_dir: &std::path::Path) -> Result<(), String> {
+    let cfg = server_config();
+    if cfg.allowed_cwds.is_empty()
+        || cfg
+            .allowed_cwds
+            .iter()
+            .filter_map(|allowed| allowed.canonicalize().ok())
+            .any(|allowed| allowed == working_dir)
+    {
+        return Ok(());
+    }
+    Err(format!(
+        "Working directory {} is not in allowed_cwds",
+        working_dir.display()
+    ))
+}
+
+/// Check a resolved model against the `allowed_models` / `denied_models` config lists
+///
+/// `None` stands for the Factory default model, which only passes when no allow list is set.
+pub fn check_model_allowed(model: Option<&str>) -> Result<(), String> {
+    check_model_allowed_with(&server_config(), model)
+}
+
+fn check_model_allowed_with(cfg: &ServerConfig, model: Option<&str>) -> Result<(), String> {
+    let Some(model) = model else {
+        if cfg.allowed_models.is_empty() {
+            return Ok(());
+        }
+        return Err(
+            "The Factory default model is not in allowed_models; specify an allowed model"
+                .to_string(),
+        );
+    };
+
+    let names = model_match_names(model);
+    if names.iter().any(|name| cfg.denied_models.contains(name)) {
+        return Err(format!(
+            "Model '{}' is denied by server configuration",
+            model
+        ));
+    }
+
+    if !cfg.allowed_models.is_empty() && !names.iter().any(|name| cfg.allowed_models.contains(name))
+    {
+        return Err(format!(
+            "Model '{}' is not allowed. Allowed models: {}",
+            model,
+            cfg.allowed_models.join(", ")
+        ));
+    }
+
+    Ok(())
+}
+
+/// Get model display name and details for logging and display
+/// Returns: (model_info for result field, warning for user display)
+fn get_model_info(model_param: &Option<String>) -> (Option<String>, Option<String>) {
+    let cfg = factory_config();
+
+    let Some(model) = model_param else {
+        // This shouldn't happen since we apply defaults, but handle it anyway
+        if let Some(first_model) = cfg.custom_models.first() {
+            let display = format!(
+                "{} [{}] ({})",
+                first_model.model_display_name, first_model.provider, first_model.model
+            );
+            // No warning - silent default
+            return (Some(display), None);
         } else {
             return (
                 Some("Factory default model".to_string()),
@@ -308,26 +1388,19 @@ fn get_model_info(model_param: &Option<String>) -> (Option<String>, Option<Strin
 
     // Check if it's a custom model reference
     if model.starts_with("custom:") {
-        // Parse custom model reference: "custom:Display-Name-0"
-        if let Some((_, rest)) = model.split_once(':') {
-            // Extract index from the end
-            if let Some((_display_part, idx_str)) = rest.rsplit_once('-') {
-                if let Ok(idx) = idx_str.parse::<usize>() {
-                    if let Some(custom_model) = cfg.custom_models.get(idx) {
-                        let display = format!(
-                            "{} [{}] ({})",
-                            custom_model.model_display_name,
-                            custom_model.provider,
-                            custom_model.model
-                        );
-                        // No warning - silent custom model
-                        return (Some(display), None);
-                    }
-                }
-            }
+        if let Some(custom_model) = lookup_custom_model(&cfg, model) {
+            let display = format!(
+                "{} [{}] ({})",
+                custom_model.model_display_name, custom_model.provider, custom_model.model
+            );
+            // No warning - silent custom model
+            return (Some(display), None);
         }
         // Failed to parse custom model reference
-        let warning = format!("⚠️  Invalid custom model reference: '{}'. Using first available model.", model);
+        let warning = format!(
+            "⚠️  Invalid custom model reference: '{}'. Using first available model.",
+            model
+        );
         if let Some(first_model) = cfg.custom_models.first() {
             let display = format!(
                 "{} [{}] ({})",
@@ -346,22 +1419,65 @@ fn get_model_info(model_param: &Option<String>) -> (Option<String>, Option<Strin
     (Some(display), None)
 }
 
-/// Resolves the droid binary path
-/// droid is typically in PATH (installed in ~/bin or C:\Users\<user>\bin)
-/// Can be overridden with DROID_BIN environment variable
-#[cfg(windows)]
-fn resolve_droid_bin() -> String {
-    if let Ok(val) = std::env::var("DROID_BIN") {
-        let trimmed = val.trim();
-        if !trimmed.is_empty() {
-            return trimmed.to_string();
-        }
+/// Model info for the given options; raw passthrough models are echoed verbatim
+fn options_model_info(opts: &Options) -> (Option<String>, Option<String>) {
+    match opts.raw_model {
+        Some(ref raw) => (Some(format!("Model: {} (raw passthrough)", raw)), None),
+        None => get_model_info(&opts.model),
     }
-    // droid is in PATH, default installation: C:\Users\<user>\bin\droid.exe
-    "droid.exe".to_string()
+}
+
+/// Bare droid binary name looked up on PATH
+#[cfg(windows)]
+const DROID_BIN_NAME: &str = "droid.exe";
+#[cfg(not(windows))]
+const DROID_BIN_NAME: &str = "droid";
+
+/// Well-known install locations probed when droid is not on PATH
+#[cfg(windows)]
+fn droid_install_candidates() -> Vec<PathBuf> {
+    // Default installation: C:\Users\<user>\bin\droid.exe
+    std::env::var_os("USERPROFILE")
+        .map(|profile| vec![PathBuf::from(profile).join("bin").join(DROID_BIN_NAME)])
+        .unwrap_or_default()
 }
 
 #[cfg(not(windows))]
+fn droid_install_candidates() -> Vec<PathBuf> {
+    // Default installation: ~/bin/droid (no extension), or ~/.local/bin/droid
+    let Some(home) = std::env::var_os("HOME") else {
+        return Vec::new();
+    };
+    let home = PathBuf::from(home);
+    vec![
+        home.join("bin").join(DROID_BIN_NAME),
+        home.join(".local").join("bin").join(DROID_BIN_NAME),
+    ]
+}
+
+#[cfg(unix)]
+fn is_executable(path: &std::path::Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path)
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &std::path::Path) -> bool {
+    path.is_file()
+}
+
+fn is_on_path(name: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| is_executable(&dir.join(name))))
+        .unwrap_or(false)
+}
+
+/// Resolves the droid binary path
+/// droid is typically in PATH (installed in ~/bin or C:\Users\<user>\bin)
+/// Can be overridden with DROID_BIN environment variable. When droid is not on
+/// PATH, well-known install locations are probed before falling back to the bare name.
 fn resolve_droid_bin() -> String {
     if let Ok(val) = std::env::var("DROID_BIN") {
         let trimmed = val.trim();
@@ -369,21 +1485,53 @@ fn resolve_droid_bin() -> String {
             return trimmed.to_string();
         }
     }
-    // droid is in PATH, default installation: ~/bin/droid (no extension)
-    "droid".to_string()
+
+    if is_on_path(DROID_BIN_NAME) {
+        return DROID_BIN_NAME.to_string();
+    }
+
+    if let Some(found) = droid_install_candidates()
+        .into_iter()
+        .find(|candidate| is_executable(candidate))
+    {
+        eprintln!(
+            "droid-mcp-rs: droid not found on PATH, using {}",
+            found.display()
+        );
+        return found.to_string_lossy().into_owned();
+    }
+
+    // Let spawn report the failure against the bare name
+    DROID_BIN_NAME.to_string()
+}
+
+fn droid_md_path(working_dir: &std::path::Path) -> PathBuf {
+    working_dir.join("DROID.md")
 }
 
 async fn read_droid_md(working_dir: &std::path::Path) -> (Option<String>, Option<String>) {
-    let droid_path = working_dir.join("DROID.md");
+    let droid_path = droid_md_path(working_dir);
 
     if !droid_path.exists() {
         return (None, None);
     }
 
-    let metadata = match tokio::fs::metadata(&droid_path).await {
+    read_text_limited(&droid_path, "DROID.md", MAX_DROID_MD_SIZE).await
+}
+
+/// Read a UTF-8 text file for the prompt, truncating it to `max_size` bytes
+///
+/// Returns the content (None when empty, unreadable or too large) and a warning naming
+/// the file as `label`.
+async fn read_text_limited(
+    path: &std::path::Path,
+    label: &str,
+    max_size: usize,
+) -> (Option<String>, Option<String>) {
+    let metadata = match tokio::fs::metadata(path).await {
         Ok(m) => m,
         Err(e) => {
-            let warning = format!("Failed to read DROID.md metadata: {}", e);
+            let warning = format!("Failed to read {} metadata: {}", label, e);
             return (None, Some(warning));
         }
     };
@@ -392,17 +1540,17 @@ async fn read_droid_md(working_dir: &std::path::Path) -> (Option<String>, Option
 
     if file_size > ABSOLUTE_MAX_SIZE {
         let warning = format!(
-            "DROID.md is {} bytes, exceeding the absolute maximum of {} bytes and will be skipped.",
-            file_size, ABSOLUTE_MAX_SIZE
+            "{} is {} bytes, exceeding the absolute maximum of {} bytes and will be skipped.",
+            label, file_size, ABSOLUTE_MAX_SIZE
         );
         return (None, Some(warning));
     }
 
-    let bytes_to_read = (file_size as usize).min(MAX_DROID_MD_SIZE + 4);
-    let file = match tokio::fs::File::open(&droid_path).await {
+    let bytes_to_read = (file_size as usize).min(max_size + 4);
+    let file = match tokio::fs::File::open(path).await {
         Ok(f) => f,
         Err(e) => {
-            let warning = format!("Failed to open DROID.md: {}", e);
+            let warning = format!("Failed to open {}: {}", label, e);
             return (None, Some(warning));
         }
     };
@@ -413,7 +1561,7 @@ async fn read_droid_md(working_dir: &std::path::Path) -> (Option<String>, Option
         .read_to_end(&mut content)
         .await
     {
-        let warning = format!("Failed to read DROID.md: {}", e);
+        let warning = format!("Failed to read {}: {}", label, e);
         return (None, Some(warning));
     }
 
@@ -429,256 +1577,1479 @@ async fn read_droid_md(working_dir: &std::path::Path) -> (Option<String>, Option
         }
     }
 
-    if content.len() > MAX_DROID_MD_SIZE {
-        let mut end = MAX_DROID_MD_SIZE;
+    if content.len() > max_size {
+        let mut end = max_size;
         while end > 0 {
             if let Ok(valid_str) = std::str::from_utf8(&content[..end]) {
                 let warning = format!(
-                    "DROID.md is {} bytes, exceeding the {} byte limit and was truncated to {} bytes.",
-                    file_size, MAX_DROID_MD_SIZE, end
+                    "{} is {} bytes, exceeding the {} byte limit and was truncated to {} bytes.",
+                    label, file_size, max_size, end
                 );
                 return (Some(valid_str.to_string()), Some(warning));
             }
             end -= 1;
         }
-        let warning = "DROID.md contains invalid UTF-8 and was skipped.".to_string();
+        let warning = format!("{} contains invalid UTF-8 and was skipped.", label);
         (None, Some(warning))
     } else {
         match String::from_utf8(content) {
             Ok(s) => (Some(s), None),
             Err(_) => {
-                let warning = "DROID.md contains invalid UTF-8 and was skipped.".to_string();
+                let warning = format!("{} contains invalid UTF-8 and was skipped.", label);
                 (None, Some(warning))
             }
         }
     }
 }
 
+/// DROID.md followed by the `system_prompt` argument, together capped at the DROID.md size limit
+///
+/// The argument is truncated (with a warning) to whatever room DROID.md leaves.
+fn combine_system_prompt(
+    droid_md: Option<String>,
+    system_prompt: Option<&str>,
+    warnings: &mut Vec<String>,
+) -> Option<String> {
+    let Some(extra) = system_prompt.filter(|s| !s.trim().is_empty()) else {
+        return droid_md;
+    };
+    let mut combined = droid_md.unwrap_or_default();
+    let separator = if combined.is_empty() { "" } else { "\n\n" };
+    let room = MAX_DROID_MD_SIZE.saturating_sub(combined.len() + separator.len());
+    if room == 0 {
+        warnings.push(format!(
+            "system_prompt skipped: DROID.md already fills the {}-byte system prompt limit",
+            MAX_DROID_MD_SIZE
+        ));
+        return Some(combined).filter(|s| !s.is_empty());
+    }
+    let mut end = extra.len().min(room);
+    while !extra.is_char_boundary(end) {
+        end -= 1;
+    }
+    if end < extra.len() {
+        warnings.push(format!(
+            "system_prompt truncated to {} bytes to keep the system prompt within {} bytes",
+            end, MAX_DROID_MD_SIZE
+        ));
+    }
+    combined.push_str(separator);
+    combined.push_str(&extra[..end]);
+    Some(combined)
+}
+
+/// Build the prompt droid receives: DROID.md (unless disabled) and `system_prompt` wrapped
+/// as a system prompt, followed by the client prompt, then `context_file` and
+/// `context_globs` as `<context>` blocks
+///
+/// Returns the assembled prompt and any DROID.md / system prompt / context file warnings.
+pub async fn assemble_prompt(opts: &Options) -> (String, Vec<String>) {
+    let (prompt, _, warnings) = assemble_prompt_parts(opts).await;
+    (prompt, warnings)
+}
+
+/// `assemble_prompt`, also returning the byte offset where the system prompt block ends
+async fn assemble_prompt_parts(opts: &Options) -> (String, usize, Vec<String>) {
+    let (droid_content, droid_warning) = if opts.use_droid_md.unwrap_or(true) {
+        read_droid_md(&opts.working_dir).await
+    } else {
+        (None, None)
+    };
+    let mut prompt = String::new();
+    let mut warnings: Vec<String> = droid_warning.into_iter().collect();
+
+    let system_prompt =
+        combine_system_prompt(droid_content, opts.system_prompt.as_deref(), &mut warnings);
+    if let Some(content) = system_prompt {
+        prompt.push_str("<system_prompt>\n");
+        prompt.push_str(&content);
+        prompt.push_str("\n</system_prompt>\n\n");
+    }
+    let body_start = prompt.len();
+
+    if let Some(ref client_prompt) = opts.prompt {
+        prompt.push_str(client_prompt);
+    }
+
+    if let Some(ref context_file) = opts.context_file {
+        let (context, context_warning) =
+            read_text_limited(context_file, "context_file", MAX_CONTEXT_FILE_SIZE).await;
+        warnings.extend(context_warning);
+        if let Some(context) = context {
+            push_context_block(&mut prompt, &context);
+        }
+    }
+
+    if !opts.context_globs.is_empty() {
+        let working_dir = opts.working_dir.clone();
+        let globs = opts.context_globs.clone();
+        let limits = glob_limits(&server_config(), opts);
+        let (context, glob_warnings) = tokio::task::spawn_blocking(move || {
+            crate::context::collect(&working_dir, &globs, limits)
+        })
+        .await
+        .unwrap_or_else(|e| {
+            (
+                String::new(),
+                vec![format!("Failed to collect context_globs: {}", e)],
+            )
+        });
+        warnings.extend(glob_warnings);
+        if !context.is_empty() {
+            push_context_block(&mut prompt, &context);
+        }
+    }
+
+    (prompt, body_start, warnings)
+}
+
+/// `context_globs` caps, lowered to what `max_attachments` / `max_attachment_bytes` leave
+/// after `file` and `context_file`
+fn glob_limits(cfg: &ServerConfig, opts: &Options) -> crate::context::GlobLimits {
+    let mut limits = crate::context::GlobLimits::default();
+    let attached: Vec<&PathBuf> = opts.file.iter().chain(&opts.context_file).collect();
+    if let Some(max_files) = cfg.max_attachments {
+        limits.max_files = limits
+            .max_files
+            .min(max_files.saturating_sub(attached.len()));
+    }
+    if let Some(max_bytes) = cfg.max_attachment_bytes {
+        let used: u64 = attached
+            .iter()
+            .filter_map(|path| std::fs::metadata(path).ok())
+            .map(|metadata| metadata.len())
+            .sum();
+        let remaining = usize::try_from(max_bytes.saturating_sub(used)).unwrap_or(usize::MAX);
+        limits.max_bytes = limits.max_bytes.min(remaining);
+    }
+    limits
+}
+
+fn push_context_block(prompt: &mut String, context: &str) {
+    if !prompt.is_empty() {
+        prompt.push_str("\n\n");
+    }
+    prompt.push_str("<context>\n");
+    prompt.push_str(context);
+    prompt.push_str("\n</context>");
+}
+
 pub async fn run(mut opts: Options) -> Result<DroidResult> {
-    // Apply default model if not specified
-    if opts.model.is_none() {
+    // Snapshot the config so a reload mid-run doesn't change this run's behavior
+    let cfg = server_config();
+
+    // Set once the prompt is assembled; rejected and failed runs are audited as well
+    let mut prompt = None;
+    let outcome = run_with(&cfg, &mut opts, &mut prompt).await;
+    if let Some(ref audit_path) = cfg.audit_log_path {
+        let record = audit_record(&cfg, &opts, prompt.as_deref(), &outcome);
+        crate::audit::append(audit_path, &record).await;
+    }
+    outcome
+}
+
+/// Checks and runs one call for [`run`], leaving `opts` as resolved for the audit record
+async fn run_with(
+    cfg: &ServerConfig,
+    opts: &mut Options,
+    prompt: &mut Option<String>,
+) -> Result<DroidResult> {
+    // Expand aliases first so the alias target goes through normal model resolution
+    opts.model = opts.model.take().map(|model| resolve_alias(cfg, model));
+
+    // Apply default model if not specified (raw passthrough models skip resolution)
+    if opts.model.is_none() && opts.raw_model.is_none() {
         opts.model = get_default_model();
     }
 
+    // The policy applies to the model droid will actually run, so callers pass theirs as given
+    check_model_allowed_with(cfg, opts.raw_model.as_deref().or(opts.model.as_deref()))
+        .map_err(rejected)?;
+
     // Apply default autonomy level if not specified
-    if opts.auto.is_none() {
-        opts.auto = get_default_auto();
+    let auto_from_default = opts.auto.is_none();
+    if auto_from_default {
+        opts.auto = get_default_auto(cfg);
+    }
+
+    // Governed workflows refuse to run without project context
+    if opts.require_droid_md.unwrap_or(cfg.require_droid_md)
+        && !droid_md_path(&opts.working_dir).is_file()
+    {
+        return Err(rejected(format!(
+            "require_droid_md is set but no DROID.md was found in {}",
+            opts.working_dir.display()
+        )));
+    }
+
+    let (assembled, body_start, mut warnings) = assemble_prompt_parts(opts).await;
+    let prompt_to_use = prompt.insert(assembled).as_str();
+
+    // Only the system prompt block would reach droid; catch this before spawning it
+    if opts.file.is_none() && prompt_to_use[body_start..].trim().is_empty() {
+        return Err(rejected(
+            "The assembled prompt is empty or whitespace-only (not counting DROID.md and \
+             system_prompt); provide a non-empty prompt or context",
+        ));
+    }
+
+    if cfg.force_readonly {
+        apply_force_readonly(opts, auto_from_default, &mut warnings);
+    } else {
+        apply_auto_bounds(cfg, opts, auto_from_default, &mut warnings)?;
     }
 
-    let (droid_content, droid_warning) = read_droid_md(&opts.working_dir).await;
-    let mut prompt_to_use = String::new();
+    if auto_from_default && !opts.skip_permissions_unsafe && opts.auto.as_deref() == Some("high") {
+        warnings.push(
+            "Running at HIGH autonomy because auto was omitted and the server default is high; \
+             pass auto explicitly or set default_auto/safe_default_auto to change this"
+                .to_string(),
+        );
+    }
 
-    if let Some(content) = droid_content {
-        prompt_to_use.push_str("<system_prompt>\n");
-        prompt_to_use.push_str(&content);
-        prompt_to_use.push_str("\n</system_prompt>\n\n");
+    // Fill in a configured default when the client did not pick a reasoning effort
+    let model = opts.raw_model.as_deref().or(opts.model.as_deref());
+    let effort_from_default = opts.reasoning_effort.is_none();
+    if effort_from_default {
+        opts.reasoning_effort = configured_reasoning_effort(cfg, model);
     }
 
-    if let Some(ref prompt) = opts.prompt {
-        prompt_to_use.push_str(prompt);
+    // Drop -r for models known not to accept it instead of letting droid fail
+    if let Some(effort) = opts.reasoning_effort.take() {
+        let model = opts.raw_model.as_deref().or(opts.model.as_deref());
+        if reasoning_effort_allowed(cfg, model) {
+            opts.reasoning_effort = Some(effort);
+        } else if !effort_from_default {
+            warnings.push(format!(
+                "Model '{}' does not support reasoning_effort; ignoring reasoning_effort '{}'",
+                model.unwrap_or_default(),
+                effort
+            ));
+        }
     }
 
     if opts.timeout_secs.is_none() {
-        opts.timeout_secs = Some(default_timeout_secs());
+        opts.timeout_secs = Some(configured_timeout_secs(cfg));
     }
 
-    let timeout_secs = opts.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS);
-    let cfg = server_config();
-    let max_timeout = cfg.max_timeout_secs.unwrap_or(MAX_TIMEOUT_SECS);
-    let timeout_secs = timeout_secs.min(max_timeout);
+    let requested_timeout = opts.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS);
+    // Trusted deployments may let clients go past max_timeout_secs, up to the absolute maximum
+    let (max_timeout, limit_name) = match cfg.max_timeout_secs {
+        Some(max) if !cfg.allow_client_timeout_override && max > 0 && max < MAX_TIMEOUT_SECS => {
+            (max, "server max_timeout_secs")
+        }
+        _ => (MAX_TIMEOUT_SECS, "absolute maximum"),
+    };
+    let timeout_secs = requested_timeout.min(max_timeout);
+    // Progress estimates are based on the timeout actually applied
+    opts.timeout_secs = Some(timeout_secs);
+    if timeout_secs < requested_timeout {
+        warnings.push(format!(
+            "Requested timeout of {} seconds exceeds the {} of {} seconds; effective timeout is {} seconds",
+            requested_timeout, limit_name, max_timeout, timeout_secs
+        ));
+    }
     let duration = std::time::Duration::from_secs(timeout_secs);
 
     if let Some(ref auto) = opts.auto {
         if auto == "high" && !cfg.allow_high_autonomy {
-            return Err(anyhow::anyhow!(
-                "High autonomy level is disabled in configuration. Set allow_high_autonomy=true to enable."
+            return Err(rejected(
+                "High autonomy level is disabled in configuration. Set allow_high_autonomy=true to enable.",
             ));
         }
     }
+    if opts.spec_auto.as_deref() == Some("high") && !cfg.allow_high_autonomy {
+        return Err(rejected(
+            "High autonomy level is disabled in configuration (spec_auto). Set allow_high_autonomy=true to enable.",
+        ));
+    }
+    if opts.skip_permissions_unsafe && !cfg.allow_high_autonomy {
+        return Err(rejected(SKIP_PERMISSIONS_DISABLED));
+    }
+    if let Some(ref droid_bin) = opts.droid_bin {
+        check_binary_override_with(cfg, droid_bin).map_err(rejected)?;
+    }
+
+    if !opts.scope_paths.is_empty() {
+        opts.scope_flag = cfg
+            .scope_flag
+            .clone()
+            .filter(|flag| !flag.trim().is_empty());
+        if opts.scope_flag.is_none() {
+            warnings.push(
+                "scope_paths is not enforced: droid has no known scope flag; set scope_flag \
+                 in the configuration if your droid version supports one"
+                    .to_string(),
+            );
+        }
+    }
+
+    // Held until the run (including post-run and diff capture) is done. Time spent
+    // queueing for the locks counts against the run's timeout.
+    let queued_at = std::time::Instant::now();
+    let modifying = opts.auto.is_some() || opts.spec_auto.is_some() || opts.skip_permissions_unsafe;
+    let _cwd_guard = if modifying {
+        Some(acquire_cwd_lock(cfg, &opts.working_dir, duration, &mut warnings).await?)
+    } else {
+        None
+    };
+    // The in-process lock comes first, so the lockfile only ever conflicts across processes
+    let _lockfile = if modifying && cfg.use_lockfile {
+        let (guard, warning) =
+            crate::lockfile::acquire(&opts.working_dir).map_err(|e| match e {
+                crate::lockfile::AcquireError::Held(message) => rejected(message),
+                crate::lockfile::AcquireError::Io(message) => anyhow::anyhow!(message),
+            })?;
+        warnings.extend(warning);
+        Some(guard)
+    } else {
+        None
+    };
+    let duration = duration.saturating_sub(queued_at.elapsed());
+
+    // Surface an unreadable Factory config once instead of silently running without
+    // models; taken only now so a rejected run doesn't swallow it
+    factory_config();
+    if let Some(warning) = FACTORY_CONFIG_WARNING
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take()
+    {
+        warnings.push(warning);
+    }
 
-    match tokio::time::timeout(
+    let mut result = match tokio::time::timeout(
         duration,
-        run_internal(opts.clone(), prompt_to_use, droid_warning.clone()),
+        run_with_rate_limit_retries(cfg, opts, prompt_to_use, warnings.clone()),
     )
     .await
     {
-        Ok(result) => result,
+        Ok(result) => result?,
         Err(_) => {
-            let (model_display, model_warning) = get_model_info(&opts.model);
-            let timeout_warning = format!("Droid execution timed out after {} seconds", timeout_secs);
-            let combined_warning = match (droid_warning, model_warning) {
-                (Some(dw), Some(mw)) => Some(format!("{}\n{}\n{}", dw, mw, timeout_warning)),
-                (Some(dw), None) => Some(format!("{}\n{}", dw, timeout_warning)),
-                (None, Some(mw)) => Some(format!("{}\n{}", mw, timeout_warning)),
-                (None, None) => Some(timeout_warning),
-            };
-            let result = DroidResult {
+            let (model_display, model_warning) = options_model_info(opts);
+            let timeout_warning =
+                format!("Droid execution timed out after {} seconds", timeout_secs);
+            warnings.extend(model_warning);
+            warnings.push(timeout_warning);
+            DroidResult {
                 success: false,
-                session_id: String::new(),
-                agent_messages: String::new(),
-                agent_messages_truncated: false,
-                all_messages: Vec::new(),
-                all_messages_truncated: false,
                 error: Some(format!("Timeout after {} seconds", timeout_secs)),
-                warnings: combined_warning,
+                error_code: Some(ErrorCode::Timeout),
+                warnings,
                 model_info: model_display,
-            };
-            Ok(result)
+                ..DroidResult::default()
+            }
         }
-    }
-}
+    };
 
-async fn run_internal(
-    opts: Options,
-    prompt: String,
-    mut droid_warning: Option<String>,
-) -> Result<DroidResult> {
-    let droid_bin = resolve_droid_bin();
+    if !opts.skip_permissions_unsafe {
+        result.effective_auto = opts.auto.clone();
+    }
+    result.working_dir = opts.working_dir.clone();
+    result.model = opts.raw_model.clone().or_else(|| opts.model.clone());
 
-    // Get model info for logging and display
-    let (model_display, model_warning) = get_model_info(&opts.model);
+    if result.success {
+        result.post_run_output = run_post_command(cfg, &opts.working_dir).await;
+    }
 
-    // Log to stderr for debugging
-    if let Some(ref info) = model_display {
-        eprintln!("droid-mcp-rs: {}", info);
+    if result.success && opts.return_diff {
+        match capture_git_diff(&opts.working_dir).await {
+            Ok(diff) => result.diff = Some(diff),
+            Err(warning) => result.warnings.push(warning),
+        }
     }
 
-    // Merge model warning into droid_warning
-    if let Some(model_warn) = model_warning {
-        droid_warning = Some(match droid_warning {
-            Some(existing) => format!("{}\n{}", existing, model_warn),
-            None => model_warn,
-        });
+    Ok(result)
+}
+
+/// Audit record of a [`run`] call; `prompt` is None when it was rejected before assembly
+fn audit_record(
+    cfg: &ServerConfig,
+    opts: &Options,
+    prompt: Option<&str>,
+    outcome: &Result<DroidResult>,
+) -> crate::audit::AuditRecord {
+    // The prompt itself is recorded separately (or not at all)
+    let mut args = build_args(opts, "");
+    if opts.file.is_none() {
+        args.pop();
+    }
+    let (session_id, success, error) = match outcome {
+        Ok(result) => (
+            result.session_id.clone(),
+            result.success,
+            result.error.clone(),
+        ),
+        Err(e) => (String::new(), false, Some(format!("{:#}", e))),
+    };
+    crate::audit::AuditRecord {
+        timestamp: crate::audit::now_secs(),
+        cwd: opts.working_dir.clone(),
+        model: opts.raw_model.clone().or_else(|| opts.model.clone()),
+        auto: opts.auto.clone().filter(|_| !opts.skip_permissions_unsafe),
+        prompt_sha256: prompt.map(crate::audit::prompt_sha256),
+        prompt: prompt.filter(|_| cfg.log_full_prompt).map(str::to_string),
+        prompt_len: prompt.map(str::len),
+        args: args
+            .into_iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect(),
+        session_id,
+        success,
+        error,
     }
+}
 
-    let mut cmd = Command::new(&droid_bin);
-    cmd.args(["exec"]);
+/// Cancellation handles of the droid children currently streaming output, by run number
+static ACTIVE_RUNS: Mutex<BTreeMap<u64, Arc<tokio::sync::Notify>>> = Mutex::new(BTreeMap::new());
 
-    // Output format (default to stream-json if not specified)
-    let output_fmt = opts.output_format.as_deref().unwrap_or("stream-json");
-    cmd.arg("-o");
-    cmd.arg(output_fmt);
+static NEXT_RUN_ID: AtomicU64 = AtomicU64::new(0);
 
-    cmd.arg("--cwd");
-    cmd.arg(opts.working_dir.as_os_str());
+/// Entry of a run in [`ACTIVE_RUNS`], removed again on drop
+struct ActiveRun {
+    id: u64,
+    cancel: Arc<tokio::sync::Notify>,
+}
 
-    // Skip permissions unsafe (mutually exclusive with auto)
-    if opts.skip_permissions_unsafe {
-        cmd.arg("--skip-permissions-unsafe");
-    } else if let Some(ref auto) = opts.auto {
-        cmd.arg("--auto");
-        cmd.arg(auto);
+impl ActiveRun {
+    fn register() -> Self {
+        let id = NEXT_RUN_ID.fetch_add(1, Ordering::Relaxed);
+        let cancel = Arc::new(tokio::sync::Notify::new());
+        ACTIVE_RUNS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(id, cancel.clone());
+        ActiveRun { id, cancel }
     }
 
-    // Reasoning effort
-    if let Some(ref reasoning) = opts.reasoning_effort {
-        cmd.arg("-r");
-        cmd.arg(reasoning);
+    /// Resolves once `cancel_all` was called while this run was registered
+    async fn cancelled(&self) {
+        self.cancel.notified().await
     }
+}
 
-    // Specification mode
-    if opts.use_spec {
-        cmd.arg("--use-spec");
-        if let Some(ref spec_model) = opts.spec_model {
-            cmd.arg("--spec-model");
-            cmd.arg(spec_model);
-        }
+impl Drop for ActiveRun {
+    fn drop(&mut self) {
+        ACTIVE_RUNS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&self.id);
     }
+}
 
-    if let Some(ref model) = opts.model {
-        cmd.arg("--model");
-        cmd.arg(model);
-    }
+/// Number of droid children currently running
+pub fn active_runs() -> usize {
+    ACTIVE_RUNS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .len()
+}
 
-    if let Some(ref enabled) = opts.enabled_tools {
-        cmd.arg("--enabled-tools");
-        cmd.arg(enabled);
+/// Kill every droid child currently running and return how many were signalled
+///
+/// Each cancelled run returns a failed result with [`ErrorCode::Cancelled`]; runs started
+/// afterwards are unaffected.
+pub fn cancel_all() -> usize {
+    let runs = ACTIVE_RUNS.lock().unwrap_or_else(PoisonError::into_inner);
+    for cancel in runs.values() {
+        // Stores a permit, so a run that is not polling right now still sees it
+        cancel.notify_one();
     }
+    runs.len()
+}
 
-    if let Some(ref disabled) = opts.disabled_tools {
-        cmd.arg("--disabled-tools");
-        cmd.arg(disabled);
-    }
+/// Per-directory locks serializing runs that may modify files, keyed by canonical path
+fn cwd_lock(working_dir: &std::path::Path) -> Arc<tokio::sync::Mutex<()>> {
+    static LOCKS: OnceLock<Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>> = OnceLock::new();
+    let key = working_dir
+        .canonicalize()
+        .unwrap_or_else(|_| working_dir.to_path_buf());
+    LOCKS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .entry(key)
+        .or_default()
+        .clone()
+}
 
-    if let Some(ref session_id) = opts.session_id {
-        cmd.arg("--session-id");
-        cmd.arg(session_id);
+/// Wait (up to `timeout`) for other modifying runs in `working_dir` to finish, or fail
+/// right away when `reject_busy_cwd` is set
+///
+/// The caller takes the wait off the run's own timeout. Read-only runs never take the lock and keep running concurrently.
+async fn acquire_cwd_lock(
+    cfg: &ServerConfig,
+    working_dir: &std::path::Path,
+    timeout: std::time::Duration,
+    warnings: &mut Vec<String>,
+) -> Result<tokio::sync::OwnedMutexGuard<()>> {
+    let lock = cwd_lock(working_dir);
+    if let Ok(guard) = lock.clone().try_lock_owned() {
+        return Ok(guard);
     }
-
-    for arg in &opts.additional_args {
-        cmd.arg(arg);
+    if cfg.reject_busy_cwd {
+        return Err(rejected(format!(
+            "Another modifying run is already active in {}; retry once it finishes",
+            working_dir.display()
+        )));
     }
 
-    if let Some(ref file) = opts.file {
-        cmd.arg("--file");
-        cmd.arg(file);
-    } else {
-        cmd.arg(prompt);
-    }
+    let started = std::time::Instant::now();
+    let guard = tokio::time::timeout(timeout, lock.lock_owned())
+        .await
+        .map_err(|_| {
+            anyhow::anyhow!(
+                "Timed out after {} seconds waiting for another modifying run in {} to finish",
+                timeout.as_secs(),
+                working_dir.display()
+            )
+        })?;
+    warnings.push(format!(
+        "Waited {} ms for another modifying run in {} to finish",
+        started.elapsed().as_millis(),
+        working_dir.display()
+    ));
+    Ok(guard)
+}
 
-    cmd.stdin(Stdio::null());
-    cmd.stdout(Stdio::piped());
-    cmd.stderr(Stdio::piped());
+/// Whether a droid error or stderr text signals a rate limit
+fn is_rate_limit_message(text: &str) -> bool {
+    let lower = text.to_lowercase();
+    lower.contains("rate limit")
+        || lower.contains("rate_limit")
+        || lower.contains("too many requests")
+        || mentions_http_429(&lower)
+}
+
+/// Whether `429` appears as an HTTP status ("HTTP 429", "HTTP/1.1 429", "status: 429")
+/// rather than as an unrelated number such as a line number or token count
+fn mentions_http_429(lower: &str) -> bool {
+    lower.match_indices("429").any(|(idx, _)| {
+        let before = &lower[..idx];
+        let after = &lower[idx + 3..];
+        let standalone = !before.ends_with(|c: char| c.is_ascii_digit())
+            && !after.starts_with(|c: char| c.is_ascii_digit());
+        standalone
+            && before
+                .split_whitespace()
+                .rev()
+                .take(2)
+                .any(|word| word.contains("http") || word.contains("status"))
+    })
+}
+
+/// Whether a droid session can still be resumed, as found by [`touch_session`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionStatus {
+    /// The session resumed and answered
+    Active,
+    /// droid no longer knows the session; start a new one
+    Expired,
+    /// The touch failed for another reason; the session may still be valid
+    Failed,
+}
+
+/// Outcome of [`touch_session`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionTouch {
+    pub status: SessionStatus,
+    /// Why the session is not active
+    pub error: Option<String>,
+}
+
+fn is_expired_session_message(text: &str) -> bool {
+    let lower = text.to_lowercase();
+    lower.contains("session")
+        && [
+            "not found",
+            "expired",
+            "does not exist",
+            "unknown session",
+            "no such session",
+        ]
+        .iter()
+        .any(|needle| lower.contains(needle))
+}
+
+/// Resume `session_id` with a trivial read-only prompt so droid keeps it warm
+///
+/// Skips DROID.md, context, autonomy defaults and retries: only the resume itself matters.
+/// The touch does add one short turn to the session.
+pub async fn touch_session(session_id: &str, working_dir: PathBuf) -> Result<SessionTouch> {
+    let cfg = server_config();
+    let opts = Options {
+        prompt: Some(TOUCH_SESSION_PROMPT.to_string()),
+        session_id: Some(session_id.to_string()),
+        working_dir,
+        additional_args: cfg.additional_args.clone(),
+        ..Default::default()
+    };
+
+    let duration = std::time::Duration::from_secs(TOUCH_SESSION_TIMEOUT_SECS);
+    let run = run_internal(&cfg, &opts, TOUCH_SESSION_PROMPT, Vec::new());
+    let result = match tokio::time::timeout(duration, run).await {
+        Ok(result) => result?,
+        Err(_) => {
+            return Ok(SessionTouch {
+                status: SessionStatus::Failed,
+                error: Some(format!(
+                    "Timeout after {} seconds",
+                    TOUCH_SESSION_TIMEOUT_SECS
+                )),
+            })
+        }
+    };
+
+    if result.success && result.session_id == session_id {
+        return Ok(SessionTouch {
+            status: SessionStatus::Active,
+            error: None,
+        });
+    }
+    if result.success {
+        // droid quietly started a fresh session instead of resuming the old one
+        return Ok(SessionTouch {
+            status: SessionStatus::Expired,
+            error: Some(format!(
+                "droid started a new session '{}' instead of resuming",
+                result.session_id
+            )),
+        });
+    }
+    let error = result.error.unwrap_or_default();
+    // droid explains an unknown session on stderr
+    let status = if is_expired_session_message(&error)
+        || result
+            .stderr
+            .as_deref()
+            .is_some_and(is_expired_session_message)
+    {
+        SessionStatus::Expired
+    } else {
+        SessionStatus::Failed
+    };
+    Ok(SessionTouch {
+        status,
+        error: Some(error),
+    })
+}
+
+/// Run droid, backing off and retrying while it reports rate limits
+///
+/// Retries only happen when `retry_on_rate_limit` is enabled; each retry waits twice as long
+/// as the previous one and is recorded as a warning on the final result.
+async fn run_with_rate_limit_retries(
+    cfg: &ServerConfig,
+    opts: &Options,
+    prompt: &str,
+    mut warnings: Vec<String>,
+) -> Result<DroidResult> {
+    let max_retries = if cfg.retry_on_rate_limit {
+        cfg.rate_limit_max_retries
+            .unwrap_or(DEFAULT_RATE_LIMIT_MAX_RETRIES)
+    } else {
+        0
+    };
+    let mut backoff = std::time::Duration::from_secs(
+        cfg.rate_limit_backoff_secs
+            .unwrap_or(DEFAULT_RATE_LIMIT_BACKOFF_SECS),
+    );
+
+    let mut attempt: u32 = 0;
+    loop {
+        let result = run_internal(cfg, opts, prompt, warnings.clone()).await?;
+        if !result.rate_limited || attempt >= max_retries {
+            return Ok(result);
+        }
+
+        attempt += 1;
+        warnings.push(format!(
+            "Droid reported a rate limit; retry {}/{} after {} seconds",
+            attempt,
+            max_retries,
+            backoff.as_secs()
+        ));
+        tokio::time::sleep(backoff).await;
+        backoff = backoff.saturating_mul(2);
+    }
+}
+
+/// Run the configured post-run command (e.g. a formatter) in the working directory
+///
+/// Only runs when `enable_post_run` is set and `post_run_command` is non-empty.
+/// Failures and timeouts are reported in the returned output rather than failing the run.
+async fn run_post_command(cfg: &ServerConfig, working_dir: &std::path::Path) -> Option<String> {
+    if !cfg.enable_post_run {
+        return None;
+    }
+    let (program, args) = cfg.post_run_command.split_first()?;
+    let timeout_secs = cfg
+        .post_run_timeout_secs
+        .filter(|t| *t > 0)
+        .unwrap_or(DEFAULT_POST_RUN_TIMEOUT_SECS);
+
+    let mut cmd = Command::new(program);
+    cmd.args(args);
+    cmd.current_dir(working_dir);
+    cmd.stdin(Stdio::null());
     cmd.kill_on_drop(true);
 
-    let mut child = cmd.spawn().with_context(|| {
-        format!(
-            "Failed to spawn droid command '{}' in '{}'",
-            droid_bin,
-            opts.working_dir.display()
-        )
-    })?;
+    let output = match tokio::time::timeout(
+        std::time::Duration::from_secs(timeout_secs),
+        cmd.output(),
+    )
+    .await
+    {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => {
+            return Some(format!(
+                "Failed to run post-run command '{}': {}",
+                program, e
+            ));
+        }
+        Err(_) => {
+            return Some(format!(
+                "Post-run command '{}' timed out after {} seconds",
+                program, timeout_secs
+            ));
+        }
+    };
 
-    let stdout = child
-        .stdout
-        .take()
-        .context("Failed to get stdout from droid command")?;
-    let stderr = child
-        .stderr
-        .take()
-        .context("Failed to get stderr from droid command")?;
+    let status = match output.status.code() {
+        Some(code) => format!("exit code {}", code),
+        None => "killed by signal".to_string(),
+    };
+    let mut text = format!("$ {} ({})\n", cfg.post_run_command.join(" "), status);
+    text.push_str(&String::from_utf8_lossy(&output.stdout));
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    if truncate_at_char_boundary(&mut text, MAX_POST_RUN_OUTPUT_SIZE) {
+        text.push_str("\n[... Post-run output truncated due to size limit ...]");
+    }
+    Some(text)
+}
 
-    let mut result = DroidResult {
-        success: true,
-        session_id: String::new(),
-        agent_messages: String::new(),
-        agent_messages_truncated: false,
-        all_messages: Vec::new(),
-        all_messages_truncated: false,
-        error: None,
-        warnings: droid_warning,
-        model_info: model_display,
+/// Run git in `working_dir` and return its stdout, or a warning describing the failure
+async fn run_git(working_dir: &std::path::Path, args: &[&str]) -> Result<String, String> {
+    let mut cmd = Command::new("git");
+    cmd.arg("-C").arg(working_dir).args(args);
+    cmd.stdin(Stdio::null());
+    cmd.kill_on_drop(true);
+
+    let output = match tokio::time::timeout(
+        std::time::Duration::from_secs(GIT_DIFF_TIMEOUT_SECS),
+        cmd.output(),
+    )
+    .await
+    {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => return Err(format!("return_diff: failed to run git: {}", e)),
+        Err(_) => {
+            return Err(format!(
+                "return_diff: git {} timed out after {} seconds",
+                args.join(" "),
+                GIT_DIFF_TIMEOUT_SECS
+            ))
+        }
     };
+    if !output.status.success() {
+        return Err(format!(
+            "return_diff: git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
 
-    let stderr_handle = tokio::spawn(async move {
-        let mut stderr_output = String::new();
-        let mut stderr_reader = BufReader::new(stderr);
-        let mut line = String::new();
+/// `git diff` of `working_dir` followed by its untracked files, truncated to `MAX_DIFF_SIZE`
+async fn capture_git_diff(working_dir: &std::path::Path) -> Result<String, String> {
+    if run_git(working_dir, &["rev-parse", "--is-inside-work-tree"])
+        .await
+        .is_err()
+    {
+        return Err(format!(
+            "return_diff: {} is not inside a git repository",
+            working_dir.display()
+        ));
+    }
 
-        loop {
-            line.clear();
-            match stderr_reader.read_line(&mut line).await {
-                Ok(0) => break,
-                Ok(_) => {
-                    if stderr_output.len() + line.len() <= MAX_STDERR_SIZE {
-                        stderr_output.push_str(&line);
+    let mut text = run_git(working_dir, &["--no-pager", "diff", "--no-color"]).await?;
+    let status = run_git(
+        working_dir,
+        &["status", "--porcelain", "--untracked-files=all"],
+    )
+    .await?;
+    let untracked: Vec<&str> = status
+        .lines()
+        .filter_map(|line| line.strip_prefix("?? "))
+        // Our own lockfile is present while the diff is taken
+        .filter(|path| {
+            std::path::Path::new(path).file_name()
+                != Some(std::ffi::OsStr::new(crate::lockfile::LOCK_FILE))
+        })
+        .collect();
+    if !untracked.is_empty() {
+        if !text.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
+        text.push_str("Untracked files:\n");
+        for path in untracked {
+            text.push_str(path);
+            text.push('\n');
+        }
+    }
+    if truncate_at_char_boundary(&mut text, MAX_DIFF_SIZE) {
+        text.push_str("\n[... Diff truncated due to size limit ...]");
+    }
+    Ok(text)
+}
+
+/// List the tool names droid accepts for enabled_tools / disabled_tools
+///
+/// Runs `droid exec --list-tools` and caches a successful result for a few minutes.
+/// Errors carry a message suitable for clients, e.g. when this droid version can't list tools.
+pub async fn list_droid_tools() -> Result<Vec<String>, String> {
+    static CACHE: std::sync::Mutex<Option<(std::time::Instant, Vec<String>)>> =
+        std::sync::Mutex::new(None);
+    let ttl = std::time::Duration::from_secs(DROID_TOOLS_CACHE_TTL_SECS);
+
+    if let Some((fetched, ref tools)) = *CACHE.lock().unwrap_or_else(PoisonError::into_inner) {
+        if fetched.elapsed() < ttl {
+            return Ok(tools.clone());
+        }
+    }
+
+    let droid_bin = resolve_droid_bin();
+    let mut cmd = Command::new(&droid_bin);
+    cmd.args(["exec", "--list-tools"]);
+    apply_no_color(&mut cmd, &server_config());
+    cmd.stdin(Stdio::null());
+    cmd.kill_on_drop(true);
+
+    let output = match tokio::time::timeout(
+        std::time::Duration::from_secs(DROID_TOOLS_TIMEOUT_SECS),
+        cmd.output(),
+    )
+    .await
+    {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => return Err(format!("Failed to run '{}': {}", droid_bin, e)),
+        Err(_) => {
+            return Err(format!(
+                "'{} exec --list-tools' timed out after {} seconds",
+                droid_bin, DROID_TOOLS_TIMEOUT_SECS
+            ))
+        }
+    };
+
+    let stdout = strip_ansi(&String::from_utf8_lossy(&output.stdout));
+    let tools = parse_tool_list(&stdout);
+    if !output.status.success() || tools.is_empty() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!(
+            "This droid version does not support listing tools ('droid exec --list-tools' exited with code {:?}: {})",
+            output.status.code(),
+            stderr.trim()
+        ));
+    }
+
+    *CACHE.lock().unwrap_or_else(PoisonError::into_inner) =
+        Some((std::time::Instant::now(), tools.clone()));
+    Ok(tools)
+}
+
+/// Whether `name` looks like a droid tool id (`Read`, `mcp_server-tool`, ...)
+fn is_tool_id(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-'))
+}
+
+/// Extract tool names from `--list-tools` output: a JSON array of names (or of objects with
+/// a `name`), or text with one tool per line, optionally bulleted and followed by a description
+///
+/// When the text has a tools section header (e.g. "Available tools:"), only lines under it
+/// are read, so usage and option lines around it are not mistaken for tools.
+fn parse_tool_list(output: &str) -> Vec<String> {
+    if let Ok(Value::Array(items)) = serde_json::from_str::<Value>(output.trim()) {
+        return items
+            .iter()
+            .filter_map(|item| {
+                item.as_str()
+                    .or_else(|| item.get("name").and_then(|v| v.as_str()))
+                    .map(str::to_string)
+            })
+            .collect();
+    }
+
+    let is_header = |line: &str| line.ends_with(':');
+    let is_tools_header = |line: &str| is_header(line) && line.to_lowercase().contains("tool");
+    let sectioned = output.lines().any(|line| is_tools_header(line.trim()));
+    let mut in_tools = !sectioned;
+
+    let mut tools: Vec<String> = Vec::new();
+    for line in output.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if is_header(line) {
+            in_tools = !sectioned || is_tools_header(line);
+            continue;
+        }
+        if !in_tools {
+            continue;
+        }
+        let line = line.trim_start_matches(['-', '*', '•']).trim_start();
+        let Some(name) = line
+            .split(|c: char| c.is_whitespace() || c == ':')
+            .next()
+            .filter(|name| is_tool_id(name))
+        else {
+            continue;
+        };
+        if !tools.iter().any(|t| t == name) {
+            tools.push(name.to_string());
+        }
+    }
+    tools
+}
+
+/// Remove ANSI escape sequences (CSI such as colors, OSC such as titles, and two-byte escapes)
+pub fn strip_ansi(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\u{1b}' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI: parameters and intermediates up to a final byte in @..~
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
                     }
                 }
-                Err(_) => break,
             }
+            // OSC: terminated by BEL or ESC \
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\u{7}' {
+                        break;
+                    }
+                    if c == '\u{1b}' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            _ => {}
         }
-        stderr_output
-    });
+    }
+
+    out
+}
+
+/// Tidy the spacing of concatenated agent messages
+///
+/// Whitespace-only lines become empty, runs of blank lines collapse to one (so at most two
+/// consecutive newlines remain), and trailing whitespace is removed.
+pub fn normalize_message(text: &str) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+    let mut previous_blank = false;
+    for line in text.split('\n') {
+        let blank = line.trim().is_empty();
+        if blank && previous_blank {
+            continue;
+        }
+        lines.push(if blank { "" } else { line });
+        previous_blank = blank;
+    }
+    lines.join("\n").trim_end().to_string()
+}
+
+/// Truncate a string to at most `max` bytes without splitting a UTF-8 character
+fn truncate_at_char_boundary(s: &mut String, max: usize) -> bool {
+    if s.len() <= max {
+        return false;
+    }
+    let mut end = max;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    s.truncate(end);
+    true
+}
+
+/// Flags set by the server itself, which clients may not pass through extra args
+const RESERVED_FLAGS: &[&str] = &[
+    "-o",
+    "--output-format",
+    "--cwd",
+    "--auto",
+    "--skip-permissions-unsafe",
+    "-r",
+    "--reasoning-effort",
+    "--use-spec",
+    "--spec-model",
+    "--spec-auto",
+    "-m",
+    "--model",
+    "--enabled-tools",
+    "--disabled-tools",
+    "--max-turns",
+    "-s",
+    "--session-id",
+    "-f",
+    "--file",
+];
+
+/// Flag name of an argument, without any `=value` suffix
+fn flag_name(arg: &str) -> &str {
+    arg.split_once('=').map_or(arg, |(name, _)| name)
+}
+
+/// Whether an argument sets a flag managed by the server (`--flag` or `--flag=value`)
+pub fn is_reserved_flag(arg: &str) -> bool {
+    RESERVED_FLAGS.contains(&flag_name(arg))
+}
+
+/// Check a `passthrough_flags` key and return the flag it renders to (`--key`)
+///
+/// Keys are ASCII letters, digits and dashes, start with a letter or digit, and may not
+/// name a flag managed by the server.
+pub fn passthrough_flag(key: &str) -> Result<String, String> {
+    let valid = key
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphanumeric())
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    if !valid {
+        return Err(format!(
+            "Invalid passthrough_flags key '{}': use letters, digits and dashes without a leading dash",
+            key
+        ));
+    }
+    let flag = format!("--{}", key);
+    if is_reserved_flag(&flag) {
+        return Err(format!(
+            "passthrough_flags may not set '{}': this flag is managed by the server",
+            flag
+        ));
+    }
+    Ok(flag)
+}
+
+/// Whether a call may read its prompt from the server environment variable `name`
+///
+/// Only names listed in `allowed_prompt_env` pass, so a client cannot read arbitrary
+/// server environment (API keys, tokens) into a prompt.
+pub fn check_prompt_env_allowed(name: &str) -> Result<(), String> {
+    let cfg = server_config();
+    if cfg.allowed_prompt_env.is_empty() {
+        return Err(
+            "prompt_env is disabled on this server. List the variables clients may read in allowed_prompt_env to enable it"
+                .to_string(),
+        );
+    }
+    if cfg.allowed_prompt_env.iter().any(|allowed| allowed == name) {
+        Ok(())
+    } else {
+        Err(format!(
+            "prompt_env variable '{}' is not allowed. Allowed variables: {}",
+            name,
+            cfg.allowed_prompt_env.join(", ")
+        ))
+    }
+}
+
+/// Check a client-supplied extra arg against the `allowed_extra_args` policy
+///
+/// With an empty allowlist all extra args are denied unless `allow_any_extra_args` is set.
+/// Non-flag entries (flag values) are accepted once extra args are enabled.
+pub fn check_extra_arg_allowed(arg: &str) -> Result<(), String> {
+    let cfg = server_config();
+    if cfg.allow_any_extra_args {
+        return Ok(());
+    }
+    if cfg.allowed_extra_args.is_empty() {
+        return Err(
+            "extra_args are disabled on this server. Configure allowed_extra_args (or allow_any_extra_args) to enable them"
+                .to_string(),
+        );
+    }
+    if !arg.starts_with('-') {
+        return Ok(());
+    }
+
+    let name = flag_name(arg);
+    if cfg.allowed_extra_args.iter().any(|allowed| allowed == name) {
+        Ok(())
+    } else {
+        Err(format!(
+            "extra_args flag '{}' is not allowed. Allowed flags: {}",
+            name,
+            cfg.allowed_extra_args.join(", ")
+        ))
+    }
+}
+
+/// Locations where stream-json variants carry the session id, in lookup order
+const SESSION_ID_POINTERS: &[&str] = &[
+    "/session_id",
+    "/meta/session_id",
+    "/session/id",
+    "/session/session_id",
+];
+
+/// Extract a non-empty session id from a stream line, checking nested variants
+/// when the top-level `session_id` field is absent
+fn extract_session_id(line_data: &Value) -> Option<&str> {
+    SESSION_ID_POINTERS
+        .iter()
+        .filter_map(|pointer| line_data.pointer(pointer).and_then(|v| v.as_str()))
+        .find(|sid| !sid.is_empty())
+}
+
+/// Build the `droid exec` argument list for the given options and assembled prompt
+fn build_args(opts: &Options, prompt: &str) -> Vec<OsString> {
+    let mut args: Vec<OsString> = vec!["exec".into()];
+
+    // Output format (default to stream-json if not specified)
+    let output_fmt = opts.output_format.as_deref().unwrap_or("stream-json");
+    args.push("-o".into());
+    args.push(output_fmt.into());
+
+    args.push("--cwd".into());
+    args.push(opts.working_dir.clone().into_os_string());
+
+    // Skip permissions unsafe (mutually exclusive with auto)
+    if opts.skip_permissions_unsafe {
+        args.push("--skip-permissions-unsafe".into());
+    } else if let Some(ref auto) = opts.auto {
+        args.push("--auto".into());
+        args.push(auto.into());
+    }
+
+    // Reasoning effort
+    if let Some(ref reasoning) = opts.reasoning_effort {
+        args.push("-r".into());
+        args.push(reasoning.into());
+    }
+
+    // Specification mode
+    if opts.use_spec {
+        args.push("--use-spec".into());
+        if let Some(ref spec_model) = opts.spec_model {
+            args.push("--spec-model".into());
+            args.push(spec_model.into());
+        }
+        if let Some(ref spec_auto) = opts.spec_auto {
+            args.push("--spec-auto".into());
+            args.push(spec_auto.into());
+        }
+    }
+
+    if let Some(model) = opts.raw_model.as_ref().or(opts.model.as_ref()) {
+        args.push("--model".into());
+        args.push(model.into());
+    }
+
+    if let Some(ref enabled) = opts.enabled_tools {
+        args.push("--enabled-tools".into());
+        args.push(enabled.into());
+    }
+
+    if let Some(ref disabled) = opts.disabled_tools {
+        args.push("--disabled-tools".into());
+        args.push(disabled.into());
+    }
+
+    if let Some(ref scope_flag) = opts.scope_flag {
+        for path in &opts.scope_paths {
+            args.push(scope_flag.into());
+            args.push(path.into());
+        }
+    }
+
+    // Iteration limit, independent of the wall-clock timeout
+    if let Some(max_turns) = opts.max_turns {
+        args.push("--max-turns".into());
+        args.push(max_turns.to_string().into());
+    }
+
+    if let Some(ref session_id) = opts.session_id {
+        args.push("--session-id".into());
+        args.push(session_id.into());
+    }
+
+    for arg in &opts.additional_args {
+        args.push(arg.into());
+    }
+
+    // Client-supplied extras come after the server's configured args
+    for arg in &opts.extra_args {
+        args.push(arg.into());
+    }
 
-    let mut reader = BufReader::new(stdout);
-    let mut line = String::new();
-    let mut all_messages_size: usize = 0;
+    for (key, value) in &opts.passthrough_flags {
+        args.push(format!("--{}", key).into());
+        args.push(value.into());
+    }
+
+    if let Some(ref file) = opts.file {
+        args.push("--file".into());
+        args.push(file.clone().into_os_string());
+    } else {
+        args.push(prompt.into());
+    }
+
+    args
+}
+
+/// Record the final status, usage and duration from a `result`/`summary` line
+fn apply_summary_line(result: &mut DroidResult, line_data: &Value) {
+    let reported = line_data
+        .get("success")
+        .and_then(|v| v.as_bool())
+        .or_else(|| {
+            line_data
+                .get("is_error")
+                .and_then(|v| v.as_bool())
+                .map(|is_error| !is_error)
+        })
+        .or_else(|| match line_data.get("status").and_then(|v| v.as_str())? {
+            "success" | "ok" | "completed" => Some(true),
+            "error" | "failed" | "failure" => Some(false),
+            _ => None,
+        });
+    if reported.is_some() {
+        result.reported_success = reported;
+    }
+
+    if let Some(text) = ["summary", "result", "text"]
+        .iter()
+        .find_map(|key| line_data.get(*key).and_then(|v| v.as_str()))
+    {
+        result.summary = Some(text.to_string());
+    }
+    if let Some(usage) = line_data.get("usage").filter(|v| !v.is_null()) {
+        result.usage = Some(usage.clone());
+    }
+    if let Some(duration) = line_data.get("duration_ms").and_then(|v| v.as_u64()) {
+        result.duration_ms = Some(duration);
+    }
+}
+
+/// `io::Write` sink that only counts the bytes written to it
+struct ByteCounter(usize);
+
+impl std::io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Length of `value` serialized as compact JSON, without building the string
+pub fn serialized_size<T: Serialize + ?Sized>(value: &T) -> usize {
+    let mut counter = ByteCounter(0);
+    match serde_json::to_writer(&mut counter, value) {
+        Ok(()) => counter.0,
+        Err(_) => 0,
+    }
+}
+
+/// Size caps applied while parsing droid's stdout
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// Maximum bytes of `agent_messages` (and of the spec)
+    pub agent_messages: usize,
+    /// Maximum serialized bytes of `all_messages`
+    pub all_messages: usize,
+    /// Longest stdout line buffered; longer lines are skipped
+    pub max_line_bytes: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            agent_messages: MAX_AGENT_MESSAGES_SIZE,
+            all_messages: MAX_ALL_MESSAGES_SIZE,
+            max_line_bytes: DEFAULT_MAX_LINE_BYTES,
+        }
+    }
+}
+
+/// Read one line (including its `\n`) into `buf`, buffering at most `max` bytes of it
+///
+/// Returns the bytes consumed from `reader` (0 at EOF) and whether the line was longer
+/// than `max`; the rest of an oversized line is consumed and discarded, leaving `buf`
+/// empty. Like `read_line`, only resolves on a full line or EOF.
+async fn read_bounded_line<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+    max: usize,
+) -> std::io::Result<(usize, bool)> {
+    let mut consumed = 0;
+    let mut oversized = false;
+    loop {
+        let available = reader.fill_buf().await?;
+        if available.is_empty() {
+            return Ok((consumed, oversized));
+        }
+        let (chunk, done) = match available.iter().position(|&b| b == b'\n') {
+            Some(end) => (&available[..=end], true),
+            None => (available, false),
+        };
+        if !oversized {
+            if buf.len() + chunk.len() > max {
+                oversized = true;
+                buf.clear();
+            } else {
+                buf.extend_from_slice(chunk);
+            }
+        }
+        let len = chunk.len();
+        reader.consume(len);
+        consumed += len;
+        if done {
+            return Ok((consumed, oversized));
+        }
+    }
+}
+
+/// Parse a droid stream-json stdout line by line into a result
+///
+/// Only accumulates what the stream contains; exit status, stderr and missing-session
+/// checks are left to the caller.
+pub async fn parse_stream<R: AsyncBufRead + Unpin>(reader: R, limits: Limits) -> DroidResult {
+    parse_stream_tee(reader, limits, None, None, "droid-mcp-rs:").await
+}
+
+/// Timer driving [`Progress`] heartbeats while the stdout read loop waits
+struct Heartbeat {
+    sender: tokio::sync::mpsc::UnboundedSender<Progress>,
+    interval: tokio::time::Interval,
+    started: tokio::time::Instant,
+    timeout_secs: u64,
+}
+
+impl Heartbeat {
+    fn new(
+        sender: tokio::sync::mpsc::UnboundedSender<Progress>,
+        period_secs: u64,
+        timeout_secs: u64,
+    ) -> Self {
+        let period = std::time::Duration::from_secs(period_secs.max(1));
+        let started = tokio::time::Instant::now();
+        let mut interval = tokio::time::interval_at(started + period, period);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        Heartbeat {
+            sender,
+            interval,
+            started,
+            timeout_secs,
+        }
+    }
+
+    fn send(&self, last_line_type: Option<&str>) {
+        // A closed receiver only means nobody is listening anymore
+        let elapsed_secs = self.started.elapsed().as_secs();
+        let _ = self.sender.send(Progress {
+            elapsed_secs,
+            last_line_type: last_line_type.map(str::to_string),
+            timeout_secs: self.timeout_secs,
+            estimated_percent: estimated_percent(elapsed_secs, self.timeout_secs),
+        });
+    }
+}
+
+/// Next heartbeat tick; never resolves without a heartbeat
+async fn next_tick(heartbeat: &mut Option<Heartbeat>) {
+    match heartbeat {
+        Some(heartbeat) => {
+            heartbeat.interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Stderr log prefix for a run, tagged with its request id when it has one
+fn log_prefix(opts: &Options) -> String {
+    match opts.request_id {
+        Some(ref id) => format!("droid-mcp-rs: [request {}]", id),
+        None => "droid-mcp-rs:".to_string(),
+    }
+}
+
+/// Like [`parse_stream`], additionally copying every raw line to `tee`
+///
+/// A failed write is reported as a warning and stops further copying.
+async fn parse_stream_tee<R: AsyncBufRead + Unpin>(
+    mut reader: R,
+    limits: Limits,
+    mut tee: Option<(PathBuf, tokio::io::BufWriter<tokio::fs::File>)>,
+    mut heartbeat: Option<Heartbeat>,
+    log_prefix: &str,
+) -> DroidResult {
+    let mut result = DroidResult {
+        success: true,
+        ..DroidResult::default()
+    };
+    let mut line_buf: Vec<u8> = Vec::new();
+    let mut last_line_type: Option<String> = None;
+    let started = std::time::Instant::now();
 
+    // Reads only resolve on a full line or EOF and park the task while droid is slow to
+    // flush, so a stalled but live process never turns this into a busy loop
     loop {
-        line.clear();
-        match reader.read_line(&mut line).await {
-            Ok(0) => break,
+        line_buf.clear();
+        // Keep the same read pending across heartbeats; it is not cancel safe
+        let read_result = {
+            let read = read_bounded_line(&mut reader, &mut line_buf, limits.max_line_bytes);
+            tokio::pin!(read);
+            loop {
+                tokio::select! {
+                    read_result = &mut read => break read_result,
+                    _ = next_tick(&mut heartbeat) => {
+                        if let Some(ref heartbeat) = heartbeat {
+                            heartbeat.send(last_line_type.as_deref());
+                        }
+                    }
+                }
+            }
+        };
+        match read_result {
+            Ok((0, _)) => break,
+            Ok((len, true)) => {
+                eprintln!(
+                    "{} skipped a {}-byte line exceeding max_line_bytes",
+                    log_prefix, len
+                );
+                result.line_stats.oversized += 1;
+            }
             Ok(_) => {
+                let line = String::from_utf8_lossy(&line_buf);
+                if let Some((ref path, ref mut file)) = tee {
+                    if let Err(e) = file.write_all(&line_buf).await {
+                        result.warnings.push(format!(
+                            "Failed to write output_file {}: {}",
+                            path.display(),
+                            e
+                        ));
+                        tee = None;
+                    }
+                }
+
                 let trimmed = line.trim();
                 if trimmed.is_empty() {
                     continue;
@@ -687,22 +3058,32 @@ async fn run_internal(
                 let line_data: Value = match serde_json::from_str(trimmed) {
                     Ok(data) => data,
                     Err(e) => {
-                        eprintln!("droid-mcp-rs: failed to parse JSON line: {}", e);
+                        eprintln!("{} failed to parse JSON line: {}", log_prefix, e);
+                        result.line_stats.parse_failures += 1;
                         continue;
                     }
                 };
 
-                if let Some(sid) = line_data.get("session_id").and_then(|v| v.as_str()) {
-                    if !sid.is_empty() && result.session_id.is_empty() {
+                if let Some(sid) = extract_session_id(&line_data) {
+                    if result.session_id.is_empty() {
                         result.session_id = sid.to_string();
                     }
                 }
 
+                result
+                    .line_stats
+                    .record(line_data.get("type").and_then(|v| v.as_str()));
+                if let Some(line_type) = line_data.get("type").and_then(|v| v.as_str()) {
+                    last_line_type = Some(line_type.to_string());
+                }
+
                 if let Some(line_type) = line_data.get("type").and_then(|v| v.as_str()) {
                     if line_type == "error" {
                         result.success = false;
-                        if let Some(msg) = line_data.get("message").and_then(|v| v.as_str()) {
-                            result.error = Some(format!("droid error: {}", msg));
+                        if let Some(error) = DroidError::from_line(&line_data) {
+                            result.error = Some(format!("droid error: {}", error.message));
+                            result.error_code = Some(error.error_code());
+                            result.droid_error = Some(error);
                         }
                     }
 
@@ -712,19 +3093,24 @@ async fn run_internal(
                             line_data.get("finalText").and_then(|v| v.as_str())
                         {
                             let new_size = result.agent_messages.len() + final_text.len();
-                            if new_size > MAX_AGENT_MESSAGES_SIZE {
-                                if !result.agent_messages_truncated {
-                                    result.agent_messages.push_str(
-                                        "\n[... Agent messages truncated due to size limit ...]",
-                                    );
-                                    result.agent_messages_truncated = true;
-                                }
+                            if new_size > limits.agent_messages {
+                                result.agent_messages_truncated = true;
                             } else if !result.agent_messages_truncated {
                                 if !result.agent_messages.is_empty() && !final_text.is_empty() {
                                     result.agent_messages.push('\n');
                                 }
                                 result.agent_messages.push_str(final_text);
                             }
+                            if !result.agent_messages_truncated {
+                                result.completions.push(Completion {
+                                    text: final_text.to_string(),
+                                    step: line_data.get("step").and_then(|v| match v {
+                                        Value::String(s) => Some(s.clone()),
+                                        Value::Number(n) => Some(n.to_string()),
+                                        _ => None,
+                                    }),
+                                });
+                            }
                         }
                     }
 
@@ -733,20 +3119,12 @@ async fn run_internal(
                         if let Some(role) = line_data.get("role").and_then(|v| v.as_str()) {
                             if role == "assistant" {
                                 // Droid uses "text" field for intermediate messages
-                                if let Some(text) =
-                                    line_data.get("text").and_then(|v| v.as_str())
-                                {
+                                if let Some(text) = line_data.get("text").and_then(|v| v.as_str()) {
                                     let new_size = result.agent_messages.len() + text.len();
-                                    if new_size > MAX_AGENT_MESSAGES_SIZE {
-                                        if !result.agent_messages_truncated {
-                                            result.agent_messages.push_str(
-                                                "\n[... Agent messages truncated due to size limit ...]",
-                                            );
-                                            result.agent_messages_truncated = true;
-                                        }
+                                    if new_size > limits.agent_messages {
+                                        result.agent_messages_truncated = true;
                                     } else if !result.agent_messages_truncated {
-                                        if !result.agent_messages.is_empty() && !text.is_empty()
-                                        {
+                                        if !result.agent_messages.is_empty() && !text.is_empty() {
                                             result.agent_messages.push('\n');
                                         }
                                         result.agent_messages.push_str(text);
@@ -755,14 +3133,44 @@ async fn run_internal(
                             }
                         }
                     }
+
+                    // Spec mode emits its plan before executing; keep it apart from the transcript
+                    if line_type == "spec" || line_type == "plan" {
+                        if let Some(text) = ["text", "content", "spec", "plan"]
+                            .iter()
+                            .find_map(|key| line_data.get(*key).and_then(|v| v.as_str()))
+                        {
+                            let spec = result.spec.get_or_insert_with(String::new);
+                            if spec.len() + text.len() <= limits.agent_messages {
+                                if !spec.is_empty() && !text.is_empty() {
+                                    spec.push('\n');
+                                }
+                                spec.push_str(text);
+                            }
+                        }
+                    }
+
+                    // Newer droid versions end with a summary line carrying the final status
+                    if line_type == "result" || line_type == "summary" {
+                        apply_summary_line(&mut result, &line_data);
+                    }
+
+                    // An explicit flag on a completion is authoritative too
+                    if line_type == "completion" {
+                        if let Some(success) = line_data.get("success").and_then(|v| v.as_bool()) {
+                            result.reported_success = Some(success);
+                        }
+                    }
                 }
 
-                if let Ok(map) =
-                    serde_json::from_value::<HashMap<String, Value>>(line_data.clone())
-                {
-                    let message_size = serde_json::to_string(&map).map(|s| s.len()).unwrap_or(0);
-                    if all_messages_size + message_size <= MAX_ALL_MESSAGES_SIZE {
-                        all_messages_size += message_size;
+                if let Value::Object(object) = line_data {
+                    let mut map: HashMap<String, Value> = object.into_iter().collect();
+                    let message_size = serialized_size(&map);
+                    if result.all_messages_bytes + message_size <= limits.all_messages {
+                        result.all_messages_bytes += message_size;
+                        // Namespaced so it never replaces a field droid itself sent
+                        map.entry(RECEIVED_MS_KEY.to_string())
+                            .or_insert_with(|| Value::from(started.elapsed().as_millis() as u64));
                         result.all_messages.push(map);
                     } else if !result.all_messages_truncated {
                         result.all_messages_truncated = true;
@@ -770,49 +3178,375 @@ async fn run_internal(
                 }
             }
             Err(e) => {
-                eprintln!("droid-mcp-rs: failed to read line: {}", e);
+                eprintln!("{} failed to read line: {}", log_prefix, e);
                 break;
             }
         }
     }
 
-    let status = child
-        .wait()
-        .await
-        .context("Failed to wait for droid command")?;
+    if result.line_stats.oversized > 0 {
+        result.warnings.push(format!(
+            "Skipped {} stream-json line(s) longer than max_line_bytes ({} bytes)",
+            result.line_stats.oversized, limits.max_line_bytes
+        ));
+    }
+
+    if let Some((path, mut file)) = tee {
+        if let Err(e) = file.flush().await {
+            result.warnings.push(format!(
+                "Failed to write output_file {}: {}",
+                path.display(),
+                e
+            ));
+        }
+    }
+
+    result
+}
+
+/// Keep droid from emitting color codes even when it misdetects a terminal
+///
+/// droid has no dedicated flag for this; it honors the standard color environment variables.
+fn apply_no_color(cmd: &mut Command, cfg: &ServerConfig) {
+    if cfg.force_no_color {
+        cmd.env("NO_COLOR", "1");
+        cmd.env("FORCE_COLOR", "0");
+    }
+}
+
+/// Signal that terminated the process, if it did not exit normally
+#[cfg(unix)]
+fn exit_signal(status: &std::process::ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal()
+}
+
+#[cfg(not(unix))]
+fn exit_signal(_status: &std::process::ExitStatus) -> Option<i32> {
+    None
+}
+
+/// Names of the signals whose numbers are the same on every Unix
+fn signal_name(signal: i32) -> Option<&'static str> {
+    Some(match signal {
+        1 => "SIGHUP",
+        2 => "SIGINT",
+        3 => "SIGQUIT",
+        4 => "SIGILL",
+        6 => "SIGABRT",
+        8 => "SIGFPE",
+        9 => "SIGKILL",
+        11 => "SIGSEGV",
+        13 => "SIGPIPE",
+        14 => "SIGALRM",
+        15 => "SIGTERM",
+        _ => return None,
+    })
+}
+
+async fn run_internal(
+    cfg: &ServerConfig,
+    opts: &Options,
+    prompt: &str,
+    mut warnings: Vec<String>,
+) -> Result<DroidResult> {
+    let droid_bin = match opts.droid_bin {
+        Some(ref droid_bin) => droid_bin.to_string_lossy().into_owned(),
+        None => resolve_droid_bin(),
+    };
+    let log_prefix = log_prefix(opts);
+
+    // Get model info for logging and display
+    let (model_display, model_warning) = options_model_info(opts);
+
+    // Log to stderr for debugging
+    if verbosity() >= Verbosity::Normal {
+        if let Some(ref info) = model_display {
+            eprintln!("{} {}", log_prefix, info);
+        }
+    }
+
+    warnings.extend(model_warning);
+
+    let args = build_args(opts, prompt);
+    if verbosity() >= Verbosity::Verbose {
+        // Without --file the prompt is the last argument; don't dump its content
+        let mut shown: Vec<String> = args
+            .iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        if opts.file.is_none() {
+            if let Some(last) = shown.last_mut() {
+                *last = format!("<prompt: {} bytes>", prompt.len());
+            }
+        }
+        eprintln!("{} {} {}", log_prefix, droid_bin, shown.join(" "));
+    }
+
+    let mut cmd = Command::new(&droid_bin);
+    cmd.args(args);
+    apply_no_color(&mut cmd, cfg);
+
+    cmd.stdin(Stdio::null());
+    cmd.stdout(Stdio::piped());
+    cmd.stderr(Stdio::piped());
+    cmd.kill_on_drop(true);
 
-    let stderr_output = match stderr_handle.await {
-        Ok(output) => output,
+    let mut child = match cmd.spawn() {
+        Ok(child) => child,
         Err(e) => {
-            eprintln!("droid-mcp-rs: failed to join stderr task: {}", e);
+            // A missing binary is the most common misconfiguration; say how to fix it
+            let (error, code) = if e.kind() == std::io::ErrorKind::NotFound {
+                (
+                    format!(
+                        "droid binary '{}' was not found. Install droid, add it to PATH, or set DROID_BIN to its location",
+                        droid_bin
+                    ),
+                    ErrorCode::DroidNotFound,
+                )
+            } else {
+                (
+                    format!(
+                        "Failed to start droid '{}' in '{}': {}",
+                        droid_bin,
+                        opts.working_dir.display(),
+                        e
+                    ),
+                    ErrorCode::SpawnFailed,
+                )
+            };
+            return Ok(DroidResult {
+                success: false,
+                error: Some(error),
+                error_code: Some(code),
+                warnings,
+                model_info: model_display,
+                ..DroidResult::default()
+            });
+        }
+    };
+
+    let stdout = child
+        .stdout
+        .take()
+        .context("Failed to get stdout from droid command")?;
+    let stderr = child
+        .stderr
+        .take()
+        .context("Failed to get stderr from droid command")?;
+
+    let stderr_prefix = log_prefix.clone();
+    let mut stderr_handle = tokio::spawn(async move {
+        let mut stderr_output = String::new();
+        let mut stderr_reader = BufReader::new(stderr);
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+            match stderr_reader.read_line(&mut line).await {
+                Ok(0) => break,
+                Ok(_) => {
+                    // Surface droid diagnostics live instead of only after exit
+                    let live = line.trim_end();
+                    if !live.is_empty() && verbosity() >= Verbosity::Normal {
+                        eprintln!("{} [droid stderr] {}", stderr_prefix, live);
+                    }
+                    if stderr_output.len() + line.len() <= MAX_STDERR_SIZE {
+                        stderr_output.push_str(&line);
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+        stderr_output
+    });
+
+    let tee = match opts.output_file {
+        Some(ref path) => match tokio::fs::File::create(path).await {
+            Ok(file) => Some((path.clone(), tokio::io::BufWriter::new(file))),
+            Err(e) => {
+                warnings.push(format!(
+                    "Failed to create output_file {}: {}",
+                    path.display(),
+                    e
+                ));
+                None
+            }
+        },
+        None => None,
+    };
+
+    let heartbeat = opts.progress.clone().map(|sender| {
+        Heartbeat::new(
+            sender,
+            cfg.progress_interval_secs
+                .unwrap_or(DEFAULT_PROGRESS_INTERVAL_SECS),
+            opts.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS),
+        )
+    });
+
+    let active = ActiveRun::register();
+    let parse = parse_stream_tee(
+        BufReader::new(stdout),
+        Limits {
+            max_line_bytes: cfg.max_line_bytes.unwrap_or(DEFAULT_MAX_LINE_BYTES),
+            ..Limits::default()
+        },
+        tee,
+        heartbeat,
+        &log_prefix,
+    );
+    let mut result = tokio::select! {
+        result = parse => result,
+        _ = active.cancelled() => {
+            // droid's own children may hold its pipes open, so don't wait for them to close
+            let _ = child.kill().await;
+            stderr_handle.abort();
+            eprintln!("{} droid cancelled by cancel_all", log_prefix);
+            return Ok(DroidResult {
+                success: false,
+                error: Some("droid run was cancelled by cancel_all".to_string()),
+                error_code: Some(ErrorCode::Cancelled),
+                warnings,
+                model_info: model_display,
+                ..DroidResult::default()
+            });
+        }
+    };
+    drop(active);
+    warnings.append(&mut result.warnings);
+    result.warnings = warnings;
+    result.model_info = model_display;
+
+    // droid occasionally closes stdout well before exiting; don't hang on it once the
+    // stream is complete (None = killed after the grace window, exit status unknown)
+    let grace =
+        std::time::Duration::from_secs(cfg.exit_grace_secs.unwrap_or(DEFAULT_EXIT_GRACE_SECS));
+    let status = match tokio::time::timeout(grace, child.wait()).await {
+        Ok(status) => Some(status.context("Failed to wait for droid command")?),
+        Err(_) => {
+            let _ = child.kill().await;
+            result.warnings.push(format!(
+                "droid did not exit within {} seconds of closing its output and was killed",
+                grace.as_secs()
+            ));
+            None
+        }
+    };
+
+    // A process droid left behind can keep stderr open after droid itself is gone
+    let stderr_drain = std::time::Duration::from_secs(STDERR_DRAIN_SECS);
+    let mut stderr_output = match tokio::time::timeout(stderr_drain, &mut stderr_handle).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => {
+            eprintln!("{} failed to join stderr task: {}", log_prefix, e);
+            String::new()
+        }
+        Err(_) => {
+            stderr_handle.abort();
+            result.warnings.push(format!(
+                "droid stderr stayed open {} seconds after droid exited; stopped reading it",
+                STDERR_DRAIN_SECS
+            ));
             String::new()
         }
     };
 
-    if !status.success() {
+    if cfg.strip_ansi {
+        stderr_output = strip_ansi(&stderr_output);
+        result.agent_messages = strip_ansi(&result.agent_messages);
+        for completion in &mut result.completions {
+            completion.text = strip_ansi(&completion.text);
+        }
+    }
+
+    if cfg.normalize_messages {
+        result.agent_messages = normalize_message(&result.agent_messages);
+    }
+
+    if verbosity() >= Verbosity::Verbose {
+        eprintln!("{} parsed lines: {}", log_prefix, result.line_stats);
+    }
+
+    if let Some(status) = status.filter(|status| !status.success()) {
         result.success = false;
         if result.error.is_none() {
-            let error_msg = if !stderr_output.is_empty() {
-                format!(
-                    "droid exited with code {:?}. stderr: {}",
-                    status.code(),
-                    stderr_output
-                )
-            } else {
-                format!("droid exited with code {:?}", status.code())
-            };
-            result.error = Some(error_msg);
+            // Any stderr is attached separately below, keeping the reason short
+            match exit_signal(&status) {
+                Some(signal) => {
+                    result.error = Some(format!(
+                        "droid was killed by signal {}{}",
+                        signal,
+                        signal_name(signal)
+                            .map(|name| format!(" ({})", name))
+                            .unwrap_or_default()
+                    ));
+                    result.error_code = Some(ErrorCode::KilledBySignal);
+                }
+                None => {
+                    result.error = Some(format!("droid exited with code {:?}", status.code()));
+                    result.error_code = Some(ErrorCode::NonZeroExit);
+                }
+            }
         }
     }
 
+    // A reported final status is authoritative over the exit code and error lines
+    match result.reported_success {
+        Some(true) => {
+            result.success = true;
+            result.error = None;
+            result.error_code = None;
+        }
+        Some(false) => {
+            result.success = false;
+            if result.error.is_none() {
+                result.error = Some(match result.summary {
+                    Some(ref summary) => format!("droid reported failure: {}", summary),
+                    None => "droid reported failure".to_string(),
+                });
+                result.error_code = Some(ErrorCode::DroidError);
+            }
+        }
+        None => {}
+    }
+
+    // Rate limits warrant a timed backoff rather than an immediate failure
+    result.rate_limited = !result.success
+        && (result.error_code == Some(ErrorCode::RateLimited)
+            || result.error.as_deref().is_some_and(is_rate_limit_message)
+            || is_rate_limit_message(&stderr_output));
+    if result.rate_limited {
+        result.error_code = Some(ErrorCode::RateLimited);
+    }
+
     if result.session_id.is_empty() {
         result.success = false;
-        result.error = Some("No session_id received from droid".to_string());
+        // A crash before the session line explains more than the missing session does
+        if result.error.is_none() {
+            result.error = Some("No session_id received from droid".to_string());
+            result.error_code = Some(ErrorCode::NoSession);
+        }
     }
 
     if result.agent_messages.is_empty() && result.success {
-        result.success = false;
-        result.error = Some("No agent messages received from droid".to_string());
+        // A clean exit with a session id is a valid (if silent) run unless configured otherwise
+        if cfg.require_agent_messages {
+            result.success = false;
+            result.error = Some("No agent messages received from droid".to_string());
+            result.error_code = Some(ErrorCode::NoAgentMessages);
+        } else {
+            result
+                .warnings
+                .push("Droid completed without producing any agent messages".to_string());
+        }
+    }
+
+    if !result.success
+        && opts.include_stderr_on_error.unwrap_or(true)
+        && !stderr_output.trim().is_empty()
+    {
+        result.stderr = Some(stderr_output.trim_end().to_string());
     }
 
     Ok(result)
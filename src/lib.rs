@@ -1,2 +1,8 @@
-pub mod server;
+pub mod audit;
+pub mod context;
 pub mod droid;
+pub mod lockfile;
+pub mod output;
+pub mod server;
+pub mod sessions;
+pub mod stats;
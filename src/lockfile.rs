@@ -0,0 +1,172 @@
+//! Cross-process lockfile guarding modifying runs (`use_lockfile` config)
+
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Name of the lockfile written into the working directory
+pub const LOCK_FILE: &str = ".droid-mcp.lock";
+
+/// Contents of the lockfile: who holds it and since when
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockInfo {
+    pub pid: u32,
+    /// Seconds since the Unix epoch
+    pub started_at: u64,
+}
+
+/// Why [`acquire`] failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AcquireError {
+    /// A live process holds the lock
+    Held(String),
+    /// The lockfile could not be created or a stale one removed
+    Io(String),
+}
+
+impl std::fmt::Display for AcquireError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AcquireError::Held(message) | AcquireError::Io(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for AcquireError {}
+
+/// Removes the lockfile when the run finishes
+#[derive(Debug)]
+pub struct LockGuard {
+    path: PathBuf,
+}
+
+impl LockGuard {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for LockGuard {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            eprintln!(
+                "droid-mcp-rs: failed to remove lockfile {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Whether `pid` still names a running process
+///
+/// Only `ESRCH` counts as exited; any other failure (another user's process answers
+/// `EPERM`) keeps the lock live.
+#[cfg(unix)]
+fn process_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return true;
+    };
+    // SAFETY: signal 0 sends nothing; kill only checks that the process exists
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        return true;
+    }
+    std::io::Error::last_os_error().raw_os_error() != Some(libc::ESRCH)
+}
+
+/// Without a portable liveness check every lock is treated as live
+#[cfg(not(unix))]
+fn process_alive(_pid: u32) -> bool {
+    true
+}
+
+/// Write our [`LockInfo`] to a private temp file, then hard-link it into place
+///
+/// The link fails with `AlreadyExists` when the lock is held, and otherwise appears with
+/// its contents complete, so other processes never read a half-written lock as stale.
+fn publish(path: &Path) -> std::io::Result<()> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let info = LockInfo {
+        pid: std::process::id(),
+        started_at: crate::audit::now_secs(),
+    };
+    let json = serde_json::to_string(&info)?;
+    let tmp = path.with_extension(format!(
+        "lock.{}.{}",
+        info.pid,
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let linked = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&tmp)
+        .and_then(|mut file| file.write_all(json.as_bytes()))
+        .and_then(|()| std::fs::hard_link(&tmp, path));
+    let _ = std::fs::remove_file(&tmp);
+    linked
+}
+
+/// Create the lockfile in `working_dir`, reclaiming one left by an exited process
+///
+/// Returns the guard plus a warning when a stale lock was reclaimed. Fails when a live
+/// process holds the lock. Any lock left by this process is stale: in-process runs are
+/// already serialized before they get here.
+pub fn acquire(working_dir: &Path) -> Result<(LockGuard, Option<String>), AcquireError> {
+    let path = working_dir.join(LOCK_FILE);
+    let mut warning = None;
+
+    for _ in 0..2 {
+        match publish(&path) {
+            Ok(()) => return Ok((LockGuard { path: path.clone() }, warning)),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+            Err(e) => {
+                return Err(AcquireError::Io(format!(
+                    "Failed to create lockfile {}: {}",
+                    path.display(),
+                    e
+                )))
+            }
+        }
+
+        let holder = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|raw| serde_json::from_str::<LockInfo>(&raw).ok());
+        match holder {
+            Some(info) if info.pid != std::process::id() && process_alive(info.pid) => {
+                return Err(AcquireError::Held(format!(
+                    "Another modifying run (pid {}, started at {}) holds {}; retry once it finishes or remove the file if that process is not droid-mcp-rs",
+                    info.pid,
+                    info.started_at,
+                    path.display()
+                )));
+            }
+            Some(info) => {
+                warning = Some(format!(
+                    "Reclaimed stale lockfile {} left by exited process {}",
+                    path.display(),
+                    info.pid
+                ));
+            }
+            None => {
+                warning = Some(format!("Reclaimed unreadable lockfile {}", path.display()));
+            }
+        }
+        if let Err(e) = std::fs::remove_file(&path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                return Err(AcquireError::Io(format!(
+                    "Failed to remove stale lockfile {}: {}",
+                    path.display(),
+                    e
+                )));
+            }
+        }
+    }
+
+    Err(AcquireError::Held(format!(
+        "Failed to acquire lockfile {}: it was recreated by another process",
+        path.display()
+    )))
+}
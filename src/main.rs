@@ -1,7 +1,9 @@
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use clap::Parser;
-use droid_mcp_rs::server::DroidServer;
+use droid_mcp_rs::droid::{Options, Verbosity};
+use droid_mcp_rs::server::{DroidOutput, DroidServer};
 use rmcp::{transport::stdio, ServiceExt};
+use std::path::PathBuf;
 
 /// MCP server wrapping the Droid CLI for AI-assisted coding tasks
 #[derive(Parser)]
@@ -14,11 +16,29 @@ use rmcp::{transport::stdio, ServiceExt};
   DROID_BIN                    Override the droid binary path
                                Default: 'droid' (Linux/macOS) or 'droid.exe' (Windows)
                                Typical installation: ~/bin/droid or C:\\Users\\<user>\\bin\\droid.exe
+                               When unset and droid is not on PATH, ~/bin, ~/.local/bin
+                               (or %USERPROFILE%\\bin on Windows) are probed
   DROID_MCP_CONFIG_PATH        Path to configuration file (default: './droid-mcp.config.json')
+                               The --config flag takes precedence over this variable
+                               Without either, ./droid-mcp.config.json is used if present, else
+                               $XDG_CONFIG_HOME/droid-mcp/config.json (default ~/.config; macOS:
+                               ~/Library/Application Support; Windows: %APPDATA%)
+
+SIGNALS (Unix):
+  SIGHUP                       Reload the server and Factory configuration; in-flight runs
+                               keep the configuration they started with
 
 USAGE:
   This server communicates via stdio using the Model Context Protocol (MCP).
+  For scripting, --once --prompt TEXT [--cwd PATH] [--auto LEVEL] runs droid a
+  single time without MCP and prints the result.
   It should be configured in your MCP client (e.g., Claude Desktop) settings.
+  --selftest checks the installation end to end against a generated fake droid.
+  --list-models prints the custom models from ~/.factory/config.json with the
+  references to pass as model, marking the default.
+  On Unix, --transport uds --socket PATH serves MCP on a Unix domain socket
+  instead (owner-only permissions; the socket file is removed on shutdown;
+  --force replaces a stale socket but never a regular file).
 
   Example MCP client configuration:
     {
@@ -34,13 +54,36 @@ SUPPORTED PARAMETERS:
 
   PROMPT (string)              Task instruction to send to Droid (mutually exclusive with file)
   file (path)                  Read prompt from file (mutually exclusive with PROMPT)
+  prompt_env (string)          Environment variable of the server holding the prompt
+                               (mutually exclusive with PROMPT and file; only variables
+                               listed in allowed_prompt_env in the config)
   auto (string)                Autonomy level: low, medium, high (omit for DEFAULT/read-only)
   SESSION_ID (string)          Resume an existing session (from previous response)
-  cwd (path)                   Working directory for the Droid session (default: current directory)
+  cwd (path)                   Working directory for the Droid session
+                               (default: --cwd flag, then config default_cwd, then current directory)
   model (string)               Model to use (overrides default)
   enabled_tools (string)       Comma/space-separated list of tools to enable
   disabled_tools (string)      Comma/space-separated list of tools to disable
   timeout_secs (number)        Timeout in seconds (default: 600, max: 3600)
+  max_turns (number)           Maximum agent turns before stopping (maps to --max-turns)
+  accumulate_history (bool)    Return the running conversation of the session, not just this turn
+  extra_args (string[])        Extra droid CLI args appended after configured additional_args
+  passthrough_flags (object)   Extra droid flags as {"key": "value"}, passed as --key value
+  output_mode (string)         Response mode: structured (default), text (message only) or ndjson (raw transcript)
+  response_format (string)     Structured response encoding: toon (default), json or text
+  include_stderr_on_error (bool) Return droid's stderr as a separate stderr field on failure (default: true)
+  use_droid_md (bool)          Prepend DROID.md from the working directory (default: true)
+  system_prompt (string)       Extra system prompt text, placed after DROID.md (combined max 1 MB)
+  require_droid_md (bool)      Fail before running droid if DROID.md is missing (default: false)
+  context_file (path)          Reference file appended to PROMPT in a <context> block (max 1 MB)
+  context_globs (string[])     Globs of files concatenated into a <context> block (honors .droidignore)
+  output_file (path)           Save the raw stream-json output (inside the working directory)
+  include_all_messages (bool)  Also return individual completions and raw stream-json lines
+  return_diff (bool)           Return git diff and untracked files after a successful run
+  scope_paths (string[])       Working-directory-relative paths droid may touch
+                               (passed with the configured scope_flag; warns when none is set)
+  droid_bin (path)             Absolute path of a droid binary for this call
+                               (requires allow_client_binary_override in the config)
 
 DROID.MD SUPPORT:
   If a DROID.md file exists in the working directory, its content will be
@@ -55,15 +98,17 @@ AUTONOMY LEVELS:
   medium                       Package installation, git commits, local builds
   high                         Git push, production deployments, script execution
 
-  Note: 'high' autonomy is disabled by default. Set allow_high_autonomy=true
-  in droid-mcp.config.json to enable it.
+  Note: calls that omit auto run at default_auto from droid-mcp.config.json,
+  or at 'high' when it is unset ('low' with safe_default_auto=true).
+  'high' is allowed unless allow_high_autonomy=false.
 
 SECURITY:
-  - By default, only read-only operations are allowed (DEFAULT autonomy)
-  - Set autonomy level explicitly to enable modifications
+  - Without default_auto or safe_default_auto, calls that omit auto run at
+    HIGH autonomy; the server warns about this at startup and in each result
+  - Pass auto explicitly, or set default_auto, to choose the level
   - Timeouts are enforced to prevent unbounded execution
-  - High autonomy level requires explicit configuration
-  - See autonomy levels above for security controls
+  - allow_high_autonomy=false refuses high and skip_permissions_unsafe;
+    min_auto/max_auto bound the level, force_readonly pins every run to DEFAULT
 
 CONFIGURATION FILE (droid-mcp.config.json):
   {
@@ -77,16 +122,402 @@ CONFIGURATION FILE (droid-mcp.config.json):
 
 For more information, visit: https://github.com/jakvbs/droid-mcp-rs"
 )]
-struct Cli {}
+#[command(group(
+    clap::ArgGroup::new("force_target")
+        .args(["generate_config", "socket"])
+        .multiple(true)
+))]
+struct Cli {
+    /// Print the JSON schema of the droid tool input and exit
+    #[arg(long)]
+    dump_schema: bool,
+
+    /// Run a canned prompt through the full pipeline against a generated fake droid,
+    /// print PASS or FAIL and exit (non-zero on failure)
+    #[arg(long, conflicts_with = "once")]
+    selftest: bool,
+
+    /// Print the custom models from ~/.factory/config.json with their `custom:` references
+    /// and the default model, then exit
+    #[arg(long, conflicts_with_all = ["once", "selftest"])]
+    list_models: bool,
+
+    /// Write a default config file listing every field to PATH
+    /// (default: ./droid-mcp.config.json) and exit
+    #[arg(
+        long,
+        value_name = "PATH",
+        num_args = 0..=1,
+        default_missing_value = "droid-mcp.config.json"
+    )]
+    generate_config: Option<PathBuf>,
+
+    /// Overwrite an existing file with --generate-config, or replace an existing --socket
+    #[arg(long, requires = "force_target")]
+    force: bool,
+
+    /// How MCP clients connect to the server
+    #[arg(long, value_enum, default_value_t = Transport::Stdio)]
+    transport: Transport,
+
+    /// Socket path for --transport uds
+    #[arg(long, value_name = "PATH", required_if_eq("transport", "uds"))]
+    socket: Option<PathBuf>,
+
+    /// Only log errors and warnings to stderr (no per-run model info or droid stderr)
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Log extra diagnostics (per-run parsed line counts, droid arguments) to stderr
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// Path to the configuration file
+    ///
+    /// Precedence: --config, then DROID_MCP_CONFIG_PATH, then ./droid-mcp.config.json,
+    /// then the user config (e.g. $XDG_CONFIG_HOME/droid-mcp/config.json)
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Default working directory when a client omits cwd (overrides config default_cwd)
+    #[arg(long, value_name = "PATH")]
+    cwd: Option<PathBuf>,
+
+    /// Run droid once with --prompt, print the result and exit (no MCP transport);
+    /// exits non-zero when the run fails
+    #[arg(long, requires = "prompt")]
+    once: bool,
+
+    /// Task instruction for --once
+    #[arg(long, value_name = "TEXT", requires = "once")]
+    prompt: Option<String>,
+
+    /// Autonomy level for --once (default: the configured default_auto)
+    #[arg(
+        long,
+        value_name = "LEVEL",
+        requires = "once",
+        value_parser = ["low", "medium", "high"]
+    )]
+    auto: Option<String>,
+
+    /// Model for --once (default: the configured default model)
+    #[arg(long, value_name = "MODEL", requires = "once")]
+    model: Option<String>,
+}
+
+/// MCP transport the server listens on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Transport {
+    /// stdin/stdout, for clients that spawn the server
+    Stdio,
+    /// Unix domain socket at --socket (Unix only)
+    Uds,
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let _cli = Cli::parse();
+    let cli = Cli::parse();
+    droid_mcp_rs::droid::set_verbosity(if cli.quiet {
+        Verbosity::Quiet
+    } else if cli.verbose {
+        Verbosity::Verbose
+    } else {
+        Verbosity::Normal
+    });
+
+    // Before any config override: the selftest runs against its own config
+    if cli.selftest {
+        return selftest().await;
+    }
+
+    if let Some(config) = cli.config {
+        droid_mcp_rs::droid::set_config_path(config);
+    }
+
+    if let Some(path) = cli.generate_config {
+        droid_mcp_rs::droid::write_default_config(&path, cli.force)?;
+        eprintln!("droid-mcp-rs: wrote default config to {}", path.display());
+        return Ok(());
+    }
+
+    if cli.dump_schema {
+        let schema = DroidServer::new()
+            .droid_input_schema()
+            .context("droid tool schema is not registered")?;
+        println!("{}", serde_json::to_string_pretty(&schema)?);
+        return Ok(());
+    }
+
+    if cli.list_models {
+        list_models();
+        return Ok(());
+    }
+
+    if droid_mcp_rs::droid::default_auto_is_high() {
+        eprintln!(
+            "droid-mcp-rs: WARNING: calls that omit auto will run at HIGH autonomy. \
+             Set default_auto (or safe_default_auto=true) in the config to change this."
+        );
+    }
+
+    #[cfg(unix)]
+    if let Err(e) = droid_mcp_rs::droid::spawn_reload_on_sighup() {
+        eprintln!("droid-mcp-rs: failed to install SIGHUP handler: {}", e);
+    }
+
+    if cli.once {
+        return run_once(cli).await;
+    }
+
+    let mut server = DroidServer::new();
+    if let Some(cwd) = cli.cwd {
+        let cwd = cwd
+            .canonicalize()
+            .with_context(|| format!("--cwd {} is not accessible", cwd.display()))?;
+        if !cwd.is_dir() {
+            bail!("--cwd {} is not a directory", cwd.display());
+        }
+        server = server.with_default_cwd(cwd);
+    }
+
+    match cli.transport {
+        Transport::Stdio => {
+            let service = server.serve(stdio()).await.inspect_err(|e| {
+                eprintln!("serving error: {:?}", e);
+            })?;
+
+            service.waiting().await?;
+            Ok(())
+        }
+        #[cfg(unix)]
+        Transport::Uds => {
+            let socket = cli.socket.context("--transport uds requires --socket")?;
+            serve_uds(server, socket, cli.force).await
+        }
+        #[cfg(not(unix))]
+        Transport::Uds => bail!("--transport uds is only supported on Unix"),
+    }
+}
+
+/// Run a single droid invocation from the command line and print its TOON result
+async fn run_once(cli: Cli) -> Result<()> {
+    let server = DroidServer::new();
+    let working_dir = server
+        .resolve_working_dir(cli.cwd)
+        .map_err(|e| anyhow::anyhow!("{}", e.message))?;
 
-    let service = DroidServer::new().serve(stdio()).await.inspect_err(|e| {
-        eprintln!("serving error: {:?}", e);
-    })?;
+    let request_id = uuid::Uuid::new_v4().to_string();
+    let opts = Options {
+        prompt: cli.prompt,
+        working_dir,
+        auto: cli.auto,
+        model: cli.model,
+        additional_args: droid_mcp_rs::droid::default_additional_args(),
+        request_id: Some(request_id.clone()),
+        ..Default::default()
+    };
+    let result = droid_mcp_rs::droid::run(opts).await?;
 
-    service.waiting().await?;
+    let output = DroidOutput::from_result(&result, request_id, false);
+    let toon_output = toon_format::encode_default(&output)
+        .map_err(|e| anyhow::anyhow!("Failed to serialize output: {}", e))?;
+    println!("{}", toon_output);
+    if !result.success {
+        std::process::exit(1);
+    }
     Ok(())
 }
+
+/// Print the Factory custom models, one `label: reference` line each, marking the default
+fn list_models() {
+    let models = droid_mcp_rs::droid::list_custom_models();
+    if models.is_empty() {
+        println!("No custom models in ~/.factory/config.json; droid uses its own default model");
+        return;
+    }
+
+    let default_model = droid_mcp_rs::droid::resolve_model(None);
+    println!("Custom models from ~/.factory/config.json:");
+    for model in &models {
+        let marker = if default_model.as_deref() == Some(model.reference.as_str()) {
+            " (default)"
+        } else {
+            ""
+        };
+        println!("  {}: {}{}", model.label, model.reference, marker);
+    }
+}
+
+/// Stand-in for droid used by --selftest: answers any prompt with a fixed stream
+#[cfg(unix)]
+const SELFTEST_HARNESS: &str = r#"#!/bin/sh
+echo '{"type":"system","session_id":"selftest-session"}'
+echo '{"type":"message","role":"assistant","text":"selftest running"}'
+echo '{"type":"completion","finalText":"selftest ok"}'
+"#;
+
+/// Server config used by --selftest: defaults, plus the harness as a per-call binary
+#[cfg(unix)]
+const SELFTEST_CONFIG: &str = r#"{"allow_client_binary_override": true}"#;
+
+/// Run `droid::run` end to end against a harness written to a temp dir, printing PASS/FAIL
+///
+/// Uses a generated config rather than the user's, so post-run hooks, the audit log and
+/// autonomy policy stay out of it, and passes the harness as `droid_bin` instead of
+/// setting `DROID_BIN` in the running process.
+#[cfg(unix)]
+async fn selftest() -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = std::env::temp_dir().join(format!("droid-mcp-selftest-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    let harness = dir.join("droid");
+    std::fs::write(&harness, SELFTEST_HARNESS)
+        .with_context(|| format!("failed to write {}", harness.display()))?;
+    std::fs::set_permissions(&harness, std::fs::Permissions::from_mode(0o700))?;
+    let config = dir.join("droid-mcp.config.json");
+    std::fs::write(&config, SELFTEST_CONFIG)
+        .with_context(|| format!("failed to write {}", config.display()))?;
+    droid_mcp_rs::droid::set_config_path(config);
+
+    let opts = Options {
+        prompt: Some("selftest".to_string()),
+        working_dir: dir.clone(),
+        auto: Some("low".to_string()),
+        droid_bin: Some(harness),
+        ..Default::default()
+    };
+    let outcome = droid_mcp_rs::droid::run(opts).await;
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let failure = match outcome {
+        Err(e) => Some(format!("droid::run failed: {:#}", e)),
+        Ok(result) if !result.success => Some(format!(
+            "run reported failure: {}",
+            result.error.unwrap_or_default()
+        )),
+        Ok(result) if result.session_id != "selftest-session" => Some(format!(
+            "expected session id 'selftest-session', got '{}'",
+            result.session_id
+        )),
+        Ok(result) if result.agent_messages != "selftest running\nselftest ok" => Some(format!(
+            "unexpected agent messages: {:?}",
+            result.agent_messages
+        )),
+        Ok(_) => None,
+    };
+
+    match failure {
+        None => {
+            println!("PASS: droid-mcp-rs selftest");
+            Ok(())
+        }
+        Some(reason) => {
+            println!("FAIL: droid-mcp-rs selftest: {}", reason);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn selftest() -> Result<()> {
+    println!("FAIL: droid-mcp-rs selftest: --selftest is only supported on Unix");
+    std::process::exit(1);
+}
+
+/// Serve every connection on a Unix domain socket until SIGINT/SIGTERM
+#[cfg(unix)]
+async fn serve_uds(server: DroidServer, socket: PathBuf, force: bool) -> Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+    use tokio::signal::unix::{signal, SignalKind};
+
+    if let Ok(metadata) = socket.symlink_metadata() {
+        if !force {
+            bail!(
+                "{} already exists; pass --force to replace it",
+                socket.display()
+            );
+        }
+        // --force only replaces a stale socket, never a file that happens to share the path
+        if !metadata.file_type().is_socket() {
+            bail!(
+                "{} exists and is not a socket; refusing to replace it",
+                socket.display()
+            );
+        }
+        std::fs::remove_file(&socket)
+            .with_context(|| format!("failed to remove {}", socket.display()))?;
+    }
+
+    // Access control is filesystem permissions: only the owner may connect by default.
+    // Bind inside a private directory and move the socket into place once it is 0600,
+    // so it is never reachable with the umask's permissions.
+    let file_name = socket
+        .file_name()
+        .with_context(|| format!("{} is not a file path", socket.display()))?;
+    let staging = socket.with_file_name(format!(
+        ".{}.{}",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+    std::fs::DirBuilder::new()
+        .mode(0o700)
+        .create(&staging)
+        .with_context(|| format!("failed to create {}", staging.display()))?;
+    let staged = staging.join(file_name);
+    let bound = tokio::net::UnixListener::bind(&staged)
+        .with_context(|| format!("failed to bind {}", socket.display()))
+        .and_then(|listener| {
+            std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o600))
+                .with_context(|| {
+                    format!("failed to restrict permissions of {}", socket.display())
+                })?;
+            std::fs::rename(&staged, &socket)
+                .with_context(|| format!("failed to move socket to {}", socket.display()))?;
+            Ok(listener)
+        });
+    let _ = std::fs::remove_file(&staged);
+    let _ = std::fs::remove_dir(&staging);
+    let listener = bound?;
+    let _cleanup = SocketCleanup(socket.clone());
+    eprintln!("droid-mcp-rs: listening on {}", socket.display());
+
+    let mut sigterm = signal(SignalKind::terminate())?;
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let stream = match accepted {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        eprintln!("droid-mcp-rs: failed to accept connection: {}", e);
+                        continue;
+                    }
+                };
+                let server = server.clone();
+                tokio::spawn(async move {
+                    match server.serve(stream.into_split()).await {
+                        Ok(service) => {
+                            let _ = service.waiting().await;
+                        }
+                        Err(e) => eprintln!("serving error: {:?}", e),
+                    }
+                });
+            }
+            _ = tokio::signal::ctrl_c() => break,
+            _ = sigterm.recv() => break,
+        }
+    }
+    Ok(())
+}
+
+/// Removes the socket file when the server stops
+#[cfg(unix)]
+struct SocketCleanup(PathBuf);
+
+#[cfg(unix)]
+impl Drop for SocketCleanup {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
@@ -0,0 +1,66 @@
+//! Encodings of the droid tool's structured response (`response_format` argument)
+
+use crate::droid;
+use crate::server::DroidOutput;
+
+/// Names accepted by `response_format`, default first
+pub const RESPONSE_FORMATS: &[&str] = &["toon", "json", "text"];
+
+/// Renders a [`DroidOutput`] into the text returned to the client
+pub trait OutputEncoder {
+    fn encode(&self, output: &DroidOutput) -> Result<String, String>;
+}
+
+/// Token-efficient TOON, the default
+pub struct ToonEncoder;
+
+impl OutputEncoder for ToonEncoder {
+    fn encode(&self, output: &DroidOutput) -> Result<String, String> {
+        toon_format::encode_default(output).map_err(|e| e.to_string())
+    }
+}
+
+/// Compact JSON, for clients that parse the response themselves
+pub struct JsonEncoder;
+
+impl OutputEncoder for JsonEncoder {
+    fn encode(&self, output: &DroidOutput) -> Result<String, String> {
+        serde_json::to_string(output).map_err(|e| e.to_string())
+    }
+}
+
+/// The assistant message followed by the session id, error and warnings as plain lines
+pub struct TextEncoder;
+
+impl OutputEncoder for TextEncoder {
+    fn encode(&self, output: &DroidOutput) -> Result<String, String> {
+        let mut text = output.message.clone();
+        if output.message_truncated {
+            text.push_str(&droid::truncation_marker());
+        }
+        if !text.is_empty() {
+            text.push_str("\n\n");
+        }
+        text.push_str(&format!("SESSION_ID: {}", output.session_id));
+        if let Some(ref error) = output.error {
+            text.push_str(&format!("\nError: {}", error));
+        }
+        if let Some(ref stderr) = output.stderr {
+            text.push_str(&format!("\nstderr:\n{}", stderr));
+        }
+        if !output.warnings.is_empty() {
+            text.push_str(&format!("\nWarnings:\n{}", output.warnings.join("\n")));
+        }
+        Ok(text)
+    }
+}
+
+/// Encoder for a `response_format` name; None when the name is unknown
+pub fn encoder(format: &str) -> Option<Box<dyn OutputEncoder>> {
+    match format {
+        "toon" => Some(Box::new(ToonEncoder)),
+        "json" => Some(Box::new(JsonEncoder)),
+        "text" => Some(Box::new(TextEncoder)),
+        _ => None,
+    }
+}
@@ -1,25 +1,121 @@
-use crate::droid::{self, Options};
+use crate::droid::{self, AttachmentLimits, Options};
+use crate::output::{self, OutputEncoder, ToonEncoder};
+use crate::sessions::SessionStore;
+use crate::stats::{RunLog, RunMetric, RunWindow};
 use rmcp::{
     handler::server::{router::tool::ToolRouter, wrapper::Parameters},
     model::*,
-    schemars, tool, tool_handler, tool_router, ErrorData as McpError, ServerHandler,
+    schemars, tool, tool_handler, tool_router, ErrorData as McpError, Peer, RoleServer,
+    ServerHandler,
 };
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 /// Output from the droid tool
 #[derive(Debug, Serialize)]
-struct DroidOutput {
+pub struct DroidOutput {
     success: bool,
     #[serde(rename = "SESSION_ID")]
-    session_id: String,
-    message: String,
+    pub(crate) session_id: String,
+    /// Server-generated id of this call, also tagging its log lines
+    request_id: String,
+    pub(crate) message: String,
+    /// `message` hit the size cap and is incomplete
+    pub(crate) message_truncated: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
-    error: Option<String>,
+    pub(crate) error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<droid::ErrorCode>,
+    /// code / message / details of droid's error line, when it emitted one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    droid_error: Option<droid::DroidError>,
+    /// droid's stderr on failure, separate from the short `error` reason
     #[serde(skip_serializing_if = "Option::is_none")]
-    warnings: Option<String>,
+    pub(crate) stderr: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) warnings: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     model_info: Option<String>,
+    /// Autonomy level applied after defaults and config gating
+    #[serde(skip_serializing_if = "Option::is_none")]
+    effective_auto: Option<String>,
+    /// Canonicalized working directory droid ran in
+    cwd: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    post_run_output: Option<String>,
+    /// `git diff` plus untracked files after the run; only with return_diff
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diff: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    spec: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    summary: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    usage: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration_ms: Option<u64>,
+    /// Individual completions in order; only with include_all_messages
+    #[serde(skip_serializing_if = "Option::is_none")]
+    completions: Option<Vec<droid::Completion>>,
+    /// Raw stream-json lines; only with include_all_messages
+    #[serde(skip_serializing_if = "Option::is_none")]
+    all_messages: Option<Vec<HashMap<String, serde_json::Value>>>,
+    /// Raw stream-json lines past the size cap were dropped
+    all_messages_truncated: bool,
+    /// Bytes of `message`, for comparing against its size cap
+    agent_messages_bytes: usize,
+    /// Serialized bytes of the raw stream-json lines kept within their size cap
+    all_messages_bytes: usize,
+    /// Number of this call within its session (1 = first), counted by this server process
+    #[serde(skip_serializing_if = "Option::is_none")]
+    turn_number: Option<u32>,
+}
+
+impl DroidOutput {
+    /// Build the tool output for `result`; completions and raw lines only with
+    /// `include_all_messages`
+    pub fn from_result(
+        result: &droid::DroidResult,
+        request_id: String,
+        include_all_messages: bool,
+    ) -> Self {
+        DroidOutput {
+            success: result.success,
+            session_id: result.session_id.clone(),
+            request_id,
+            message: result.agent_messages.clone(),
+            message_truncated: result.agent_messages_truncated,
+            error: result.error.clone(),
+            error_code: result.error_code,
+            droid_error: result.droid_error.clone(),
+            stderr: result.stderr.clone(),
+            warnings: result.warnings.clone(),
+            model_info: result.model_info.clone(),
+            effective_auto: result.effective_auto.clone(),
+            cwd: result.working_dir.clone(),
+            post_run_output: result.post_run_output.clone(),
+            diff: result.diff.clone(),
+            spec: result.spec.clone(),
+            summary: result.summary.clone(),
+            usage: result.usage.clone(),
+            duration_ms: result.duration_ms,
+            completions: include_all_messages.then(|| result.completions.clone()),
+            all_messages: include_all_messages.then(|| result.all_messages.clone()),
+            all_messages_truncated: result.all_messages_truncated,
+            agent_messages_bytes: result.agent_messages.len(),
+            all_messages_bytes: result.all_messages_bytes,
+            turn_number: None,
+        }
+    }
+
+    /// Attach the session turn number returned by the session store
+    pub fn with_turn_number(mut self, turn_number: Option<u32>) -> Self {
+        self.turn_number = turn_number;
+        self
+    }
 }
 
 /// Input parameters for droid tool
@@ -33,6 +129,11 @@ pub struct DroidArgs {
     #[serde(default)]
     pub file: Option<PathBuf>,
 
+    /// Name of a server environment variable holding the prompt
+    /// (mutually exclusive with PROMPT and file)
+    #[serde(default)]
+    pub prompt_env: Option<String>,
+
     /// Autonomy level: low, medium, high (omit for DEFAULT/read-only)
     /// Cannot be used with skip_permissions_unsafe
     #[serde(default)]
@@ -50,6 +151,11 @@ pub struct DroidArgs {
     #[serde(default)]
     pub model: Option<String>,
 
+    /// Provider model id passed verbatim to --model, bypassing custom model resolution
+    /// and defaults (mutually exclusive with model)
+    #[serde(default)]
+    pub raw_model: Option<String>,
+
     /// Enable specific tools (comma or space separated)
     #[serde(default)]
     pub enabled_tools: Option<String>,
@@ -77,6 +183,11 @@ pub struct DroidArgs {
     #[serde(default)]
     pub spec_model: Option<String>,
 
+    /// Autonomy level for the specification phase: low, medium, high (when use_spec is true)
+    /// Maps to --spec-auto flag; droid's default applies when omitted
+    #[serde(default)]
+    pub spec_auto: Option<String>,
+
     /// Skip ALL permission checks (DANGEROUS - only for isolated environments)
     /// Cannot be combined with auto parameter
     /// Maps to --skip-permissions-unsafe flag
@@ -86,11 +197,282 @@ pub struct DroidArgs {
     /// Output format: stream-json (default) or stream-jsonrpc
     #[serde(default)]
     pub output_format: Option<String>,
+
+    /// Maximum number of agent turns before droid stops (must be positive)
+    /// Maps to --max-turns flag
+    #[serde(default)]
+    pub max_turns: Option<u32>,
+
+    /// Return the accumulated assistant messages of all turns of this session
+    /// (oldest content is dropped first once the size cap is reached)
+    #[serde(default)]
+    pub accumulate_history: Option<bool>,
+
+    /// Extra droid CLI arguments, appended after the server's configured additional_args
+    /// Flags managed by the server (e.g. --model, --auto) are rejected, and other flags
+    /// must be listed in the server's allowed_extra_args
+    #[serde(default)]
+    pub extra_args: Option<Vec<String>>,
+
+    /// Extra droid flags as key/value pairs, each passed as `--key value` (keys: letters,
+    /// digits and dashes). Server-managed flags are rejected and keys are subject to
+    /// allowed_extra_args like extra_args
+    #[serde(default)]
+    pub passthrough_flags: Option<HashMap<String, String>>,
+
+    /// Response mode: structured (default, TOON-encoded result), text (assistant message only)
+    /// or ndjson (droid's raw transcript, one JSON line per stream message)
+    #[serde(default)]
+    pub output_mode: Option<String>,
+
+    /// On failure, return droid's captured stderr in a separate `stderr` field (default: true)
+    #[serde(default)]
+    pub include_stderr_on_error: Option<bool>,
+
+    /// Encoding of the structured response: toon (default), json, or text (message plus
+    /// session id, error and warnings). Only applies to output_mode structured
+    #[serde(default)]
+    pub response_format: Option<String>,
+
+    /// Prepend DROID.md from the working directory as a system prompt (default: true)
+    #[serde(default)]
+    pub use_droid_md: Option<bool>,
+
+    /// Additional system prompt text, placed after DROID.md in the same system prompt
+    /// block (combined size limit 1 MB)
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+
+    /// Fail without running droid when the working directory has no DROID.md (default: false)
+    #[serde(default)]
+    pub require_droid_md: Option<bool>,
+
+    /// File of reference material appended to the prompt in a <context> block (max 1 MB);
+    /// unlike file, it is combined with PROMPT
+    #[serde(default)]
+    pub context_file: Option<PathBuf>,
+
+    /// Globs relative to the working directory (e.g. "src/**/*.rs") whose files are
+    /// concatenated into a <context> block; honors .droidignore, max 50 files / 1 MB
+    #[serde(default)]
+    pub context_globs: Option<Vec<String>>,
+
+    /// Save droid's raw stream-json output to this file (must be inside the working directory)
+    #[serde(default)]
+    pub output_file: Option<PathBuf>,
+
+    /// Also return the individual completions and the raw stream-json lines (structured mode)
+    #[serde(default)]
+    pub include_all_messages: Option<bool>,
+
+    /// After a successful run in a git repository, return `git diff` and untracked files
+    /// (max 200 KB)
+    #[serde(default)]
+    pub return_diff: Option<bool>,
+
+    /// Paths relative to the working directory that droid may touch, passed with the server's
+    /// scope_flag (without one, scoping is not enforced and a warning is returned)
+    #[serde(default)]
+    pub scope_paths: Option<Vec<String>>,
+
+    /// Absolute path of the droid binary to run for this call instead of the configured one
+    /// (only when the server sets allow_client_binary_override)
+    #[serde(default)]
+    pub droid_bin: Option<PathBuf>,
+}
+
+/// Input parameters for preview_prompt tool
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct PreviewPromptArgs {
+    /// Instruction that would be sent to droid
+    #[serde(rename = "PROMPT")]
+    pub prompt: String,
+
+    /// Working directory whose DROID.md is used (default: same as the droid tool)
+    #[serde(default)]
+    pub cwd: Option<PathBuf>,
+
+    /// Prepend DROID.md from the working directory as a system prompt (default: true)
+    #[serde(default)]
+    pub use_droid_md: Option<bool>,
+
+    /// Additional system prompt text, placed after DROID.md
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+
+    /// File of reference material appended in a <context> block
+    #[serde(default)]
+    pub context_file: Option<PathBuf>,
+
+    /// Globs of files concatenated into a <context> block
+    #[serde(default)]
+    pub context_globs: Option<Vec<String>>,
+}
+
+/// Output from the preview_prompt tool
+#[derive(Debug, Serialize)]
+struct PreviewPromptOutput {
+    prompt: String,
+    bytes: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<String>,
+}
+
+/// Input parameters for validate_paths tool
+#[derive(Debug, Default, Deserialize, schemars::JsonSchema)]
+pub struct ValidatePathsArgs {
+    /// Working directory, as it would be passed to the droid tool
+    #[serde(default)]
+    pub cwd: Option<PathBuf>,
+
+    /// Prompt file, as it would be passed to the droid tool
+    #[serde(default)]
+    pub file: Option<PathBuf>,
+
+    /// Context file, as it would be passed to the droid tool
+    #[serde(default)]
+    pub context_file: Option<PathBuf>,
+
+    /// Output file, as it would be passed to the droid tool
+    #[serde(default)]
+    pub output_file: Option<PathBuf>,
+
+    /// Context globs, as they would be passed to the droid tool
+    #[serde(default)]
+    pub context_globs: Option<Vec<String>>,
+
+    /// Scope paths, as they would be passed to the droid tool
+    #[serde(default)]
+    pub scope_paths: Option<Vec<String>>,
+}
+
+/// Output from the validate_paths tool
+#[derive(Debug, Serialize)]
+pub struct ValidatePathsOutput {
+    /// No path failed validation
+    pub valid: bool,
+    /// Paths that resolved, keyed by argument name
+    pub resolved: BTreeMap<String, PathBuf>,
+    /// Validation errors, in the order the droid tool checks them
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+/// Input parameters for session_last_message tool
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct SessionLastMessageArgs {
+    /// Session to look up (SESSION_ID from a previous droid response)
+    #[serde(rename = "SESSION_ID")]
+    pub session_id: String,
+}
+
+/// Output from the session_last_message tool
+#[derive(Debug, Serialize)]
+struct SessionLastMessageOutput {
+    #[serde(rename = "SESSION_ID")]
+    session_id: String,
+    message: String,
+}
+
+/// Input parameters for touch_session tool
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct TouchSessionArgs {
+    /// Session to keep warm (SESSION_ID from a previous droid response)
+    #[serde(rename = "SESSION_ID")]
+    pub session_id: String,
+
+    /// Working directory the session ran in (default: current directory)
+    #[serde(default)]
+    pub cwd: Option<PathBuf>,
+}
+
+/// Output from the touch_session tool
+#[derive(Debug, Serialize)]
+struct TouchSessionOutput {
+    #[serde(rename = "SESSION_ID")]
+    session_id: String,
+    /// The session resumed and can keep being used
+    valid: bool,
+    status: droid::SessionStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Default and maximum page sizes for get_messages
+const DEFAULT_MESSAGES_PAGE: usize = 50;
+const MAX_MESSAGES_PAGE: usize = 200;
+
+/// Input parameters for get_messages tool
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct GetMessagesArgs {
+    /// Session whose stored messages to page through
+    #[serde(rename = "SESSION_ID")]
+    pub session_id: String,
+
+    /// Index of the first message to return (default: 0)
+    #[serde(default)]
+    pub offset: Option<usize>,
+
+    /// Maximum number of messages to return (default: 50, max: 200)
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// Output from the get_messages tool
+#[derive(Debug, Serialize)]
+struct GetMessagesOutput {
+    #[serde(rename = "SESSION_ID")]
+    session_id: String,
+    offset: usize,
+    total: usize,
+    has_more: bool,
+    messages: Vec<HashMap<String, serde_json::Value>>,
+}
+
+/// Input parameters for run_stats tool
+#[derive(Debug, Default, Deserialize, schemars::JsonSchema)]
+pub struct RunStatsArgs {
+    /// Only aggregate the most recent N runs
+    #[serde(default)]
+    pub last: Option<usize>,
+
+    /// Only aggregate runs that finished within this many seconds
+    #[serde(default)]
+    pub since_secs: Option<u64>,
+}
+
+/// Output from the droid_tools tool
+#[derive(Debug, Serialize)]
+struct DroidToolsOutput {
+    tools: Vec<String>,
+}
+
+/// Output from the cancel_all tool
+#[derive(Debug, Serialize)]
+struct CancelAllOutput {
+    /// Runs whose droid child was killed
+    cancelled: usize,
+}
+
+/// Output from the list_models tool
+#[derive(Debug, Serialize)]
+struct ListModelsOutput {
+    models: Vec<droid::ModelEntry>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    default_model: Option<String>,
 }
 
 #[derive(Clone)]
 pub struct DroidServer {
     tool_router: ToolRouter<DroidServer>,
+    /// Last invocation time per session, used for per-session rate limiting
+    session_calls: Arc<Mutex<HashMap<String, Instant>>>,
+    /// Results of recent sessions run through this server
+    sessions: SessionStore,
+    /// Outcome metrics of recent runs, for run_stats
+    runs: RunLog,
+    /// Default working directory from the `--cwd` CLI flag; takes precedence over `default_cwd`
+    default_cwd: Option<PathBuf>,
 }
 
 impl Default for DroidServer {
@@ -99,12 +481,499 @@ impl Default for DroidServer {
     }
 }
 
-impl DroidServer {
-    pub fn new() -> Self {
-        Self {
-            tool_router: Self::tool_router(),
-        }
+impl DroidServer {
+    pub fn new() -> Self {
+        Self {
+            tool_router: Self::tool_router(),
+            session_calls: Arc::new(Mutex::new(HashMap::new())),
+            sessions: SessionStore::new(),
+            runs: RunLog::new(),
+            default_cwd: None,
+        }
+    }
+
+    /// Use `cwd` as the working directory when a client omits `cwd`
+    ///
+    /// The caller is expected to pass a canonical, existing directory.
+    pub fn with_default_cwd(mut self, cwd: PathBuf) -> Self {
+        self.default_cwd = Some(cwd);
+        self
+    }
+
+    /// Resolve and validate the working directory for a call
+    ///
+    /// When the client omits `cwd`, the `--cwd` flag and then the configured
+    /// `default_cwd` are used before falling back to the process current directory.
+    /// The result must be listed in `allowed_cwds` when that config list is set.
+    pub fn resolve_working_dir(&self, cwd: Option<PathBuf>) -> Result<PathBuf, McpError> {
+        let working_dir = resolve_working_dir(
+            cwd.or_else(|| self.default_cwd.clone())
+                .or_else(droid::default_working_dir),
+        )?;
+        droid::check_cwd_allowed(&working_dir).map_err(|e| McpError::invalid_params(e, None))?;
+        Ok(working_dir)
+    }
+
+    /// Run the droid tool's path checks on `args` without spawning droid
+    ///
+    /// Unlike the droid tool, every check runs and all failures are collected.
+    pub fn validate_paths(&self, args: ValidatePathsArgs) -> ValidatePathsOutput {
+        let mut resolved = BTreeMap::new();
+        let mut errors = Vec::new();
+
+        match self.resolve_working_dir(args.cwd) {
+            Ok(working_dir) => {
+                let inputs = [("file", args.file), ("context_file", args.context_file)];
+                let mut attachments = Vec::new();
+                for (name, path) in inputs {
+                    let Some(path) = path else { continue };
+                    match resolve_input_file(&working_dir, path) {
+                        Ok(path) => {
+                            attachments.push(path.clone());
+                            resolved.insert(name.to_string(), path);
+                        }
+                        Err(e) => errors.push(format!("{}: {}", name, e.message)),
+                    }
+                }
+                if let Err(e) = check_attachment_limits(&attachments, &droid::attachment_limits()) {
+                    errors.push(e.message.to_string());
+                }
+                if let Some(path) = args.output_file {
+                    match resolve_output_file(&working_dir, path) {
+                        Ok(path) => {
+                            resolved.insert("output_file".to_string(), path);
+                        }
+                        Err(e) => errors.push(format!("output_file: {}", e.message)),
+                    }
+                }
+                if let Some(scope_paths) = args.scope_paths {
+                    if let Err(e) = droid::validate_scope_paths(&working_dir, &scope_paths) {
+                        errors.push(format!("scope_paths: {}", e));
+                    }
+                }
+                resolved.insert("cwd".to_string(), working_dir);
+            }
+            Err(e) => errors.push(format!("cwd: {}", e.message)),
+        }
+
+        if let Err(e) = validate_context_globs(args.context_globs) {
+            errors.push(format!("context_globs: {}", e.message));
+        }
+
+        ValidatePathsOutput {
+            valid: errors.is_empty(),
+            resolved,
+            errors,
+        }
+    }
+
+    /// JSON schema of the `droid` tool input, exactly as advertised over MCP
+    pub fn droid_input_schema(&self) -> Option<serde_json::Value> {
+        self.current_tool_router()
+            .list_all()
+            .into_iter()
+            .find(|tool| tool.name == "droid")
+            .map(|tool| serde_json::Value::Object((*tool.input_schema).clone()))
+    }
+
+    /// The tools with schemas reflecting the current config, which a reload may have changed
+    fn current_tool_router(&self) -> ToolRouter<DroidServer> {
+        let mut router = self.tool_router.clone();
+        restrict_auto_schema(&mut router);
+        router
+    }
+
+    /// Reject calls that reuse a session sooner than `min_session_interval_ms` allows
+    fn check_session_rate(&self, session_id: &str) -> Result<(), McpError> {
+        let Some(min_interval) = droid::min_session_interval() else {
+            return Ok(());
+        };
+
+        let now = Instant::now();
+        let mut calls = self
+            .session_calls
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        if let Some(last) = calls.get(session_id) {
+            let elapsed = now.duration_since(*last);
+            if elapsed < min_interval {
+                return Err(McpError::invalid_request(
+                    format!(
+                        "Session {} was called {}ms ago; wait at least {}ms between calls to the same session",
+                        session_id,
+                        elapsed.as_millis(),
+                        min_interval.as_millis()
+                    ),
+                    None,
+                ));
+            }
+        }
+
+        // Entries older than the interval can no longer throttle anything
+        calls.retain(|_, last| now.duration_since(*last) < min_interval);
+        calls.insert(session_id.to_string(), now);
+        Ok(())
+    }
+}
+
+/// Advertise only the currently enabled autonomy levels as the `auto` and `spec_auto`
+/// enums of the droid tool
+fn restrict_auto_schema(router: &mut ToolRouter<DroidServer>) {
+    let Some(route) = router.map.get_mut("droid") else {
+        return;
+    };
+    let mut schema = (*route.attr.input_schema).clone();
+    let Some(properties) = schema
+        .get_mut("properties")
+        .and_then(|props| props.as_object_mut())
+    else {
+        return;
+    };
+
+    let mut levels: Vec<serde_json::Value> = droid::allowed_auto_levels()
+        .into_iter()
+        .map(serde_json::Value::from)
+        .collect();
+    // The parameters are optional; omitting them (or null) selects the default level
+    levels.push(serde_json::Value::Null);
+    for name in ["auto", "spec_auto"] {
+        if let Some(level) = properties
+            .get_mut(name)
+            .and_then(|level| level.as_object_mut())
+        {
+            level.insert("enum".to_string(), serde_json::Value::Array(levels.clone()));
+        }
+    }
+    route.attr.input_schema = Arc::new(schema);
+}
+
+/// Body of a text mode result: the agent messages, or the error when there are none
+///
+/// Truncated messages end with the configured `truncation_marker`, since text mode has
+/// no flag to report it.
+pub fn text_message(result: &droid::DroidResult) -> String {
+    if result.agent_messages.is_empty() {
+        return result.error.clone().unwrap_or_default();
+    }
+    let mut text = result.agent_messages.clone();
+    if result.agent_messages_truncated {
+        text.push_str(&droid::truncation_marker());
+    }
+    text
+}
+
+/// Map a `droid::run` failure to the MCP error reported to the client
+///
+/// Requests `run` refused ([`droid::Rejected`]) are invalid params; anything else is an
+/// execution failure.
+pub fn run_error(e: anyhow::Error) -> McpError {
+    match e.downcast_ref::<droid::Rejected>() {
+        Some(rejected) => McpError::invalid_params(rejected.to_string(), None),
+        None => McpError::internal_error(format!("Failed to execute droid: {e:#}"), None),
+    }
+}
+
+/// Replace `prompt_env` with the prompt it names, read from the server's environment
+///
+/// Fails when PROMPT or file is also given, the variable is not in `allowed_prompt_env`,
+/// or it is unset or blank.
+pub fn resolve_prompt_env(args: &mut DroidArgs) -> Result<(), McpError> {
+    let Some(name) = args.prompt_env.take() else {
+        return Ok(());
+    };
+    if args.prompt.is_some() || args.file.is_some() {
+        return Err(McpError::invalid_params(
+            "prompt_env is mutually exclusive with PROMPT and file, provide only one",
+            None,
+        ));
+    }
+    droid::check_prompt_env_allowed(&name).map_err(|e| McpError::invalid_params(e, None))?;
+    match std::env::var(&name) {
+        Ok(value) if !value.trim().is_empty() => {
+            args.prompt = Some(value);
+            Ok(())
+        }
+        Ok(_) => Err(McpError::invalid_params(
+            format!("prompt_env variable '{}' is empty", name),
+            None,
+        )),
+        Err(_) => Err(McpError::invalid_params(
+            format!("prompt_env variable '{}' is not set", name),
+            None,
+        )),
+    }
+}
+
+/// Forward run heartbeats to the client as progress notifications for `token`
+fn forward_progress(
+    peer: Peer<RoleServer>,
+    token: ProgressToken,
+) -> tokio::sync::mpsc::UnboundedSender<droid::Progress> {
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel::<droid::Progress>();
+    tokio::spawn(async move {
+        while let Some(progress) = receiver.recv().await {
+            let estimate = format!(
+                "est. {}% of the {}s timeout",
+                progress.estimated_percent, progress.timeout_secs
+            );
+            let message = match progress.last_line_type {
+                Some(line_type) => format!(
+                    "droid running for {}s, {} (last line: {})",
+                    progress.elapsed_secs, estimate, line_type
+                ),
+                None => format!(
+                    "droid running for {}s, {} (no output yet)",
+                    progress.elapsed_secs, estimate
+                ),
+            };
+            let notification = ProgressNotificationParam {
+                progress_token: token.clone(),
+                progress: progress.elapsed_secs as f64,
+                total: None,
+                message: Some(message),
+            };
+            if peer.notify_progress(notification).await.is_err() {
+                break;
+            }
+        }
+    });
+    sender
+}
+
+/// Canonicalize and validate a working directory, defaulting to the process current directory
+fn resolve_working_dir(cwd: Option<PathBuf>) -> Result<PathBuf, McpError> {
+    let working_dir = if let Some(cwd) = cwd {
+        let resolved = if cwd.is_absolute() {
+            cwd
+        } else {
+            std::env::current_dir()
+                .map_err(|e| {
+                    McpError::invalid_params(
+                        format!("Failed to resolve current directory: {}", e),
+                        None,
+                    )
+                })?
+                .join(cwd)
+        };
+        resolved.canonicalize().map_err(|e| {
+            McpError::invalid_params(
+                format!(
+                    "Working directory does not exist or is not accessible: {} ({})",
+                    resolved.display(),
+                    e
+                ),
+                None,
+            )
+        })?
+    } else {
+        std::env::current_dir().map_err(|e| {
+            McpError::invalid_params(
+                format!("Failed to resolve current working directory: {}", e),
+                None,
+            )
+        })?
+    };
+
+    if !working_dir.is_dir() {
+        return Err(McpError::invalid_params(
+            format!(
+                "Working directory is not a directory: {}",
+                working_dir.display()
+            ),
+            None,
+        ));
+    }
+
+    Ok(working_dir)
+}
+
+/// Resolve an input file against the working directory and check that it is a readable file
+fn resolve_input_file(working_dir: &std::path::Path, file: PathBuf) -> Result<PathBuf, McpError> {
+    let resolved = if file.is_absolute() {
+        file
+    } else {
+        working_dir.join(file)
+    };
+    let canonical = resolved.canonicalize().map_err(|e| {
+        McpError::invalid_params(
+            format!(
+                "File does not exist or is not accessible: {} ({})",
+                resolved.display(),
+                e
+            ),
+            None,
+        )
+    })?;
+    if !canonical.is_file() {
+        return Err(McpError::invalid_params(
+            format!("File path is not a file: {}", resolved.display()),
+            None,
+        ));
+    }
+    Ok(canonical)
+}
+
+/// Check that every `context_globs` entry stays inside the working directory
+fn validate_context_globs(globs: Option<Vec<String>>) -> Result<Vec<String>, McpError> {
+    let globs = globs.unwrap_or_default();
+    for pattern in &globs {
+        crate::context::validate_glob(pattern).map_err(|e| McpError::invalid_params(e, None))?;
+    }
+    Ok(globs)
+}
+
+/// Reject a call whose input files exceed the configured count or combined size
+pub fn check_attachment_limits(
+    files: &[PathBuf],
+    limits: &AttachmentLimits,
+) -> Result<(), McpError> {
+    if let Some(max_files) = limits.max_files {
+        if files.len() > max_files {
+            return Err(McpError::invalid_params(
+                format!(
+                    "Too many attachments: {} files (server max_attachments is {})",
+                    files.len(),
+                    max_files
+                ),
+                None,
+            ));
+        }
+    }
+
+    if let Some(max_bytes) = limits.max_bytes {
+        let mut total: u64 = 0;
+        for file in files {
+            let len = std::fs::metadata(file)
+                .map_err(|e| {
+                    McpError::invalid_params(
+                        format!("Cannot read attachment {}: {}", file.display(), e),
+                        None,
+                    )
+                })?
+                .len();
+            total = total.saturating_add(len);
+        }
+        if total > max_bytes {
+            return Err(McpError::invalid_params(
+                format!(
+                    "Attachments too large: {} bytes in total (server max_attachment_bytes is {})",
+                    total, max_bytes
+                ),
+                None,
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Resolve `output_file` against the working directory and keep it inside it
+fn resolve_output_file(working_dir: &std::path::Path, path: PathBuf) -> Result<PathBuf, McpError> {
+    let resolved = working_dir.join(&path);
+    let (Some(parent), Some(file_name)) = (resolved.parent(), resolved.file_name()) else {
+        return Err(McpError::invalid_params(
+            format!("Invalid output_file: {}", path.display()),
+            None,
+        ));
+    };
+    let parent = parent.canonicalize().map_err(|e| {
+        McpError::invalid_params(
+            format!(
+                "output_file directory does not exist or is not accessible: {} ({})",
+                parent.display(),
+                e
+            ),
+            None,
+        )
+    })?;
+    if !parent.starts_with(working_dir) {
+        return Err(McpError::invalid_params(
+            format!(
+                "output_file must be inside the working directory: {}",
+                path.display()
+            ),
+            None,
+        ));
+    }
+    Ok(parent.join(file_name))
+}
+
+/// Normalize a comma/space separated tool list into a single comma-separated list
+///
+/// Empty entries are dropped; entries with characters outside a conservative
+/// tool-name alphabet are rejected. Returns `None` when no tools remain.
+fn normalize_tool_list(field: &str, raw: Option<String>) -> Result<Option<String>, McpError> {
+    let Some(raw) = raw else {
+        return Ok(None);
+    };
+
+    let mut tools = Vec::new();
+    for entry in raw.split(|c: char| c == ',' || c.is_whitespace()) {
+        if entry.is_empty() {
+            continue;
+        }
+        let valid = entry
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | ':'));
+        if !valid {
+            return Err(McpError::invalid_params(
+                format!(
+                    "Invalid tool name in {}: '{}'. Tool names may only contain letters, digits, '_', '-', '.' and ':'",
+                    field, entry
+                ),
+                None,
+            ));
+        }
+        tools.push(entry);
+    }
+
+    Ok(if tools.is_empty() {
+        None
+    } else {
+        Some(tools.join(","))
+    })
+}
+
+/// Validate client-supplied extra droid args, rejecting flags the server manages itself
+/// and flags outside the configured allowlist
+fn validate_extra_args(extra_args: Option<Vec<String>>) -> Result<Vec<String>, McpError> {
+    let mut cleaned = Vec::new();
+    for arg in extra_args.unwrap_or_default() {
+        let arg = arg.trim();
+        if arg.is_empty() {
+            continue;
+        }
+        if droid::is_reserved_flag(arg) {
+            return Err(McpError::invalid_params(
+                format!(
+                    "extra_args may not contain '{}': this flag is managed by the server",
+                    arg
+                ),
+                None,
+            ));
+        }
+        droid::check_extra_arg_allowed(arg)
+            .map_err(|reason| McpError::invalid_params(reason, None))?;
+        cleaned.push(arg.to_string());
+    }
+    Ok(cleaned)
+}
+
+/// Validate `passthrough_flags` into `(key, value)` pairs, sorted by key
+pub fn validate_passthrough_flags(
+    flags: Option<HashMap<String, String>>,
+) -> Result<Vec<(String, String)>, McpError> {
+    let flags: BTreeMap<String, String> = flags.unwrap_or_default().into_iter().collect();
+    let mut validated = Vec::new();
+    for (key, value) in flags {
+        let flag = droid::passthrough_flag(&key)
+            .map_err(|reason| McpError::invalid_params(reason, None))?;
+        droid::check_extra_arg_allowed(&flag)
+            .map_err(|reason| McpError::invalid_params(reason, None))?;
+        validated.push((key, value));
     }
+    Ok(validated)
 }
 
 #[tool_router]
@@ -116,7 +985,12 @@ impl DroidServer {
     /// - `SESSION_ID`: unique identifier for resuming this conversation in future calls
     /// - `message`: concatenated assistant response text
     /// - `error`: error description when `success=False`
-    /// - `warnings`: optional warnings (e.g., DROID.md truncation)
+    /// - `warnings`: list of warnings, omitted when empty (e.g., DROID.md truncation)
+    /// - `spec`: the plan produced in spec mode (`use_spec`), if any
+    /// - `effective_auto`: autonomy level actually applied (client value or server default)
+    /// - `turn_number`: number of this call within its session (1 = first turn)
+    /// - `agent_messages_bytes` / `all_messages_bytes`: bytes used of the message and
+    ///   raw-line size caps
     ///
     /// **Best practices:**
     /// - Always capture and reuse `SESSION_ID` for multi-turn interactions
@@ -128,13 +1002,20 @@ impl DroidServer {
     )]
     async fn droid(
         &self,
-        Parameters(args): Parameters<DroidArgs>,
+        Parameters(mut args): Parameters<DroidArgs>,
+        meta: Meta,
+        peer: Peer<RoleServer>,
     ) -> Result<CallToolResult, McpError> {
+        // Correlates this call with its log lines; independent of droid's session id
+        let request_id = uuid::Uuid::new_v4().to_string();
+
+        resolve_prompt_env(&mut args)?;
+
         // Validate prompt/file mutual exclusivity
         match (&args.prompt, &args.file) {
             (None, None) => {
                 return Err(McpError::invalid_params(
-                    "Either PROMPT or file parameter is required",
+                    "Either PROMPT, file or prompt_env parameter is required",
                     None,
                 ));
             }
@@ -161,87 +1042,56 @@ impl DroidServer {
                 None,
             ));
         }
-
-        // Resolve working directory
-        let working_dir = if let Some(cwd) = args.cwd {
-            let resolved = if cwd.is_absolute() {
-                cwd
-            } else {
-                std::env::current_dir()
-                    .map_err(|e| {
-                        McpError::invalid_params(
-                            format!("Failed to resolve current directory: {}", e),
-                            None,
-                        )
-                    })?
-                    .join(cwd)
-            };
-            resolved.canonicalize().map_err(|e| {
-                McpError::invalid_params(
-                    format!(
-                        "Working directory does not exist or is not accessible: {} ({})",
-                        resolved.display(),
-                        e
-                    ),
-                    None,
-                )
-            })?
-        } else {
-            std::env::current_dir().map_err(|e| {
-                McpError::invalid_params(
-                    format!("Failed to resolve current working directory: {}", e),
-                    None,
-                )
-            })?
-        };
-
-        if !working_dir.is_dir() {
-            return Err(McpError::invalid_params(
-                format!(
-                    "Working directory is not a directory: {}",
-                    working_dir.display()
-                ),
-                None,
-            ));
+        if skip_perms {
+            droid::check_skip_permissions_allowed()
+                .map_err(|e| McpError::invalid_params(e, None))?;
+        }
+        if let Some(ref droid_bin) = args.droid_bin {
+            droid::check_binary_override_allowed(droid_bin)
+                .map_err(|e| McpError::invalid_params(e, None))?;
         }
 
+        // Resolve working directory (falls back to the configured default_cwd)
+        let working_dir = self.resolve_working_dir(args.cwd)?;
+
         // Validate file path if provided
-        let file_path = if let Some(file) = args.file {
-            let resolved = if file.is_absolute() {
-                file
-            } else {
-                working_dir.join(file)
-            };
-            let canonical = resolved.canonicalize().map_err(|e| {
-                McpError::invalid_params(
-                    format!(
-                        "File does not exist or is not accessible: {} ({})",
-                        resolved.display(),
-                        e
-                    ),
-                    None,
-                )
-            })?;
-            if !canonical.is_file() {
-                return Err(McpError::invalid_params(
-                    format!("File path is not a file: {}", resolved.display()),
-                    None,
-                ));
-            }
-            Some(canonical)
-        } else {
-            None
-        };
+        let file_path = args
+            .file
+            .map(|file| resolve_input_file(&working_dir, file))
+            .transpose()?;
+        let context_file = args
+            .context_file
+            .map(|file| resolve_input_file(&working_dir, file))
+            .transpose()?;
+
+        let attachments: Vec<PathBuf> = file_path.iter().chain(&context_file).cloned().collect();
+        check_attachment_limits(&attachments, &droid::attachment_limits())?;
+
+        let context_globs = validate_context_globs(args.context_globs)?;
+
+        let scope_paths = args.scope_paths.unwrap_or_default();
+        droid::validate_scope_paths(&working_dir, &scope_paths)
+            .map_err(|e| McpError::invalid_params(e, None))?;
+
+        let output_file = args
+            .output_file
+            .map(|path| resolve_output_file(&working_dir, path))
+            .transpose()?;
 
         // Filter empty strings to None
         let session_id = args.session_id.filter(|s| !s.is_empty());
         let auto = args.auto.filter(|s| !s.is_empty());
         let model = args.model.filter(|s| !s.is_empty());
-        let enabled_tools = args.enabled_tools.filter(|s| !s.is_empty());
-        let disabled_tools = args.disabled_tools.filter(|s| !s.is_empty());
+        let raw_model = args.raw_model.filter(|s| !s.is_empty());
+        let enabled_tools = normalize_tool_list("enabled_tools", args.enabled_tools)?;
+        let disabled_tools = normalize_tool_list("disabled_tools", args.disabled_tools)?;
         let reasoning_effort = args.reasoning_effort.filter(|s| !s.is_empty());
         let spec_model = args.spec_model.filter(|s| !s.is_empty());
+        let spec_auto = args.spec_auto.filter(|s| !s.is_empty());
         let output_format = args.output_format.filter(|s| !s.is_empty());
+        let output_mode = args.output_mode.filter(|s| !s.is_empty());
+        let response_format = args.response_format.filter(|s| !s.is_empty());
+        let include_all_messages = args.include_all_messages.unwrap_or(false);
 
         // Validate autonomy level
         if let Some(ref level) = auto {
@@ -259,6 +1109,22 @@ impl DroidServer {
             }
         }
 
+        // Validate spec phase autonomy level
+        if let Some(ref level) = spec_auto {
+            match level.as_str() {
+                "low" | "medium" | "high" => {}
+                _ => {
+                    return Err(McpError::invalid_params(
+                        format!(
+                            "Invalid spec_auto level: '{}'. Must be one of: low, medium, high",
+                            level
+                        ),
+                        None,
+                    ));
+                }
+            }
+        }
+
         // Validate reasoning effort level
         if let Some(ref level) = reasoning_effort {
             match level.as_str() {
@@ -291,6 +1157,82 @@ impl DroidServer {
             }
         }
 
+        // Validate turn limit
+        if args.max_turns == Some(0) {
+            return Err(McpError::invalid_params(
+                "max_turns must be a positive integer",
+                None,
+            ));
+        }
+
+        // Validate output mode
+        if let Some(ref mode) = output_mode {
+            match mode.as_str() {
+                "structured" | "text" | "ndjson" => {}
+                _ => {
+                    return Err(McpError::invalid_params(
+                        format!(
+                            "Invalid output_mode: '{}'. Must be one of: structured, text, ndjson",
+                            mode
+                        ),
+                        None,
+                    ));
+                }
+            }
+        }
+
+        // Resolve the response encoder up front so a bad name fails before droid runs
+        let encoder: Box<dyn OutputEncoder> = match response_format.as_deref() {
+            None => Box::new(ToonEncoder),
+            Some(format) => {
+                if output_mode
+                    .as_deref()
+                    .is_some_and(|mode| mode != "structured")
+                {
+                    return Err(McpError::invalid_params(
+                        "response_format only applies to output_mode structured",
+                        None,
+                    ));
+                }
+                output::encoder(format).ok_or_else(|| {
+                    McpError::invalid_params(
+                        format!(
+                            "Invalid response_format: '{}'. Must be one of: {}",
+                            format,
+                            output::RESPONSE_FORMATS.join(", ")
+                        ),
+                        None,
+                    )
+                })?
+            }
+        };
+
+        if model.is_some() && raw_model.is_some() {
+            return Err(McpError::invalid_params(
+                "model and raw_model are mutually exclusive, provide only one",
+                None,
+            ));
+        }
+        // Default models are subject to the allow/deny lists too
+        let effective_model = match raw_model {
+            Some(ref raw_model) => Some(raw_model.clone()),
+            None => droid::resolve_model(model.clone()),
+        };
+        droid::check_model_allowed(effective_model.as_deref())
+            .map_err(|e| McpError::invalid_params(e, None))?;
+
+        let extra_args = validate_extra_args(args.extra_args)?;
+        let passthrough_flags = validate_passthrough_flags(args.passthrough_flags)?;
+
+        // Throttle tight loops against a single session
+        if let Some(ref sid) = session_id {
+            self.check_session_rate(sid)?;
+        }
+        // Resuming elsewhere is allowed, but may mean the client mixed up projects
+        let cwd_warning = session_id
+            .as_deref()
+            .and_then(|sid| self.sessions.cwd_mismatch(sid, &working_dir));
+
         // Build Options
         let opts = Options {
             prompt: args.prompt,
@@ -299,31 +1241,367 @@ impl DroidServer {
             session_id,
             auto,
             model,
+            raw_model,
             enabled_tools,
             disabled_tools,
             additional_args: droid::default_additional_args(),
+            extra_args,
+            passthrough_flags,
             timeout_secs: args.timeout_secs,
             reasoning_effort,
             use_spec: args.use_spec.unwrap_or(false),
             spec_model,
+            spec_auto,
             skip_permissions_unsafe: skip_perms,
             output_format,
+            max_turns: args.max_turns,
+            use_droid_md: args.use_droid_md,
+            output_file,
+            context_file,
+            context_globs,
+            request_id: Some(request_id.clone()),
+            require_droid_md: args.require_droid_md,
+            return_diff: args.return_diff.unwrap_or(false),
+            // Heartbeats are only sent to clients that asked for progress
+            progress: meta
+                .get_progress_token()
+                .map(|token| forward_progress(peer, token)),
+            include_stderr_on_error: args.include_stderr_on_error,
+            system_prompt: args.system_prompt.filter(|s| !s.is_empty()),
+            droid_bin: args.droid_bin,
+            scope_paths,
+            scope_flag: None,
         };
 
+        let started = Instant::now();
+
         // Execute droid
-        let result = droid::run(opts).await.map_err(|e| {
-            eprintln!("droid-mcp-rs: droid::run failed: {e:?}");
-            McpError::internal_error(format!("Failed to execute droid: {e:?}"), None)
+        let mut result = droid::run(opts).await.map_err(|e| {
+            eprintln!("droid-mcp-rs: [request {request_id}] droid::run failed: {e:?}");
+            run_error(e)
         })?;
+        if let Some(warning) = cwd_warning {
+            result.warnings.push(warning);
+        }
 
-        // Build output using TOON encoding
-        let output = DroidOutput {
+        let turn_number = self.sessions.record(&result);
+        self.runs.record(RunMetric {
+            finished_at: Instant::now(),
             success: result.success,
-            session_id: result.session_id.clone(),
-            message: result.agent_messages.clone(),
-            error: result.error.clone(),
-            warnings: result.warnings.clone(),
-            model_info: result.model_info.clone(),
+            duration: started.elapsed(),
+            model: result
+                .model
+                .clone()
+                .unwrap_or_else(|| "default".to_string()),
+        });
+
+        // Optionally return the running conversation of the session instead of just this turn
+        if args.accumulate_history.unwrap_or(false) {
+            if let Some(history) = self.sessions.history(&result.session_id) {
+                result.agent_messages = history;
+            }
+        }
+
+        // Text mode returns the assistant message as-is, without the structured wrapper
+        if output_mode.as_deref() == Some("text") {
+            let mut content = vec![Content::text(text_message(&result))];
+            if let Some(ref stderr) = result.stderr {
+                content.push(Content::text(format!("stderr:\n{}", stderr)));
+            }
+            if let Some(warnings) = result.joined_warnings() {
+                content.push(Content::text(format!("Warnings:\n{}", warnings)));
+            }
+            return Ok(if result.success {
+                CallToolResult::success(content)
+            } else {
+                CallToolResult::error(content)
+            });
+        }
+
+        // NDJSON mode replays droid's stream as-is, for clients that want the raw transcript
+        if output_mode.as_deref() == Some("ndjson") {
+            let mut content = vec![Content::text(result.transcript_ndjson())];
+            if let Some(warnings) = result.joined_warnings() {
+                content.push(Content::text(format!("Warnings:\n{}", warnings)));
+            }
+            return Ok(if result.success {
+                CallToolResult::success(content)
+            } else {
+                CallToolResult::error(content)
+            });
+        }
+
+        // Build output in the requested encoding (TOON by default)
+        let output = DroidOutput::from_result(&result, request_id, include_all_messages)
+            .with_turn_number(turn_number);
+
+        let encoded = encoder.encode(&output).map_err(|e| {
+            McpError::internal_error(format!("Failed to serialize output: {}", e), None)
+        })?;
+
+        // Flag failed runs as tool errors so clients don't have to parse the body
+        let content = vec![Content::text(encoded)];
+        Ok(if result.success {
+            CallToolResult::success(content)
+        } else {
+            CallToolResult::error(content)
+        })
+    }
+
+    /// Resumes a session with a trivial read-only prompt to keep it from expiring
+    #[tool(
+        name = "touch_session",
+        description = "Keep a droid session warm with a minimal read-only resume (adds one short turn) and report whether it is still valid: status active, expired or failed"
+    )]
+    async fn touch_session(
+        &self,
+        Parameters(args): Parameters<TouchSessionArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        if args.session_id.trim().is_empty() {
+            return Err(McpError::invalid_params(
+                "SESSION_ID must be a non-empty string",
+                None,
+            ));
+        }
+        let working_dir = self.resolve_working_dir(args.cwd)?;
+        self.check_session_rate(&args.session_id)?;
+
+        let touch = droid::touch_session(&args.session_id, working_dir)
+            .await
+            .map_err(run_error)?;
+
+        let output = TouchSessionOutput {
+            session_id: args.session_id,
+            valid: touch.status == droid::SessionStatus::Active,
+            status: touch.status,
+            error: touch.error,
+        };
+
+        let toon_output = toon_format::encode_default(&output).map_err(|e| {
+            McpError::internal_error(format!("Failed to serialize output: {}", e), None)
+        })?;
+
+        Ok(CallToolResult::success(vec![Content::text(toon_output)]))
+    }
+
+    /// Returns the last assistant message of a session previously run through this server
+    ///
+    /// Transcripts are kept in memory for the lifetime of the server process only.
+    #[tool(
+        name = "session_last_message",
+        description = "Fetch the last assistant message of a previous droid session without re-running it"
+    )]
+    async fn session_last_message(
+        &self,
+        Parameters(args): Parameters<SessionLastMessageArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let Some(record) = self.sessions.get(&args.session_id) else {
+            return Err(McpError::resource_not_found(
+                format!(
+                    "No stored transcript for session '{}'. Transcripts are only kept in memory for sessions run through this server process; resume the session with the droid tool instead.",
+                    args.session_id
+                ),
+                None,
+            ));
+        };
+
+        let output = SessionLastMessageOutput {
+            session_id: args.session_id,
+            message: record.last_message,
+        };
+
+        let toon_output = toon_format::encode_default(&output).map_err(|e| {
+            McpError::internal_error(format!("Failed to serialize output: {}", e), None)
+        })?;
+
+        Ok(CallToolResult::success(vec![Content::text(toon_output)]))
+    }
+
+    /// Returns a page of the raw stream messages stored for a session
+    #[tool(
+        name = "get_messages",
+        description = "Page through the raw droid stream messages (all_messages) of a previous session"
+    )]
+    async fn get_messages(
+        &self,
+        Parameters(args): Parameters<GetMessagesArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let offset = args.offset.unwrap_or(0);
+        let limit = args
+            .limit
+            .unwrap_or(DEFAULT_MESSAGES_PAGE)
+            .clamp(1, MAX_MESSAGES_PAGE);
+
+        let Some((messages, total)) = self.sessions.messages_page(&args.session_id, offset, limit)
+        else {
+            return Err(McpError::resource_not_found(
+                format!(
+                    "No stored messages for session '{}'. Messages are only kept in memory for sessions run through this server process.",
+                    args.session_id
+                ),
+                None,
+            ));
+        };
+
+        let output = GetMessagesOutput {
+            session_id: args.session_id,
+            offset,
+            total,
+            has_more: offset.saturating_add(messages.len()) < total,
+            messages,
+        };
+
+        let toon_output = toon_format::encode_default(&output).map_err(|e| {
+            McpError::internal_error(format!("Failed to serialize output: {}", e), None)
+        })?;
+
+        Ok(CallToolResult::success(vec![Content::text(toon_output)]))
+    }
+
+    /// Aggregates the outcomes of recent runs through this server
+    #[tool(
+        name = "run_stats",
+        description = "Summarize recent droid runs of this server process: total runs, success rate, average duration and runs per model; optionally limited to the last N runs or the last N seconds"
+    )]
+    async fn run_stats(
+        &self,
+        Parameters(args): Parameters<RunStatsArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let stats = self.runs.summarize(RunWindow {
+            last: args.last,
+            within: args.since_secs.map(std::time::Duration::from_secs),
+        });
+
+        let toon_output = toon_format::encode_default(&stats).map_err(|e| {
+            McpError::internal_error(format!("Failed to serialize output: {}", e), None)
+        })?;
+
+        Ok(CallToolResult::success(vec![Content::text(toon_output)]))
+    }
+
+    /// Shows the exact prompt droid would receive, without running it
+    #[tool(
+        name = "preview_prompt",
+        description = "Preview the fully assembled prompt (DROID.md system prompt + PROMPT + context_file) and its size without running droid"
+    )]
+    async fn preview_prompt(
+        &self,
+        Parameters(args): Parameters<PreviewPromptArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let working_dir = self.resolve_working_dir(args.cwd)?;
+        let context_file = args
+            .context_file
+            .map(|file| resolve_input_file(&working_dir, file))
+            .transpose()?;
+        let opts = Options {
+            prompt: Some(args.prompt),
+            working_dir,
+            use_droid_md: args.use_droid_md,
+            system_prompt: args.system_prompt.filter(|s| !s.is_empty()),
+            context_file,
+            context_globs: validate_context_globs(args.context_globs)?,
+            ..Default::default()
+        };
+        let (prompt, warnings) = droid::assemble_prompt(&opts).await;
+
+        let output = PreviewPromptOutput {
+            bytes: prompt.len(),
+            prompt,
+            warnings,
+        };
+
+        let toon_output = toon_format::encode_default(&output).map_err(|e| {
+            McpError::internal_error(format!("Failed to serialize output: {}", e), None)
+        })?;
+
+        Ok(CallToolResult::success(vec![Content::text(toon_output)]))
+    }
+
+    /// Checks the droid tool's path arguments without running droid
+    #[tool(
+        name = "validate_paths",
+        description = "Check cwd, file, context_file, output_file and context_globs the way the droid tool would, without running droid; returns the resolved paths and any errors"
+    )]
+    async fn validate_paths_tool(
+        &self,
+        Parameters(args): Parameters<ValidatePathsArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let output = self.validate_paths(args);
+
+        let toon_output = toon_format::encode_default(&output).map_err(|e| {
+            McpError::internal_error(format!("Failed to serialize output: {}", e), None)
+        })?;
+
+        Ok(CallToolResult::success(vec![Content::text(toon_output)]))
+    }
+
+    /// Lists the tool names droid accepts, for use in enabled_tools / disabled_tools
+    #[tool(
+        name = "droid_tools",
+        description = "List the droid tool names that can be passed as enabled_tools / disabled_tools"
+    )]
+    async fn droid_tools(&self) -> Result<CallToolResult, McpError> {
+        let tools = match droid::list_droid_tools().await {
+            Ok(tools) => tools,
+            Err(message) => return Ok(CallToolResult::error(vec![Content::text(message)])),
+        };
+
+        let output = DroidToolsOutput { tools };
+        let toon_output = toon_format::encode_default(&output).map_err(|e| {
+            McpError::internal_error(format!("Failed to serialize output: {}", e), None)
+        })?;
+
+        Ok(CallToolResult::success(vec![Content::text(toon_output)]))
+    }
+
+    /// Kills every droid run in flight, as an emergency stop
+    #[tool(
+        name = "cancel_all",
+        description = "Terminate every droid run currently in flight on this server and return how many were cancelled"
+    )]
+    async fn cancel_all(&self) -> Result<CallToolResult, McpError> {
+        let output = CancelAllOutput {
+            cancelled: droid::cancel_all(),
+        };
+        let toon_output = toon_format::encode_default(&output).map_err(|e| {
+            McpError::internal_error(format!("Failed to serialize output: {}", e), None)
+        })?;
+
+        Ok(CallToolResult::success(vec![Content::text(toon_output)]))
+    }
+
+    /// Re-reads the server config and ~/.factory/config.json without restarting
+    #[tool(
+        name = "reload_config",
+        description = "Reload droid-mcp.config.json and ~/.factory/config.json (e.g. after adding custom models) and report what changed"
+    )]
+    async fn reload_config(&self) -> Result<CallToolResult, McpError> {
+        let summary = droid::reload_config().map_err(|e| {
+            McpError::internal_error(
+                format!(
+                    "Failed to reload configuration, keeping previous config: {}",
+                    e
+                ),
+                None,
+            )
+        })?;
+
+        let toon_output = toon_format::encode_default(&summary).map_err(|e| {
+            McpError::internal_error(format!("Failed to serialize output: {}", e), None)
+        })?;
+
+        Ok(CallToolResult::success(vec![Content::text(toon_output)]))
+    }
+
+    /// Lists custom models from ~/.factory/config.json with their canonical references
+    #[tool(
+        name = "list_models",
+        description = "List custom models from the Factory config with the references to pass as model"
+    )]
+    async fn list_models(&self) -> Result<CallToolResult, McpError> {
+        let output = ListModelsOutput {
+            models: droid::list_custom_models(),
+            default_model: droid::resolve_model(None),
         };
 
         let toon_output = toon_format::encode_default(&output).map_err(|e| {
@@ -334,7 +1612,7 @@ impl DroidServer {
     }
 }
 
-#[tool_handler]
+#[tool_handler(router = self.current_tool_router())]
 impl ServerHandler for DroidServer {
     fn get_info(&self) -> ServerInfo {
         let custom_models = droid::list_custom_models();
@@ -345,7 +1623,7 @@ impl ServerHandler for DroidServer {
                 "\n\nAvailable custom models from ~/.factory/config.json:\n{}",
                 custom_models
                     .iter()
-                    .map(|m| format!("  - {}", m))
+                    .map(|m| format!("  - {} ({})", m.label, m.reference))
                     .collect::<Vec<_>>()
                     .join("\n")
             )
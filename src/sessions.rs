@@ -0,0 +1,153 @@
+use crate::droid::{DroidResult, MAX_AGENT_MESSAGES_SIZE};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Instant;
+
+/// Maximum number of sessions kept in memory (oldest evicted first)
+const MAX_STORED_SESSIONS: usize = 64;
+
+/// What the server remembers about a droid session between calls
+#[derive(Debug, Clone)]
+pub struct SessionRecord {
+    /// Agent messages produced by the most recent call
+    pub last_message: String,
+    /// Raw stream messages from the most recent call
+    pub all_messages: Vec<HashMap<String, Value>>,
+    /// Agent messages of every call in this session, capped at the agent message size limit
+    pub history: String,
+    /// Working directory of the most recent call
+    pub working_dir: PathBuf,
+    /// Calls recorded for this session so far
+    pub turns: u32,
+    /// When the session was last updated
+    pub updated_at: Instant,
+}
+
+/// In-memory store of recent session results, keyed by session id
+///
+/// Transcripts only live for the lifetime of the server process.
+#[derive(Debug, Clone, Default)]
+pub struct SessionStore {
+    inner: Arc<Mutex<HashMap<String, SessionRecord>>>,
+}
+
+impl SessionStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the outcome of a call for its session and return its turn number (1 for the
+    /// first call); None when droid reported no session id
+    pub fn record(&self, result: &DroidResult) -> Option<u32> {
+        if result.session_id.is_empty() {
+            return None;
+        }
+
+        let mut sessions = self.lock();
+        if !sessions.contains_key(&result.session_id) && sessions.len() >= MAX_STORED_SESSIONS {
+            let oldest = sessions
+                .iter()
+                .min_by_key(|(_, record)| record.updated_at)
+                .map(|(id, _)| id.clone());
+            if let Some(oldest) = oldest {
+                sessions.remove(&oldest);
+            }
+        }
+
+        let record = sessions
+            .entry(result.session_id.clone())
+            .or_insert_with(|| SessionRecord {
+                last_message: String::new(),
+                all_messages: Vec::new(),
+                history: String::new(),
+                working_dir: PathBuf::new(),
+                turns: 0,
+                updated_at: Instant::now(),
+            });
+        record.last_message = result.agent_messages.clone();
+        record.all_messages = result.all_messages.clone();
+        append_history(&mut record.history, &result.agent_messages);
+        record.working_dir = result.working_dir.clone();
+        record.turns += 1;
+        record.updated_at = Instant::now();
+        Some(record.turns)
+    }
+
+    /// Look up a stored session
+    pub fn get(&self, session_id: &str) -> Option<SessionRecord> {
+        self.lock().get(session_id).cloned()
+    }
+
+    /// Accumulated agent messages across all calls of a session
+    pub fn history(&self, session_id: &str) -> Option<String> {
+        self.lock()
+            .get(session_id)
+            .map(|record| record.history.clone())
+    }
+
+    /// Warning when resuming `session_id` from a different directory than it last ran in
+    ///
+    /// Sessions not run through this server process are never flagged.
+    pub fn cwd_mismatch(&self, session_id: &str, working_dir: &Path) -> Option<String> {
+        let sessions = self.lock();
+        let recorded = &sessions.get(session_id)?.working_dir;
+        if recorded.as_os_str().is_empty() || recorded == working_dir {
+            return None;
+        }
+        Some(format!(
+            "Resuming session {} in {}, but it last ran in {}",
+            session_id,
+            working_dir.display(),
+            recorded.display()
+        ))
+    }
+
+    /// Return `limit` stored messages starting at `offset`, plus the total count
+    ///
+    /// An offset past the end yields an empty page.
+    pub fn messages_page(
+        &self,
+        session_id: &str,
+        offset: usize,
+        limit: usize,
+    ) -> Option<(Vec<HashMap<String, Value>>, usize)> {
+        let sessions = self.lock();
+        let record = sessions.get(session_id)?;
+        let total = record.all_messages.len();
+        let page = record
+            .all_messages
+            .iter()
+            .skip(offset)
+            .take(limit)
+            .cloned()
+            .collect();
+        Some((page, total))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, SessionRecord>> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Append a turn to the session history, dropping the oldest content beyond the size cap
+fn append_history(history: &mut String, message: &str) {
+    if message.is_empty() {
+        return;
+    }
+    if !history.is_empty() {
+        history.push('\n');
+    }
+    history.push_str(message);
+
+    if history.len() > MAX_AGENT_MESSAGES_SIZE {
+        let mut start = history.len() - MAX_AGENT_MESSAGES_SIZE;
+        while !history.is_char_boundary(start) {
+            start += 1;
+        }
+        history.drain(..start);
+    }
+}
@@ -0,0 +1,105 @@
+//! Per-run outcome metrics behind the `run_stats` tool.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Maximum number of runs kept in memory (oldest evicted first)
+const MAX_RECORDED_RUNS: usize = 1000;
+
+/// Minimal outcome of one droid run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunMetric {
+    /// When the run finished
+    pub finished_at: Instant,
+    pub success: bool,
+    /// Wall-clock time of the run
+    pub duration: Duration,
+    /// Model reference the run used (`default` for the Factory default)
+    pub model: String,
+}
+
+/// Which recorded runs to aggregate; both bounds apply when set
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RunWindow {
+    /// Only the most recent `last` runs
+    pub last: Option<usize>,
+    /// Only runs that finished within this long before now
+    pub within: Option<Duration>,
+}
+
+/// Aggregate outcome of the runs in a window
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct RunStats {
+    pub total_runs: usize,
+    pub successful_runs: usize,
+    /// Share of successful runs in [0, 1]; absent when there were no runs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub success_rate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub average_duration_ms: Option<u64>,
+    /// Number of runs per model
+    pub models: BTreeMap<String, usize>,
+}
+
+/// In-memory log of recent run metrics, shared across clones
+///
+/// Metrics only live for the lifetime of the server process.
+#[derive(Debug, Clone, Default)]
+pub struct RunLog {
+    inner: Arc<Mutex<VecDeque<RunMetric>>>,
+}
+
+impl RunLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a finished run
+    pub fn record(&self, metric: RunMetric) {
+        let mut runs = self.lock();
+        if runs.len() >= MAX_RECORDED_RUNS {
+            runs.pop_front();
+        }
+        runs.push_back(metric);
+    }
+
+    /// Aggregate the recorded runs in `window`
+    pub fn summarize(&self, window: RunWindow) -> RunStats {
+        let runs = self.lock();
+        let now = Instant::now();
+        let selected: Vec<&RunMetric> = runs
+            .iter()
+            .rev()
+            .take(window.last.unwrap_or(usize::MAX))
+            .filter(|run| {
+                window
+                    .within
+                    .map_or(true, |within| now.duration_since(run.finished_at) <= within)
+            })
+            .collect();
+
+        let total_runs = selected.len();
+        let successful_runs = selected.iter().filter(|run| run.success).count();
+        let total_ms: u128 = selected.iter().map(|run| run.duration.as_millis()).sum();
+        let mut models = BTreeMap::new();
+        for run in &selected {
+            *models.entry(run.model.clone()).or_default() += 1;
+        }
+
+        RunStats {
+            total_runs,
+            successful_runs,
+            success_rate: (total_runs > 0).then(|| successful_runs as f64 / total_runs as f64),
+            average_duration_ms: (total_runs > 0).then(|| (total_ms / total_runs as u128) as u64),
+            models,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, VecDeque<RunMetric>> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
@@ -0,0 +1,51 @@
+//! `allowed_cwds` exact-match working directory allowlist (server config from a temp file).
+
+mod common;
+
+use common::TestConfig;
+use droid_mcp_rs::server::DroidServer;
+use std::path::PathBuf;
+
+/// Load a config allowing exactly one project directory, returned canonicalized
+fn allowed_dir() -> (TestConfig, PathBuf) {
+    let config = common::with_config(serde_json::json!({}));
+    let project = config.dir().join("project");
+    std::fs::create_dir_all(project.join("sub")).unwrap();
+    config.reload(serde_json::json!({ "allowed_cwds": [project] }));
+    let project = project.canonicalize().unwrap();
+    (config, project)
+}
+
+#[test]
+fn listed_cwd_is_allowed() {
+    let (_config, allowed) = allowed_dir();
+    let server = DroidServer::new();
+
+    assert_eq!(
+        server.resolve_working_dir(Some(allowed.clone())).unwrap(),
+        allowed
+    );
+    // Any spelling that canonicalizes to the entry matches
+    assert_eq!(
+        server
+            .resolve_working_dir(Some(allowed.join("sub").join("..")))
+            .unwrap(),
+        allowed
+    );
+}
+
+#[test]
+fn unlisted_cwd_is_rejected() {
+    let (_config, allowed) = allowed_dir();
+    let other = tempfile::tempdir().expect("failed to create temp dir");
+    let server = DroidServer::new();
+
+    for cwd in [other.path().to_path_buf(), allowed.join("sub")] {
+        let err = server.resolve_working_dir(Some(cwd)).unwrap_err();
+        assert!(
+            err.message.contains("not in allowed_cwds"),
+            "{}",
+            err.message
+        );
+    }
+}
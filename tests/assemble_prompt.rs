@@ -0,0 +1,180 @@
+//! Prompt assembly with a context file and system prompt.
+
+use droid_mcp_rs::droid::{self, Options};
+
+#[tokio::test]
+async fn context_file_is_appended_after_prompt() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let context = dir.path().join("notes.txt");
+    std::fs::write(&context, "API v2 is deprecated.").unwrap();
+    let opts = Options {
+        prompt: Some("Update the client".to_string()),
+        working_dir: dir.path().to_path_buf(),
+        context_file: Some(context),
+        ..Default::default()
+    };
+
+    let (prompt, warnings) = droid::assemble_prompt(&opts).await;
+
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    assert_eq!(
+        prompt,
+        "Update the client\n\n<context>\nAPI v2 is deprecated.\n</context>"
+    );
+}
+
+#[tokio::test]
+async fn oversized_context_file_is_truncated_with_warning() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let context = dir.path().join("big.txt");
+    std::fs::write(&context, "x".repeat(1024 * 1024 + 100)).unwrap();
+    let opts = Options {
+        prompt: Some("Summarize".to_string()),
+        working_dir: dir.path().to_path_buf(),
+        context_file: Some(context),
+        ..Default::default()
+    };
+
+    let (prompt, warnings) = droid::assemble_prompt(&opts).await;
+
+    assert_eq!(warnings.len(), 1, "{:?}", warnings);
+    assert!(warnings[0].contains("context_file"), "{:?}", warnings);
+    assert!(warnings[0].contains("truncated"), "{:?}", warnings);
+    let body = prompt
+        .strip_prefix("Summarize\n\n<context>\n")
+        .and_then(|rest| rest.strip_suffix("\n</context>"))
+        .expect("unexpected prompt layout");
+    assert_eq!(body.len(), 1024 * 1024);
+}
+
+#[tokio::test]
+async fn system_prompt_alone_is_wrapped() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let opts = Options {
+        prompt: Some("Fix the build".to_string()),
+        working_dir: dir.path().to_path_buf(),
+        system_prompt: Some("Answer tersely.".to_string()),
+        ..Default::default()
+    };
+
+    let (prompt, warnings) = droid::assemble_prompt(&opts).await;
+
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    assert_eq!(
+        prompt,
+        "<system_prompt>\nAnswer tersely.\n</system_prompt>\n\nFix the build"
+    );
+}
+
+#[tokio::test]
+async fn system_prompt_follows_droid_md_in_one_block() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    std::fs::write(dir.path().join("DROID.md"), "Project rules.").unwrap();
+    let context = dir.path().join("notes.txt");
+    std::fs::write(&context, "Some notes.").unwrap();
+    let opts = Options {
+        prompt: Some("Fix the build".to_string()),
+        working_dir: dir.path().to_path_buf(),
+        system_prompt: Some("Answer tersely.".to_string()),
+        context_file: Some(context),
+        ..Default::default()
+    };
+
+    let (prompt, warnings) = droid::assemble_prompt(&opts).await;
+
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    assert_eq!(
+        prompt,
+        "<system_prompt>\nProject rules.\n\nAnswer tersely.\n</system_prompt>\n\n\
+         Fix the build\n\n<context>\nSome notes.\n</context>"
+    );
+}
+
+#[tokio::test]
+async fn system_prompt_is_kept_when_droid_md_is_disabled() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    std::fs::write(dir.path().join("DROID.md"), "Project rules.").unwrap();
+    let opts = Options {
+        prompt: Some("Fix the build".to_string()),
+        working_dir: dir.path().to_path_buf(),
+        use_droid_md: Some(false),
+        system_prompt: Some("Answer tersely.".to_string()),
+        ..Default::default()
+    };
+
+    let (prompt, _) = droid::assemble_prompt(&opts).await;
+
+    assert!(!prompt.contains("Project rules."), "{}", prompt);
+    assert!(prompt.starts_with("<system_prompt>\nAnswer tersely.\n"));
+}
+
+#[tokio::test]
+async fn combined_system_prompt_respects_size_limit() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    std::fs::write(dir.path().join("DROID.md"), "d".repeat(1024 * 1024 - 10)).unwrap();
+    let opts = Options {
+        prompt: Some("Go".to_string()),
+        working_dir: dir.path().to_path_buf(),
+        system_prompt: Some("s".repeat(100)),
+        ..Default::default()
+    };
+
+    let (prompt, warnings) = droid::assemble_prompt(&opts).await;
+
+    assert_eq!(warnings.len(), 1, "{:?}", warnings);
+    assert!(
+        warnings[0].contains("system_prompt truncated to 8 bytes"),
+        "{:?}",
+        warnings
+    );
+    let body = prompt
+        .strip_prefix("<system_prompt>\n")
+        .and_then(|rest| rest.strip_suffix("\n</system_prompt>\n\nGo"))
+        .expect("unexpected prompt layout");
+    assert_eq!(body.len(), 1024 * 1024);
+    assert!(body.ends_with("\n\nssssssss"));
+}
+
+#[tokio::test]
+async fn run_rejects_prompt_that_assembles_to_whitespace() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    std::fs::write(dir.path().join("DROID.md"), "Project rules.").unwrap();
+    let opts = Options {
+        prompt: Some(" \n\t ".to_string()),
+        working_dir: dir.path().to_path_buf(),
+        system_prompt: Some("Be terse.".to_string()),
+        ..Default::default()
+    };
+
+    let err = droid::run(opts)
+        .await
+        .expect_err("empty prompt should fail");
+
+    assert!(
+        err.to_string().contains("assembled prompt is empty"),
+        "{}",
+        err
+    );
+    assert!(err.downcast_ref::<droid::Rejected>().is_some(), "{:#}", err);
+}
+
+#[tokio::test]
+async fn run_rejects_globs_matching_nothing_without_prompt() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let opts = Options {
+        prompt: Some(String::new()),
+        working_dir: dir.path().to_path_buf(),
+        context_globs: vec!["src/**/*.rs".to_string()],
+        ..Default::default()
+    };
+
+    let err = droid::run(opts)
+        .await
+        .expect_err("empty prompt should fail");
+
+    assert!(
+        err.to_string().contains("assembled prompt is empty"),
+        "{}",
+        err
+    );
+}
@@ -0,0 +1,56 @@
+//! `context_globs` share the `max_attachments` / `max_attachment_bytes` budget.
+
+mod common;
+
+use droid_mcp_rs::droid::{self, Options};
+use std::path::Path;
+
+fn write(dir: &Path, name: &str, content: &str) -> std::path::PathBuf {
+    let path = dir.join(name);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, content).unwrap();
+    path
+}
+
+#[tokio::test]
+async fn globs_get_what_file_and_context_file_leave() {
+    let config = common::with_config(serde_json::json!({
+        "max_attachments": 3,
+        "max_attachment_bytes": 100
+    }));
+    let dir = config.dir();
+    write(dir, "src/a.rs", &"a".repeat(30));
+    write(dir, "src/b.rs", &"b".repeat(30));
+
+    // 40 bytes are left after a 60-byte context_file, so only one 30-byte match fits
+    let opts = Options {
+        prompt: Some("Review".to_string()),
+        working_dir: dir.to_path_buf(),
+        context_file: Some(write(dir, "notes.txt", &"n".repeat(60))),
+        context_globs: vec!["src/*.rs".to_string()],
+        ..Default::default()
+    };
+    let (prompt, warnings) = droid::assemble_prompt(&opts).await;
+    assert!(prompt.contains("// file: src/a.rs"), "{}", prompt);
+    assert!(!prompt.contains("// file: src/b.rs"), "{}", prompt);
+    assert!(
+        warnings.iter().any(|w| w.contains("40-byte limit")),
+        "{:?}",
+        warnings
+    );
+
+    // file and context_file take two of the three slots
+    let opts = Options {
+        file: Some(write(dir, "task.md", "t")),
+        context_file: Some(write(dir, "short.txt", "s")),
+        ..opts
+    };
+    let (prompt, warnings) = droid::assemble_prompt(&opts).await;
+    assert!(prompt.contains("// file: src/a.rs"), "{}", prompt);
+    assert!(!prompt.contains("// file: src/b.rs"), "{}", prompt);
+    assert!(
+        warnings.iter().any(|w| w.contains("only the first 1")),
+        "{:?}",
+        warnings
+    );
+}
@@ -0,0 +1,104 @@
+//! Audit log records written by `droid::run` (server config loaded from a temp file).
+#![cfg(unix)]
+
+mod common;
+
+use droid_mcp_rs::audit;
+use droid_mcp_rs::droid::{self, Options};
+
+/// Run once with `audit_log_path` (plus `extra` config) set and return the single record
+async fn audited_run(extra: serde_json::Value) -> (common::TestConfig, serde_json::Value) {
+    let audit_dir = tempfile::tempdir().expect("failed to create temp dir");
+    let audit_path = audit_dir.path().join("audit.jsonl");
+    let mut config = serde_json::json!({ "audit_log_path": audit_path });
+    config
+        .as_object_mut()
+        .unwrap()
+        .extend(extra.as_object().unwrap().clone());
+    let config = common::with_config(config);
+
+    let opts = Options {
+        prompt: Some("say hello".to_string()),
+        working_dir: config.dir().to_path_buf(),
+        model: Some("raw-test-model".to_string()),
+        auto: Some("low".to_string()),
+        ..Default::default()
+    };
+    let result = droid::run(opts).await.expect("droid::run failed");
+    assert!(result.success, "unexpected error: {:?}", result.error);
+
+    let log = std::fs::read_to_string(&audit_path).expect("audit log not written");
+    let lines: Vec<&str> = log.lines().collect();
+    assert_eq!(lines.len(), 1);
+    let record = serde_json::from_str(lines[0]).unwrap();
+    (config, record)
+}
+
+#[tokio::test]
+async fn run_appends_audit_record() {
+    let (config, record) = audited_run(serde_json::json!({})).await;
+
+    assert!(record["timestamp"].as_u64().is_some_and(|t| t > 0));
+    assert_eq!(record["cwd"], serde_json::json!(config.dir()));
+    assert_eq!(record["model"], "raw-test-model");
+    assert_eq!(record["auto"], "low");
+    assert_eq!(record["session_id"], "fake-session-ok");
+    assert_eq!(record["success"], true);
+    assert_eq!(record["prompt_len"], "say hello".len());
+    assert_eq!(record["prompt_sha256"], audit::prompt_sha256("say hello"));
+    assert!(record.get("prompt").is_none(), "{}", record);
+    let args = record["args"].as_array().unwrap();
+    assert_eq!(args[0], "exec");
+    assert!(!args.iter().any(|a| a == "say hello"), "{}", record);
+}
+
+#[tokio::test]
+async fn full_prompt_is_logged_when_enabled() {
+    let (_config, record) = audited_run(serde_json::json!({ "log_full_prompt": true })).await;
+
+    assert_eq!(record["prompt"], "say hello");
+    assert_eq!(record["prompt_sha256"], audit::prompt_sha256("say hello"));
+}
+
+#[tokio::test]
+async fn rejected_run_is_audited() {
+    let audit_dir = tempfile::tempdir().expect("failed to create temp dir");
+    let audit_path = audit_dir.path().join("audit.jsonl");
+    let config = common::with_config(serde_json::json!({
+        "audit_log_path": audit_path,
+        "denied_models": ["raw-test-model"]
+    }));
+
+    let opts = Options {
+        prompt: Some("say hello".to_string()),
+        working_dir: config.dir().to_path_buf(),
+        model: Some("raw-test-model".to_string()),
+        auto: Some("low".to_string()),
+        ..Default::default()
+    };
+    droid::run(opts)
+        .await
+        .expect_err("denied model should be rejected");
+
+    let log = std::fs::read_to_string(&audit_path).expect("audit log not written");
+    let lines: Vec<&str> = log.lines().collect();
+    assert_eq!(lines.len(), 1);
+    let record: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+    assert_eq!(record["model"], "raw-test-model");
+    assert_eq!(record["success"], false);
+    assert!(
+        record["error"].as_str().unwrap().contains("is denied"),
+        "{}",
+        record
+    );
+    // Rejected before the prompt was assembled
+    assert!(record.get("prompt_sha256").is_none(), "{}", record);
+}
+
+#[test]
+fn prompt_sha256_matches_known_digest() {
+    assert_eq!(
+        audit::prompt_sha256("abc"),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+}
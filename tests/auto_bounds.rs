@@ -0,0 +1,93 @@
+//! `min_auto` / `max_auto` autonomy bounds (server config loaded from a temp file).
+#![cfg(unix)]
+
+mod common;
+
+use common::{with_config, TestConfig};
+use droid_mcp_rs::droid::{self, Options};
+
+/// Load a config bounding autonomy to [medium, medium], and use the fake droid
+fn setup() -> TestConfig {
+    with_config(serde_json::json!({ "min_auto": "medium", "max_auto": "medium" }))
+}
+
+async fn run_with_auto(auto: Option<&str>) -> anyhow::Result<droid::DroidResult> {
+    let config = setup();
+    droid::run(Options {
+        prompt: Some("say hello".to_string()),
+        working_dir: config.dir().to_path_buf(),
+        auto: auto.map(str::to_string),
+        ..Default::default()
+    })
+    .await
+}
+
+#[tokio::test]
+async fn low_is_raised_to_min_auto() {
+    let result = run_with_auto(Some("low")).await.expect("droid::run failed");
+
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert_eq!(result.effective_auto.as_deref(), Some("medium"));
+    assert!(
+        result
+            .warnings
+            .iter()
+            .any(|w| w.contains("raised to 'medium'")),
+        "{:?}",
+        result.warnings
+    );
+}
+
+#[tokio::test]
+async fn high_default_is_capped_at_max_auto() {
+    let result = run_with_auto(None).await.expect("droid::run failed");
+
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert_eq!(result.effective_auto.as_deref(), Some("medium"));
+    assert!(
+        result
+            .warnings
+            .iter()
+            .any(|w| w.contains("lowered to 'medium'")),
+        "{:?}",
+        result.warnings
+    );
+    assert!(
+        !result.warnings.iter().any(|w| w.contains("HIGH autonomy")),
+        "{:?}",
+        result.warnings
+    );
+}
+
+#[tokio::test]
+async fn explicit_request_above_max_auto_is_rejected() {
+    let err = run_with_auto(Some("high"))
+        .await
+        .expect_err("run should fail");
+    assert!(err.to_string().contains("max_auto"), "{}", err);
+    assert!(err.downcast_ref::<droid::Rejected>().is_some(), "{:#}", err);
+}
+
+#[tokio::test]
+async fn spec_auto_above_max_auto_is_rejected() {
+    let config = setup();
+    let err = droid::run(Options {
+        prompt: Some("say hello".to_string()),
+        working_dir: config.dir().to_path_buf(),
+        auto: Some("medium".to_string()),
+        use_spec: true,
+        spec_auto: Some("high".to_string()),
+        ..Default::default()
+    })
+    .await
+    .expect_err("run should fail");
+
+    assert!(err.to_string().contains("spec_auto"), "{}", err);
+    assert!(err.to_string().contains("max_auto"), "{}", err);
+}
+
+#[test]
+fn schema_levels_stop_at_max_auto() {
+    let _config = setup();
+    assert_eq!(droid::allowed_auto_levels(), ["low", "medium"]);
+}
@@ -0,0 +1,45 @@
+//! `cancel_all` kills every droid run in flight.
+#![cfg(unix)]
+
+mod common;
+
+use droid_mcp_rs::droid::{self, ErrorCode, Options};
+use std::time::{Duration, Instant};
+
+#[tokio::test]
+async fn cancel_all_reaps_every_running_droid() {
+    common::use_fake_droid();
+
+    // Separate directories, so the runs don't wait on each other's cwd lock
+    let dirs: Vec<_> = (0..3)
+        .map(|_| tempfile::tempdir().expect("failed to create temp dir"))
+        .collect();
+    let runs: Vec<_> = dirs
+        .iter()
+        .map(|dir| {
+            tokio::spawn(droid::run(Options {
+                prompt: Some("scenario:hang".to_string()),
+                working_dir: dir.path().to_path_buf(),
+                auto: Some("low".to_string()),
+                ..Default::default()
+            }))
+        })
+        .collect();
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while droid::active_runs() < 3 {
+        assert!(Instant::now() < deadline, "runs did not start");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    let started = Instant::now();
+    assert_eq!(droid::cancel_all(), 3);
+    for run in runs {
+        let result = run.await.unwrap().expect("droid::run failed");
+        assert!(!result.success);
+        assert_eq!(result.error_code, Some(ErrorCode::Cancelled));
+    }
+    assert!(started.elapsed() < Duration::from_secs(10));
+    assert_eq!(droid::active_runs(), 0);
+    assert_eq!(droid::cancel_all(), 0);
+}
@@ -0,0 +1,120 @@
+//! Stderr logging of the server binary driven over MCP stdio.
+#![cfg(unix)]
+
+mod common;
+
+use common::McpSession;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Run one droid tool call through the server binary and return its stderr
+fn server_stderr(flags: &[&str]) -> String {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_droid-mcp-rs"))
+        .args(flags)
+        .current_dir(dir.path())
+        .env("DROID_BIN", common::fake_droid())
+        .env("DROID_MCP_CONFIG_PATH", dir.path().join("missing.json"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to start server");
+
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut send = |message: serde_json::Value| {
+        writeln!(stdin, "{}", message).expect("failed to write request");
+    };
+    let mut wait_for = |id: u64| {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            assert!(
+                stdout.read_line(&mut line).unwrap() > 0,
+                "server closed stdout"
+            );
+            let response: serde_json::Value = serde_json::from_str(&line).unwrap();
+            if response["id"] == id {
+                return response;
+            }
+        }
+    };
+
+    send(serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "initialize",
+        "params": {
+            "protocolVersion": "2024-11-05",
+            "capabilities": {},
+            "clientInfo": { "name": "test", "version": "0" }
+        }
+    }));
+    wait_for(1);
+    send(serde_json::json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }));
+    send(serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 2,
+        "method": "tools/call",
+        "params": {
+            "name": "droid",
+            "arguments": { "PROMPT": "say hello", "raw_model": "test-raw-model", "auto": "low" }
+        }
+    }));
+    let response = wait_for(2);
+    assert!(response.get("result").is_some(), "{}", response);
+
+    // Closing stdin ends the MCP session and the server exits
+    drop(send);
+    drop(stdin);
+    let output = child.wait_with_output().expect("server did not exit");
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn normal_logs_model_info() {
+    let stderr = server_stderr(&[]);
+    assert!(
+        stderr.contains("test-raw-model (raw passthrough)"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn quiet_suppresses_model_info() {
+    let stderr = server_stderr(&["--quiet"]);
+    assert!(!stderr.contains("raw passthrough"), "{}", stderr);
+}
+
+#[test]
+fn droid_stderr_is_logged_while_running() {
+    let (mut server, stderr) = McpSession::start_with_stderr(serde_json::json!({}));
+    let (seen_tx, seen_rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+            if line.contains("[droid stderr] early diagnostic") {
+                let _ = seen_tx.send(Instant::now());
+            }
+        }
+    });
+
+    let response = server.call(
+        "droid",
+        serde_json::json!({ "PROMPT": "scenario:stderr-stream", "auto": "low" }),
+    );
+    let finished = Instant::now();
+    assert!(response.get("result").is_some(), "{}", response);
+
+    // The fake sleeps 2s after the line; it must have been logged before the run ended
+    let seen = seen_rx
+        .recv_timeout(Duration::from_secs(5))
+        .expect("droid stderr line was not logged");
+    assert!(
+        seen + Duration::from_secs(1) < finished,
+        "logged only {:?} before the response",
+        finished.saturating_duration_since(seen)
+    );
+}
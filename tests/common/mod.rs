@@ -0,0 +1,200 @@
+//! Helpers shared by the integration tests: the fake droid and per-test server configs.
+#![allow(dead_code)]
+
+use droid_mcp_rs::droid;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, Once, PoisonError};
+
+/// tests/fixtures/fake-droid.sh, the scripted stand-in for droid
+pub fn fake_droid() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join("fake-droid.sh")
+}
+
+/// Point DROID_BIN at the fake droid for the rest of the test binary
+pub fn use_fake_droid() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| std::env::set_var("DROID_BIN", fake_droid()));
+}
+
+/// A server config loaded for the duration of one test
+///
+/// Tests in one binary share the process environment and the config cache, so holding
+/// this also keeps every other `with_config` test in the binary waiting.
+pub struct TestConfig {
+    dir: tempfile::TempDir,
+    _serial: MutexGuard<'static, ()>,
+}
+
+impl TestConfig {
+    /// Temp dir holding the config file, also usable as the test's working directory
+    pub fn dir(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// The config file DROID_MCP_CONFIG_PATH points at
+    pub fn path(&self) -> PathBuf {
+        self.dir.path().join("droid-mcp.config.json")
+    }
+
+    /// Replace the config file without reloading it
+    pub fn rewrite(&self, config: serde_json::Value) {
+        std::fs::write(self.path(), config.to_string()).expect("failed to write config");
+    }
+
+    /// Replace the config file and reload it
+    pub fn reload(&self, config: serde_json::Value) {
+        self.rewrite(config);
+        droid::reload_config().expect("test config failed to load");
+    }
+
+    /// Use the config dir as HOME with `factory` as its Factory config, and reload
+    pub fn use_home(&self, factory: serde_json::Value) {
+        let factory_dir = self.dir().join(".factory");
+        std::fs::create_dir_all(&factory_dir).expect("failed to create .factory");
+        std::fs::write(factory_dir.join("config.json"), factory.to_string())
+            .expect("failed to write Factory config");
+        std::env::set_var("HOME", self.dir());
+        std::env::set_var("USERPROFILE", self.dir());
+        droid::reload_config().expect("test config failed to load");
+    }
+}
+
+/// Load `config` as the server config, with DROID_BIN pointing at the fake droid
+pub fn with_config(config: serde_json::Value) -> TestConfig {
+    static SERIAL: Mutex<()> = Mutex::new(());
+    let serial = SERIAL.lock().unwrap_or_else(PoisonError::into_inner);
+
+    let test_config = TestConfig {
+        dir: tempfile::tempdir().expect("failed to create temp dir"),
+        _serial: serial,
+    };
+    test_config.rewrite(config);
+    std::env::set_var("DROID_MCP_CONFIG_PATH", test_config.path());
+    // Reset every time, in case the previous test pointed it elsewhere
+    std::env::set_var("DROID_BIN", fake_droid());
+    droid::reload_config().expect("test config failed to load");
+    test_config
+}
+
+/// A droid-mcp-rs server process driven over MCP stdio, already initialized
+///
+/// Runs against the fake droid, in a temp dir that is also its working directory and HOME.
+pub struct McpSession {
+    child: std::process::Child,
+    stdin: std::process::ChildStdin,
+    stdout: std::io::BufReader<std::process::ChildStdout>,
+    next_id: u64,
+    dir: tempfile::TempDir,
+}
+
+impl McpSession {
+    /// Start the server with `config` as its config file
+    pub fn start(config: serde_json::Value) -> Self {
+        Self::spawn(config, std::process::Stdio::null())
+    }
+
+    /// Like [`McpSession::start`], also returning the server's stderr
+    pub fn start_with_stderr(config: serde_json::Value) -> (Self, std::process::ChildStderr) {
+        let mut session = Self::spawn(config, std::process::Stdio::piped());
+        let stderr = session.child.stderr.take().unwrap();
+        (session, stderr)
+    }
+
+    fn spawn(config: serde_json::Value, stderr: std::process::Stdio) -> Self {
+        use std::process::{Command, Stdio};
+
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        let config_path = dir.path().join("droid-mcp.config.json");
+        std::fs::write(&config_path, config.to_string()).expect("failed to write config");
+        let mut child = Command::new(env!("CARGO_BIN_EXE_droid-mcp-rs"))
+            .current_dir(dir.path())
+            .env("DROID_BIN", fake_droid())
+            .env("DROID_MCP_CONFIG_PATH", &config_path)
+            .env("HOME", dir.path())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(stderr)
+            .spawn()
+            .expect("failed to start server");
+
+        let mut session = McpSession {
+            stdin: child.stdin.take().unwrap(),
+            stdout: std::io::BufReader::new(child.stdout.take().unwrap()),
+            child,
+            next_id: 1,
+            dir,
+        };
+        session.request(
+            "initialize",
+            serde_json::json!({
+                "protocolVersion": "2024-11-05",
+                "capabilities": {},
+                "clientInfo": { "name": "test", "version": "0" }
+            }),
+        );
+        session
+            .send(serde_json::json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }));
+        session
+    }
+
+    /// The server's working directory
+    pub fn dir(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// Call `tool` and return the whole JSON-RPC response, `result` or `error`
+    pub fn call(&mut self, tool: &str, arguments: serde_json::Value) -> serde_json::Value {
+        self.request(
+            "tools/call",
+            serde_json::json!({ "name": tool, "arguments": arguments }),
+        )
+    }
+
+    fn request(&mut self, method: &str, params: serde_json::Value) -> serde_json::Value {
+        use std::io::BufRead;
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params
+        }));
+        let mut line = String::new();
+        loop {
+            line.clear();
+            assert!(
+                self.stdout.read_line(&mut line).unwrap() > 0,
+                "server closed stdout"
+            );
+            let response: serde_json::Value = serde_json::from_str(&line).unwrap();
+            if response["id"] == id {
+                return response;
+            }
+        }
+    }
+
+    fn send(&mut self, message: serde_json::Value) {
+        use std::io::Write;
+
+        writeln!(self.stdin, "{}", message).expect("failed to write request");
+    }
+}
+
+impl Drop for McpSession {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// The first text content of a tool call response
+pub fn tool_text(response: &serde_json::Value) -> &str {
+    response["result"]["content"][0]["text"]
+        .as_str()
+        .unwrap_or_else(|| panic!("no text content: {}", response))
+}
@@ -0,0 +1,100 @@
+//! Precedence of the server config path sources.
+
+use droid_mcp_rs::droid::{select_config_path, user_config_path};
+use std::path::{Path, PathBuf};
+
+const CWD: &str = "/work";
+
+fn select(cli: Option<&str>, env: Option<&str>) -> Option<PathBuf> {
+    select_config_path(cli.map(Path::new), env, Some(Path::new(CWD)), None)
+}
+
+#[test]
+fn cli_flag_wins_over_env_and_default() {
+    assert_eq!(
+        select(Some("/cli.json"), Some("/env.json")),
+        Some(PathBuf::from("/cli.json"))
+    );
+}
+
+#[test]
+fn cli_flag_alone_is_used() {
+    assert_eq!(
+        select(Some("/cli.json"), None),
+        Some(PathBuf::from("/cli.json"))
+    );
+}
+
+#[test]
+fn env_var_wins_over_default() {
+    assert_eq!(
+        select(None, Some("  /env.json ")),
+        Some(PathBuf::from("/env.json"))
+    );
+}
+
+#[test]
+fn blank_env_var_falls_back_to_default() {
+    assert_eq!(
+        select(None, Some("   ")),
+        Some(Path::new(CWD).join("droid-mcp.config.json"))
+    );
+}
+
+#[test]
+fn default_is_config_in_cwd() {
+    assert_eq!(
+        select(None, None),
+        Some(Path::new(CWD).join("droid-mcp.config.json"))
+    );
+    assert_eq!(select_config_path(None, None, None, None), None);
+}
+
+#[test]
+fn user_config_dir_is_used_when_cwd_has_no_config() {
+    let xdg = tempfile::tempdir().expect("failed to create temp dir");
+    let cwd = tempfile::tempdir().expect("failed to create temp dir");
+    let user_config = xdg.path().join("droid-mcp").join("config.json");
+    std::fs::create_dir_all(user_config.parent().unwrap()).unwrap();
+    std::fs::write(&user_config, "{}").unwrap();
+    std::env::set_var("XDG_CONFIG_HOME", xdg.path());
+
+    assert_eq!(user_config_path(), Some(user_config.clone()));
+    assert_eq!(
+        select_config_path(None, None, Some(cwd.path()), user_config_path().as_deref()),
+        Some(user_config.clone())
+    );
+
+    // A config in the working directory still takes precedence
+    let cwd_config = cwd.path().join("droid-mcp.config.json");
+    std::fs::write(&cwd_config, "{}").unwrap();
+    assert_eq!(
+        select_config_path(None, None, Some(cwd.path()), Some(&user_config)),
+        Some(cwd_config)
+    );
+    // So do the CLI flag and environment variable
+    assert_eq!(
+        select_config_path(
+            None,
+            Some("/env.json"),
+            Some(cwd.path()),
+            Some(&user_config)
+        ),
+        Some(PathBuf::from("/env.json"))
+    );
+}
+
+#[test]
+fn missing_user_config_falls_back_to_cwd_path() {
+    let cwd = tempfile::tempdir().expect("failed to create temp dir");
+
+    assert_eq!(
+        select_config_path(
+            None,
+            None,
+            Some(cwd.path()),
+            Some(Path::new("/nonexistent/droid-mcp/config.json"))
+        ),
+        Some(cwd.path().join("droid-mcp.config.json"))
+    );
+}
@@ -0,0 +1,42 @@
+//! SIGHUP reloads the server config for subsequent runs.
+#![cfg(unix)]
+
+mod common;
+
+use droid_mcp_rs::droid::{self, Options};
+use std::time::Duration;
+
+#[tokio::test]
+async fn sighup_reloads_config_for_next_run() {
+    let config = common::with_config(serde_json::json!({ "timeout_secs": 100 }));
+    let audit_path = config.dir().join("audit.jsonl");
+    let opts = || Options {
+        prompt: Some("say hello".to_string()),
+        working_dir: config.dir().to_path_buf(),
+        ..Default::default()
+    };
+
+    droid::spawn_reload_on_sighup().expect("failed to install SIGHUP handler");
+    assert_eq!(droid::default_timeout_secs(), 100);
+    droid::run(opts()).await.expect("droid::run failed");
+    assert!(!audit_path.exists());
+
+    config.rewrite(serde_json::json!({ "timeout_secs": 200, "audit_log_path": audit_path }));
+    let status = std::process::Command::new("kill")
+        .arg("-HUP")
+        .arg(std::process::id().to_string())
+        .status()
+        .expect("failed to run kill");
+    assert!(status.success());
+
+    for _ in 0..100 {
+        if droid::default_timeout_secs() == 200 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(droid::default_timeout_secs(), 200);
+
+    droid::run(opts()).await.expect("droid::run failed");
+    assert!(audit_path.exists(), "reloaded audit_log_path was not used");
+}
@@ -0,0 +1,93 @@
+//! `context_globs` file collection, `.droidignore` filtering and limits.
+
+use droid_mcp_rs::context::{self, GlobLimits};
+
+fn write(dir: &std::path::Path, name: &str, content: &str) {
+    let path = dir.join(name);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, content).unwrap();
+}
+
+#[test]
+fn matching_files_are_concatenated_with_headers() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    write(dir.path(), "src/a.rs", "fn a() {}\n");
+    write(dir.path(), "src/nested/b.rs", "fn b() {}");
+    write(dir.path(), "README.md", "readme");
+
+    let (text, warnings) = context::collect(
+        dir.path(),
+        &["src/**/*.rs".to_string()],
+        GlobLimits::default(),
+    );
+
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    assert_eq!(
+        text,
+        "// file: src/a.rs\nfn a() {}\n\n// file: src/nested/b.rs\nfn b() {}"
+    );
+}
+
+#[test]
+fn droidignore_excludes_files_and_directories() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    write(dir.path(), "src/a.rs", "keep");
+    write(dir.path(), "src/generated/b.rs", "skip");
+    write(dir.path(), "src/c_test.rs", "skip");
+    write(
+        dir.path(),
+        ".droidignore",
+        "# build output\nsrc/generated/\n*_test.rs\n",
+    );
+
+    let (text, warnings) = context::collect(
+        dir.path(),
+        &["src/**/*.rs".to_string()],
+        GlobLimits::default(),
+    );
+
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    assert_eq!(text, "// file: src/a.rs\nkeep");
+}
+
+#[test]
+fn limits_cap_included_files() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    for name in ["a.txt", "b.txt", "c.txt"] {
+        write(dir.path(), name, &"x".repeat(40));
+    }
+    let globs = ["*.txt".to_string()];
+
+    let (text, warnings) = context::collect(
+        dir.path(),
+        &globs,
+        GlobLimits {
+            max_files: 50,
+            max_bytes: 100,
+        },
+    );
+    assert!(text.contains("a.txt") && text.contains("b.txt"), "{}", text);
+    assert!(!text.contains("c.txt"), "{}", text);
+    assert_eq!(warnings.len(), 1, "{:?}", warnings);
+    assert!(warnings[0].contains("included 2 of 3"), "{:?}", warnings);
+
+    let (text, warnings) = context::collect(
+        dir.path(),
+        &globs,
+        GlobLimits {
+            max_files: 1,
+            max_bytes: 1024,
+        },
+    );
+    assert_eq!(text, format!("// file: a.txt\n{}", "x".repeat(40)));
+    assert!(warnings[0].contains("only the first 1"), "{:?}", warnings);
+}
+
+#[test]
+fn globs_outside_working_dir_are_rejected() {
+    assert!(context::validate_glob("src/**/*.rs").is_ok());
+    assert!(context::validate_glob("../secrets/*").is_err());
+    assert!(context::validate_glob("src/../../x").is_err());
+    assert!(context::validate_glob("/etc/*").is_err());
+    assert!(context::validate_glob("").is_err());
+}
@@ -0,0 +1,115 @@
+//! Modifying runs in the same working directory are serialized, or rejected with
+//! `reject_busy_cwd`.
+#![cfg(unix)]
+
+mod common;
+
+use common::with_config;
+use droid_mcp_rs::droid::{self, Options};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+fn slow_edit(working_dir: &Path) -> Options {
+    edit(working_dir, "scenario:slow", None)
+}
+
+fn edit(working_dir: &Path, prompt: &str, timeout_secs: Option<u64>) -> Options {
+    Options {
+        prompt: Some(prompt.to_string()),
+        working_dir: working_dir.to_path_buf(),
+        auto: Some("low".to_string()),
+        timeout_secs,
+        ..Default::default()
+    }
+}
+
+fn waited(result: &droid::DroidResult) -> bool {
+    result
+        .warnings
+        .iter()
+        .any(|w| w.contains("for another modifying run"))
+}
+
+#[tokio::test]
+async fn edit_runs_in_same_dir_run_one_after_another() {
+    let _config = with_config(serde_json::json!({}));
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let started = Instant::now();
+    let (first, second) = tokio::join!(
+        droid::run(slow_edit(dir.path())),
+        droid::run(slow_edit(dir.path()))
+    );
+    let (first, second) = (first.unwrap(), second.unwrap());
+
+    assert!(first.success && second.success);
+    // Each fake run sleeps a second, so serialized runs take at least two
+    assert!(started.elapsed() >= Duration::from_secs(2));
+    assert!(
+        waited(&first) != waited(&second),
+        "{:?} / {:?}",
+        first,
+        second
+    );
+}
+
+#[tokio::test]
+async fn waiting_for_the_lock_counts_against_the_timeout() {
+    let _config = with_config(serde_json::json!({}));
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let (first, second) = tokio::join!(
+        droid::run(edit(dir.path(), "scenario:hang", Some(2))),
+        async {
+            // Make sure the hanging run takes the lock first
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            droid::run(edit(dir.path(), "scenario:stall", Some(3))).await
+        }
+    );
+    let (first, second) = (first.unwrap(), second.unwrap());
+
+    assert!(!first.success);
+    // The stall takes 2.5 seconds but only about one of its three is left after the wait
+    assert!(waited(&second), "{:?}", second);
+    assert_eq!(
+        second.error_code,
+        Some(droid::ErrorCode::Timeout),
+        "{:?}",
+        second
+    );
+}
+
+#[tokio::test]
+async fn edit_runs_in_different_dirs_overlap() {
+    let _config = with_config(serde_json::json!({}));
+    let first_dir = tempfile::tempdir().expect("failed to create temp dir");
+    let second_dir = tempfile::tempdir().expect("failed to create temp dir");
+    let (first, second) = tokio::join!(
+        droid::run(slow_edit(first_dir.path())),
+        droid::run(slow_edit(second_dir.path()))
+    );
+    let (first, second) = (first.unwrap(), second.unwrap());
+
+    assert!(first.success && second.success);
+    assert!(!waited(&first) && !waited(&second));
+}
+
+#[tokio::test]
+async fn concurrent_edit_run_is_rejected_with_reject_busy_cwd() {
+    let config = with_config(serde_json::json!({ "reject_busy_cwd": true }));
+
+    let (first, second) = tokio::join!(
+        droid::run(slow_edit(config.dir())),
+        droid::run(slow_edit(config.dir()))
+    );
+    let (succeeded, rejected) = match (first, second) {
+        (Ok(result), Err(e)) | (Err(e), Ok(result)) => (result, e),
+        other => panic!("expected exactly one rejected run, got {:?}", other),
+    };
+
+    assert!(succeeded.success);
+    assert!(
+        rejected.to_string().contains("already active"),
+        "{}",
+        rejected
+    );
+    assert!(rejected.downcast_ref::<droid::Rejected>().is_some());
+}
@@ -0,0 +1,74 @@
+//! A per-call `droid_bin` runs only when `allow_client_binary_override` is set.
+#![cfg(unix)]
+
+mod common;
+
+use common::{fake_droid, with_config, TestConfig};
+use droid_mcp_rs::droid::{self, Options};
+use std::path::PathBuf;
+
+/// Config allowing the override, with DROID_BIN pointing at a binary that does not exist
+fn allowing() -> TestConfig {
+    let config = with_config(serde_json::json!({ "allow_client_binary_override": true }));
+    std::env::set_var("DROID_BIN", config.dir().join("no-such-droid"));
+    config
+}
+
+#[tokio::test]
+async fn override_replaces_resolved_binary() {
+    let config = allowing();
+    let result = droid::run(Options {
+        prompt: Some("say hello".to_string()),
+        working_dir: config.dir().to_path_buf(),
+        auto: Some("low".to_string()),
+        droid_bin: Some(fake_droid()),
+        ..Default::default()
+    })
+    .await
+    .expect("droid::run failed");
+
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert_eq!(result.session_id, "fake-session-ok");
+}
+
+#[test]
+fn relative_override_is_rejected() {
+    let _config = allowing();
+    let error = droid::check_binary_override_allowed(&PathBuf::from("bin/droid"))
+        .expect_err("expected a rejection");
+
+    assert!(error.contains("absolute path"), "{}", error);
+}
+
+#[test]
+fn override_is_rejected_by_default() {
+    let _config = with_config(serde_json::json!({}));
+    let error =
+        droid::check_binary_override_allowed(&fake_droid()).expect_err("expected a rejection");
+
+    assert!(
+        error.contains("allow_client_binary_override is false"),
+        "{}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn run_refuses_override_by_default() {
+    let config = with_config(serde_json::json!({}));
+    let error = droid::run(Options {
+        prompt: Some("say hello".to_string()),
+        working_dir: config.dir().to_path_buf(),
+        auto: Some("low".to_string()),
+        droid_bin: Some(fake_droid()),
+        ..Default::default()
+    })
+    .await
+    .expect_err("run must not start droid");
+
+    assert!(
+        error.to_string().contains("droid_bin is disabled"),
+        "{}",
+        error
+    );
+}
@@ -0,0 +1,70 @@
+//! Probing well-known install locations when droid is neither in DROID_BIN nor on PATH.
+#![cfg(unix)]
+
+mod common;
+
+use droid_mcp_rs::droid::{self, DroidResult, ErrorCode, Options};
+use serde_json::json;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+/// Install a droid at `home/relative` that reports `session_id`
+fn install_droid(home: &Path, relative: &str, session_id: &str) {
+    let path = home.join(relative);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let script = format!(
+        "#!/bin/sh\necho '{{\"type\":\"system\",\"session_id\":\"{}\"}}'\necho '{{\"type\":\"completion\",\"finalText\":\"ok\"}}'\n",
+        session_id
+    );
+    std::fs::write(&path, script).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+/// Run with `home` as HOME, no DROID_BIN and a PATH without droid
+async fn run_with_home(home: &Path) -> DroidResult {
+    let empty = home.join("empty-path");
+    std::fs::create_dir_all(&empty).unwrap();
+    std::env::remove_var("DROID_BIN");
+    std::env::set_var("HOME", home);
+    std::env::set_var("PATH", &empty);
+
+    let opts = Options {
+        prompt: Some("say hello".to_string()),
+        working_dir: home.to_path_buf(),
+        auto: Some("low".to_string()),
+        ..Default::default()
+    };
+    droid::run(opts).await.expect("droid::run failed")
+}
+
+#[tokio::test]
+async fn droid_in_local_bin_is_found() {
+    let config = common::with_config(json!({}));
+    install_droid(config.dir(), ".local/bin/droid", "probed-local-bin");
+
+    let result = run_with_home(config.dir()).await;
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert_eq!(result.session_id, "probed-local-bin");
+}
+
+#[tokio::test]
+async fn home_bin_is_probed_first() {
+    let config = common::with_config(json!({}));
+    install_droid(config.dir(), "bin/droid", "probed-home-bin");
+    install_droid(config.dir(), ".local/bin/droid", "probed-local-bin");
+
+    let result = run_with_home(config.dir()).await;
+    assert_eq!(result.session_id, "probed-home-bin");
+}
+
+#[tokio::test]
+async fn non_executable_candidates_are_skipped() {
+    let config = common::with_config(json!({}));
+    install_droid(config.dir(), "bin/droid", "probed-home-bin");
+    let home_bin = config.dir().join("bin/droid");
+    std::fs::set_permissions(&home_bin, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+    let result = run_with_home(config.dir()).await;
+    assert!(!result.success);
+    assert_eq!(result.error_code, Some(ErrorCode::DroidNotFound));
+}
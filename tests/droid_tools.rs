@@ -0,0 +1,27 @@
+//! `droid::list_droid_tools` against the fake droid.
+#![cfg(unix)]
+
+mod common;
+
+use droid_mcp_rs::droid;
+
+#[tokio::test]
+async fn lists_tools_and_reports_unsupported_versions() {
+    common::use_fake_droid();
+
+    std::env::set_var("FAKE_DROID_NO_LIST_TOOLS", "1");
+    let err = droid::list_droid_tools()
+        .await
+        .expect_err("listing should fail");
+    assert!(err.contains("does not support listing tools"), "{}", err);
+    assert!(err.contains("unknown option"), "{}", err);
+
+    std::env::remove_var("FAKE_DROID_NO_LIST_TOOLS");
+    // The usage and option lines around the tools section are not tools
+    let tools = droid::list_droid_tools().await.expect("listing failed");
+    assert_eq!(tools, ["Read", "Edit", "Execute"]);
+
+    // A successful listing is cached
+    std::env::set_var("FAKE_DROID_NO_LIST_TOOLS", "1");
+    assert_eq!(droid::list_droid_tools().await.unwrap(), tools);
+}
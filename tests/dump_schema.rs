@@ -0,0 +1,46 @@
+//! `--dump-schema` prints the droid tool input schema advertised over MCP.
+
+use droid_mcp_rs::server::DroidServer;
+use std::process::Command;
+
+#[test]
+fn dump_schema_prints_droid_args_schema() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    // Same (default) config here and in the binary, so both advertise the same levels
+    std::env::set_var("DROID_MCP_CONFIG_PATH", dir.path().join("missing.json"));
+    let output = Command::new(env!("CARGO_BIN_EXE_droid-mcp-rs"))
+        .arg("--dump-schema")
+        .output()
+        .expect("failed to run server binary");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success(), "{}", stdout);
+    let schema: serde_json::Value = serde_json::from_str(&stdout).expect("schema is not JSON");
+    let properties = schema["properties"]
+        .as_object()
+        .unwrap_or_else(|| panic!("no properties: {}", schema));
+    for name in [
+        "PROMPT",
+        "SESSION_ID",
+        "file",
+        "auto",
+        "cwd",
+        "model",
+        "max_turns",
+    ] {
+        assert!(
+            properties.contains_key(name),
+            "missing {}: {}",
+            name,
+            schema
+        );
+    }
+    // The serde renames replace the field names
+    assert!(!properties.contains_key("prompt"), "{}", schema);
+    assert!(!properties.contains_key("session_id"), "{}", schema);
+
+    assert_eq!(
+        schema["properties"],
+        DroidServer::new().droid_input_schema().unwrap()["properties"]
+    );
+}
@@ -0,0 +1,62 @@
+//! `exit_grace_secs`: droid lingering after closing stdout is killed, not waited on.
+#![cfg(unix)]
+
+mod common;
+
+use droid_mcp_rs::droid::{self, Options};
+
+#[tokio::test]
+async fn lingering_droid_is_killed_after_grace_window() {
+    let config = common::with_config(serde_json::json!({ "exit_grace_secs": 1 }));
+
+    let started = std::time::Instant::now();
+    let result = droid::run(Options {
+        prompt: Some("scenario:linger".to_string()),
+        working_dir: config.dir().to_path_buf(),
+        auto: Some("low".to_string()),
+        ..Default::default()
+    })
+    .await
+    .expect("droid::run failed");
+
+    // The fake sleeps for 30 seconds after closing stdout
+    assert!(started.elapsed() < std::time::Duration::from_secs(15));
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert_eq!(result.agent_messages, "finished early");
+    assert!(
+        result
+            .warnings
+            .iter()
+            .any(|w| w.contains("did not exit within 1 seconds")),
+        "{:?}",
+        result.warnings
+    );
+}
+
+#[tokio::test]
+async fn stderr_held_open_by_a_leftover_process_is_not_waited_on() {
+    let config = common::with_config(serde_json::json!({}));
+
+    let started = std::time::Instant::now();
+    let result = droid::run(Options {
+        prompt: Some("scenario:stderr-orphan".to_string()),
+        working_dir: config.dir().to_path_buf(),
+        auto: Some("low".to_string()),
+        ..Default::default()
+    })
+    .await
+    .expect("droid::run failed");
+
+    // The leftover process keeps stderr open for 30 seconds
+    assert!(started.elapsed() < std::time::Duration::from_secs(15));
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert_eq!(result.agent_messages, "finished early");
+    assert!(
+        result
+            .warnings
+            .iter()
+            .any(|w| w.contains("stderr stayed open")),
+        "{:?}",
+        result.warnings
+    );
+}
@@ -0,0 +1,100 @@
+//! Client `extra_args`: merge order with the configured args and flag policy, driven over
+//! MCP stdio.
+#![cfg(unix)]
+
+mod common;
+
+use common::{tool_text, McpSession};
+use serde_json::json;
+
+fn call(server: &mut McpSession, extra_args: &[&str]) -> serde_json::Value {
+    server.call(
+        "droid",
+        json!({
+            "PROMPT": "scenario:echo-args",
+            "auto": "low",
+            "output_mode": "text",
+            "extra_args": extra_args
+        }),
+    )
+}
+
+fn error_message(response: &serde_json::Value) -> &str {
+    // invalid_params
+    assert_eq!(response["error"]["code"], -32602, "{}", response);
+    response["error"]["message"].as_str().unwrap()
+}
+
+#[test]
+fn extra_args_follow_configured_args() {
+    let mut server = McpSession::start(json!({
+        "additional_args": ["--config-flag"],
+        "allow_any_extra_args": true
+    }));
+
+    let response = call(&mut server, &["--client-flag", " 1 ", ""]);
+    let args = tool_text(&response);
+    assert!(
+        args.ends_with(" --config-flag --client-flag 1 scenario:echo-args"),
+        "{}",
+        args
+    );
+}
+
+#[test]
+fn reserved_flags_are_rejected() {
+    // Even when any other flag is allowed
+    let mut server = McpSession::start(json!({ "allow_any_extra_args": true }));
+
+    for flag in ["--model", "--model=gpt-5", "--auto", "-s", "--cwd=/"] {
+        let response = call(&mut server, &[flag]);
+        let message = error_message(&response);
+        assert!(message.contains("managed by the server"), "{}", message);
+    }
+}
+
+#[test]
+fn extra_args_are_denied_by_default() {
+    let mut server = McpSession::start(json!({}));
+
+    let response = call(&mut server, &["--some-flag"]);
+    let message = error_message(&response);
+    assert!(message.contains("extra_args are disabled"), "{}", message);
+}
+
+#[test]
+fn allowlist_admits_only_listed_flags() {
+    let mut server = McpSession::start(json!({ "allowed_extra_args": ["--log-level"] }));
+
+    // Values and `--flag=value` forms of listed flags pass
+    let response = call(&mut server, &["--log-level", "debug", "--log-level=trace"]);
+    let args = tool_text(&response);
+    assert!(
+        args.contains(" --log-level debug --log-level=trace "),
+        "{}",
+        args
+    );
+
+    let response = call(&mut server, &["--log-level", "debug", "--other-flag"]);
+    let message = error_message(&response);
+    assert!(
+        message.contains("extra_args flag '--other-flag' is not allowed"),
+        "{}",
+        message
+    );
+    assert!(
+        message.contains("Allowed flags: --log-level"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn permissive_mode_admits_any_unreserved_flag() {
+    let mut server = McpSession::start(json!({ "allow_any_extra_args": true }));
+
+    let response = call(&mut server, &["--anything", "--goes=here"]);
+    assert_ne!(response["result"]["isError"], true, "{}", response);
+    let args = tool_text(&response);
+    assert!(args.contains(" --anything --goes=here "), "{}", args);
+}
@@ -0,0 +1,60 @@
+//! A malformed `~/.factory/config.json` is reported on the next run instead of degrading silently.
+#![cfg(unix)]
+
+mod common;
+
+use droid_mcp_rs::droid::{self, Options};
+
+#[tokio::test]
+async fn malformed_factory_config_warns_on_first_run_only() {
+    let home = tempfile::tempdir().expect("failed to create temp dir");
+    std::fs::create_dir(home.path().join(".factory")).unwrap();
+    let factory_path = home.path().join(".factory").join("config.json");
+    std::fs::write(&factory_path, "{ not json").unwrap();
+    std::env::set_var("HOME", home.path());
+    std::env::set_var("DROID_MCP_CONFIG_PATH", home.path().join("missing.json"));
+    common::use_fake_droid();
+
+    let run = || async {
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        let result = droid::run(Options {
+            prompt: Some("say hello".to_string()),
+            working_dir: dir.path().to_path_buf(),
+            auto: Some("low".to_string()),
+            ..Default::default()
+        })
+        .await
+        .expect("droid::run failed");
+        assert!(result.success, "unexpected error: {:?}", result.error);
+        result.warnings
+    };
+
+    // A rejected run does not consume the warning
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    droid::run(Options {
+        prompt: Some("   ".to_string()),
+        working_dir: dir.path().to_path_buf(),
+        auto: Some("low".to_string()),
+        ..Default::default()
+    })
+    .await
+    .expect_err("empty prompt should be rejected");
+
+    let first = run().await;
+    let warning = first
+        .iter()
+        .find(|w| w.contains("failed to parse Factory config"))
+        .unwrap_or_else(|| panic!("no Factory config warning: {:?}", first));
+    assert!(
+        warning.contains(&factory_path.display().to_string()),
+        "{}",
+        warning
+    );
+
+    let second = run().await;
+    assert!(
+        !second.iter().any(|w| w.contains("Factory config")),
+        "{:?}",
+        second
+    );
+}
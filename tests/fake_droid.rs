@@ -0,0 +1,574 @@
+//! End-to-end tests of `droid::run` against a scripted fake droid binary.
+#![cfg(unix)]
+
+mod common;
+
+use common::use_fake_droid;
+use droid_mcp_rs::droid::{self, Completion, DroidResult, LineStats, Options};
+
+/// Run droid in a fresh temp dir; the prompt selects the fake's scenario
+async fn run_fake(prompt: &str) -> DroidResult {
+    use_fake_droid();
+    let working_dir = tempfile::tempdir().expect("failed to create temp dir");
+    let opts = Options {
+        prompt: Some(prompt.to_string()),
+        working_dir: working_dir.path().to_path_buf(),
+        ..Default::default()
+    };
+    droid::run(opts).await.expect("droid::run failed")
+}
+
+#[tokio::test]
+async fn success_collects_session_and_messages() {
+    let result = run_fake("say hello").await;
+
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert_eq!(result.session_id, "fake-session-ok");
+    assert_eq!(result.agent_messages, "working on it\ndone");
+    assert_eq!(result.all_messages.len(), 3);
+    assert!(result.error.is_none());
+}
+
+#[tokio::test]
+async fn error_line_marks_run_failed() {
+    let result = run_fake("scenario:error").await;
+
+    assert!(!result.success);
+    assert_eq!(result.session_id, "fake-session-error");
+    assert_eq!(
+        result.error.as_deref(),
+        Some("droid error: simulated failure")
+    );
+}
+
+#[tokio::test]
+async fn structured_error_line_keeps_code_and_details() {
+    let result = run_fake("scenario:structured-error").await;
+
+    assert!(!result.success);
+    assert_eq!(result.error.as_deref(), Some("droid error: slow down"));
+    assert_eq!(result.error_code, Some(droid::ErrorCode::RateLimited));
+    assert!(result.rate_limited);
+    let error = result.droid_error.expect("expected a structured error");
+    assert_eq!(error.code.as_deref(), Some("rate_limit_exceeded"));
+    assert_eq!(error.message, "slow down");
+    assert_eq!(
+        error.details,
+        Some(serde_json::json!({ "retry_after": 30 }))
+    );
+}
+
+#[tokio::test]
+async fn non_zero_exit_reports_code_and_stderr() {
+    let result = run_fake("scenario:exit").await;
+
+    assert!(!result.success);
+    assert_eq!(
+        result.error.as_deref(),
+        Some("droid exited with code Some(3)")
+    );
+    assert_eq!(result.stderr.as_deref(), Some("fatal: simulated crash"));
+}
+
+#[tokio::test]
+async fn killed_by_signal_is_reported_apart_from_exit_codes() {
+    let result = run_fake("scenario:signal").await;
+
+    assert!(!result.success);
+    assert_eq!(result.session_id, "fake-session-signal");
+    assert_eq!(result.error_code, Some(droid::ErrorCode::KilledBySignal));
+    assert_eq!(
+        result.error.as_deref(),
+        Some("droid was killed by signal 9 (SIGKILL)")
+    );
+}
+
+#[tokio::test]
+async fn stderr_is_omitted_when_not_requested() {
+    use_fake_droid();
+    let working_dir = tempfile::tempdir().expect("failed to create temp dir");
+    let result = droid::run(Options {
+        prompt: Some("scenario:exit".to_string()),
+        working_dir: working_dir.path().to_path_buf(),
+        include_stderr_on_error: Some(false),
+        ..Default::default()
+    })
+    .await
+    .expect("droid::run failed");
+
+    assert!(!result.success);
+    assert!(result.stderr.is_none());
+    assert!(!result.error.unwrap().contains("simulated crash"));
+}
+
+#[tokio::test]
+async fn successful_run_has_no_stderr_field() {
+    let result = run_fake("say hello").await;
+
+    assert!(result.success);
+    assert!(result.stderr.is_none());
+}
+
+#[tokio::test]
+async fn nested_session_id_is_extracted() {
+    let result = run_fake("scenario:nested-session").await;
+
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert_eq!(result.session_id, "fake-session-nested");
+    assert_eq!(result.agent_messages, "nested ok");
+}
+
+#[tokio::test]
+async fn warnings_are_collected_as_separate_entries() {
+    use_fake_droid();
+    let working_dir = tempfile::tempdir().expect("failed to create temp dir");
+    // A DROID.md over the absolute size limit (skipped, so the prompt stays small) and an
+    // over-limit timeout each produce their own warning
+    std::fs::File::create(working_dir.path().join("DROID.md"))
+        .and_then(|f| f.set_len(11 * 1024 * 1024))
+        .expect("failed to create DROID.md");
+    let opts = Options {
+        prompt: Some("say hello".to_string()),
+        working_dir: working_dir.path().to_path_buf(),
+        timeout_secs: Some(100_000),
+        auto: Some("low".to_string()),
+        ..Default::default()
+    };
+    let result = droid::run(opts).await.expect("droid::run failed");
+
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert_eq!(result.warnings.len(), 2, "{:?}", result.warnings);
+    assert!(
+        result.warnings[0].contains("DROID.md"),
+        "{:?}",
+        result.warnings
+    );
+    assert!(
+        result.warnings[1].contains("Requested timeout of 100000 seconds"),
+        "{:?}",
+        result.warnings
+    );
+    assert_eq!(
+        result.joined_warnings(),
+        Some(format!("{}\n{}", result.warnings[0], result.warnings[1]))
+    );
+}
+
+#[tokio::test]
+async fn line_stats_count_each_line_type() {
+    let result = run_fake("scenario:mixed").await;
+
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert_eq!(
+        result.line_stats,
+        LineStats {
+            system: 1,
+            message: 2,
+            completion: 1,
+            error: 0,
+            warning: 1,
+            spec: 0,
+            summary: 0,
+            unknown: 1,
+            parse_failures: 1,
+            oversized: 0,
+        }
+    );
+}
+
+#[tokio::test]
+async fn multiple_completions_are_kept_distinct() {
+    let result = run_fake("scenario:multi-completion").await;
+
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert_eq!(result.agent_messages, "first part\nsecond part");
+    assert_eq!(
+        result.completions,
+        vec![
+            Completion {
+                text: "first part".to_string(),
+                step: Some("1".to_string()),
+            },
+            Completion {
+                text: "second part".to_string(),
+                step: Some("verify".to_string()),
+            },
+        ]
+    );
+}
+
+#[tokio::test]
+async fn effective_auto_reports_default_when_omitted() {
+    let result = run_fake("say hello").await;
+
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    // Without a config file the server default level is high
+    assert_eq!(result.effective_auto.as_deref(), Some("high"));
+}
+
+#[tokio::test]
+async fn default_high_autonomy_is_warned() {
+    let result = run_fake("say hello").await;
+
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert!(
+        result.warnings.iter().any(|w| w.contains("HIGH autonomy")),
+        "{:?}",
+        result.warnings
+    );
+}
+
+#[tokio::test]
+async fn explicit_high_autonomy_is_not_warned() {
+    use_fake_droid();
+    let working_dir = tempfile::tempdir().expect("failed to create temp dir");
+    let opts = Options {
+        prompt: Some("say hello".to_string()),
+        working_dir: working_dir.path().to_path_buf(),
+        auto: Some("high".to_string()),
+        ..Default::default()
+    };
+    let result = droid::run(opts).await.expect("droid::run failed");
+
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert!(
+        !result.warnings.iter().any(|w| w.contains("HIGH autonomy")),
+        "{:?}",
+        result.warnings
+    );
+}
+
+#[tokio::test]
+async fn assembled_prompt_matches_what_run_sends() {
+    use_fake_droid();
+    let working_dir = tempfile::tempdir().expect("failed to create temp dir");
+    std::fs::write(working_dir.path().join("DROID.md"), "Use tabs.").unwrap();
+    let opts = Options {
+        prompt: Some("scenario:echo-len".to_string()),
+        working_dir: working_dir.path().to_path_buf(),
+        auto: Some("low".to_string()),
+        ..Default::default()
+    };
+
+    let (preview, warnings) = droid::assemble_prompt(&opts).await;
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    assert_eq!(
+        preview,
+        "<system_prompt>\nUse tabs.\n</system_prompt>\n\nscenario:echo-len"
+    );
+
+    // The fake droid echoes the length of the prompt it was given
+    let result = droid::run(opts.clone()).await.expect("droid::run failed");
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert_eq!(result.agent_messages, preview.len().to_string());
+
+    let (without_droid_md, _) = droid::assemble_prompt(&Options {
+        use_droid_md: Some(false),
+        ..opts
+    })
+    .await;
+    assert_eq!(without_droid_md, "scenario:echo-len");
+}
+
+#[tokio::test]
+async fn output_file_captures_raw_stream() {
+    use_fake_droid();
+    let working_dir = tempfile::tempdir().expect("failed to create temp dir");
+    let output_file = working_dir.path().join("stream.jsonl");
+    let opts = Options {
+        prompt: Some("say hello".to_string()),
+        working_dir: working_dir.path().to_path_buf(),
+        auto: Some("low".to_string()),
+        output_file: Some(output_file.clone()),
+        ..Default::default()
+    };
+    let result = droid::run(opts).await.expect("droid::run failed");
+    assert!(result.success, "unexpected error: {:?}", result.error);
+
+    let captured = std::fs::read_to_string(&output_file).expect("output_file not written");
+    assert_eq!(
+        captured,
+        concat!(
+            "{\"type\":\"system\",\"session_id\":\"fake-session-ok\"}\n",
+            "not json, should be skipped\n",
+            "{\"type\":\"message\",\"role\":\"assistant\",\"text\":\"working on it\"}\n",
+            "{\"type\":\"completion\",\"finalText\":\"done\"}\n",
+        )
+    );
+}
+
+#[tokio::test]
+async fn summary_line_failure_overrides_clean_exit() {
+    let result = run_fake("scenario:summary-fail").await;
+
+    assert!(!result.success);
+    assert_eq!(result.reported_success, Some(false));
+    assert_eq!(result.summary.as_deref(), Some("2 tests failed"));
+    assert_eq!(
+        result.error.as_deref(),
+        Some("droid reported failure: 2 tests failed")
+    );
+    assert_eq!(result.usage.unwrap()["output_tokens"], 5);
+    assert_eq!(result.duration_ms, Some(1234));
+}
+
+#[tokio::test]
+async fn completion_success_flag_overrides_clean_exit() {
+    let result = run_fake("scenario:completion-fail").await;
+
+    assert!(!result.success);
+    assert_eq!(result.reported_success, Some(false));
+    assert_eq!(result.agent_messages, "could not apply the patch");
+    assert_eq!(result.error.as_deref(), Some("droid reported failure"));
+    assert_eq!(result.error_code, Some(droid::ErrorCode::DroidError));
+}
+
+#[tokio::test]
+async fn summary_line_success_overrides_exit_code() {
+    let result = run_fake("scenario:summary-ok-exit").await;
+
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert_eq!(result.reported_success, Some(true));
+    assert!(result.error.is_none());
+}
+
+#[tokio::test]
+async fn ansi_codes_are_stripped_from_messages_and_stderr() {
+    let result = run_fake("scenario:ansi").await;
+
+    assert_eq!(result.agent_messages, "red text");
+    let stderr = result.stderr.expect("expected stderr");
+    assert!(stderr.contains("warning: colored stderr"), "{}", stderr);
+    assert!(!stderr.contains('\u{1b}'), "{:?}", stderr);
+}
+
+#[tokio::test]
+async fn require_droid_md_fails_when_missing() {
+    use_fake_droid();
+    let working_dir = tempfile::tempdir().expect("failed to create temp dir");
+    let opts = Options {
+        prompt: Some("say hello".to_string()),
+        working_dir: working_dir.path().to_path_buf(),
+        auto: Some("low".to_string()),
+        require_droid_md: Some(true),
+        ..Default::default()
+    };
+
+    let err = droid::run(opts).await.expect_err("run should fail");
+    assert!(err.to_string().contains("no DROID.md"), "{}", err);
+    assert!(err.downcast_ref::<droid::Rejected>().is_some(), "{:#}", err);
+}
+
+#[tokio::test]
+async fn require_droid_md_runs_when_present() {
+    use_fake_droid();
+    let working_dir = tempfile::tempdir().expect("failed to create temp dir");
+    std::fs::write(working_dir.path().join("DROID.md"), "Use tabs.").unwrap();
+    let opts = Options {
+        prompt: Some("say hello".to_string()),
+        working_dir: working_dir.path().to_path_buf(),
+        auto: Some("low".to_string()),
+        require_droid_md: Some(true),
+        ..Default::default()
+    };
+
+    let result = droid::run(opts).await.expect("droid::run failed");
+    assert!(result.success, "unexpected error: {:?}", result.error);
+}
+
+#[tokio::test]
+async fn trickled_output_is_read_across_stalls() {
+    let started = std::time::Instant::now();
+    let result = run_fake("scenario:trickle").await;
+
+    // The fake stalls mid-line while alive; the read must wait it out, not give up or spin
+    assert!(started.elapsed() >= std::time::Duration::from_millis(1000));
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert_eq!(result.session_id, "fake-session-trickle");
+    assert_eq!(result.agent_messages, "slow but steady");
+    assert_eq!(result.line_stats.parse_failures, 0);
+}
+
+#[tokio::test]
+async fn droid_runs_with_color_disabled_by_default() {
+    let result = run_fake("scenario:color-env").await;
+
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert_eq!(result.agent_messages, "NO_COLOR=1 FORCE_COLOR=0");
+}
+
+/// Run git in `dir`, panicking on failure
+fn git(dir: &std::path::Path, args: &[&str]) {
+    let status = std::process::Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .status()
+        .expect("failed to run git");
+    assert!(status.success(), "git {:?} failed", args);
+}
+
+#[tokio::test]
+async fn return_diff_reports_edits_and_untracked_files() {
+    use_fake_droid();
+    let working_dir = tempfile::tempdir().expect("failed to create temp dir");
+    std::fs::write(working_dir.path().join("tracked.txt"), "original\n").unwrap();
+    git(working_dir.path(), &["init", "-q"]);
+    git(working_dir.path(), &["add", "tracked.txt"]);
+    git(working_dir.path(), &["commit", "-q", "-m", "init"]);
+
+    let opts = Options {
+        prompt: Some("scenario:edit-file".to_string()),
+        working_dir: working_dir.path().to_path_buf(),
+        return_diff: true,
+        ..Default::default()
+    };
+    let result = droid::run(opts).await.expect("droid::run failed");
+
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    let diff = result.diff.expect("expected a diff");
+    assert!(diff.contains("+changed by droid"), "{}", diff);
+    assert!(diff.contains("Untracked files:\nnew.txt"), "{}", diff);
+}
+
+#[tokio::test]
+async fn return_diff_outside_git_repo_warns() {
+    use_fake_droid();
+    let working_dir = tempfile::tempdir().expect("failed to create temp dir");
+    std::fs::write(working_dir.path().join("tracked.txt"), "original\n").unwrap();
+    let opts = Options {
+        prompt: Some("scenario:edit-file".to_string()),
+        working_dir: working_dir.path().to_path_buf(),
+        return_diff: true,
+        ..Default::default()
+    };
+    let result = droid::run(opts).await.expect("droid::run failed");
+
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert!(result.diff.is_none());
+    assert!(
+        result
+            .warnings
+            .iter()
+            .any(|w| w.contains("not inside a git repository")),
+        "{:?}",
+        result.warnings
+    );
+}
+
+#[tokio::test]
+async fn touch_session_reports_live_session_active() {
+    use_fake_droid();
+    let working_dir = tempfile::tempdir().expect("failed to create temp dir");
+    let touch = droid::touch_session("live-session-1", working_dir.path().to_path_buf())
+        .await
+        .expect("touch_session failed");
+
+    assert_eq!(touch.status, droid::SessionStatus::Active);
+    assert_eq!(touch.error, None);
+}
+
+#[tokio::test]
+async fn touch_session_reports_expired_session() {
+    use_fake_droid();
+    let working_dir = tempfile::tempdir().expect("failed to create temp dir");
+    let touch = droid::touch_session("expired-session-1", working_dir.path().to_path_buf())
+        .await
+        .expect("touch_session failed");
+
+    assert_eq!(touch.status, droid::SessionStatus::Expired);
+    let error = touch.error.expect("expected an error");
+    assert!(error.contains("not found or has expired"), "{}", error);
+}
+
+/// Run the fake's echo-args scenario with `opts` and return the argv droid received
+async fn echo_args(opts: Options) -> String {
+    use_fake_droid();
+    let working_dir = tempfile::tempdir().expect("failed to create temp dir");
+    let opts = Options {
+        prompt: Some("scenario:echo-args".to_string()),
+        working_dir: working_dir.path().to_path_buf(),
+        auto: Some("low".to_string()),
+        ..opts
+    };
+    let result = droid::run(opts).await.expect("droid::run failed");
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    result.agent_messages
+}
+
+#[tokio::test]
+async fn max_turns_is_passed_only_when_set() {
+    let args = echo_args(Options {
+        max_turns: Some(7),
+        ..Default::default()
+    })
+    .await;
+    assert!(args.contains(" --max-turns 7 "), "{}", args);
+
+    let args = echo_args(Options::default()).await;
+    assert!(!args.contains("--max-turns"), "{}", args);
+}
+
+#[tokio::test]
+async fn passthrough_flags_render_as_key_value_pairs() {
+    use_fake_droid();
+    let working_dir = tempfile::tempdir().expect("failed to create temp dir");
+    let opts = Options {
+        prompt: Some("scenario:echo-args".to_string()),
+        working_dir: working_dir.path().to_path_buf(),
+        auto: Some("low".to_string()),
+        passthrough_flags: vec![
+            ("new-flag".to_string(), "1".to_string()),
+            ("other-flag".to_string(), "two".to_string()),
+        ],
+        ..Default::default()
+    };
+    let result = droid::run(opts).await.expect("droid::run failed");
+
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert!(
+        result
+            .agent_messages
+            .ends_with("--new-flag 1 --other-flag two scenario:echo-args"),
+        "{}",
+        result.agent_messages
+    );
+}
+
+#[tokio::test]
+async fn agent_messages_are_normalized() {
+    let result = run_fake("scenario:spaced").await;
+
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert_eq!(result.agent_messages, "first\n\nsecond");
+}
+
+#[tokio::test]
+async fn scope_paths_without_scope_flag_warn() {
+    use_fake_droid();
+    let working_dir = tempfile::tempdir().expect("failed to create temp dir");
+    let opts = Options {
+        prompt: Some("scenario:echo-args".to_string()),
+        working_dir: working_dir.path().to_path_buf(),
+        auto: Some("low".to_string()),
+        scope_paths: vec!["src".to_string()],
+        ..Default::default()
+    };
+    let result = droid::run(opts).await.expect("droid::run failed");
+
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert!(
+        result
+            .warnings
+            .iter()
+            .any(|w| w.contains("scope_paths is not enforced")),
+        "{:?}",
+        result.warnings
+    );
+    assert!(
+        !result.agent_messages.contains(" src "),
+        "{}",
+        result.agent_messages
+    );
+}
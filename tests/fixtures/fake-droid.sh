@@ -0,0 +1,201 @@
+#!/bin/sh
+# Fake droid CLI for integration tests.
+#
+# Emits scripted stream-json lines chosen by the prompt, which droid-mcp-rs
+# passes as the last argument to `droid exec`. Point DROID_BIN at this script.
+
+all_args="$*"
+prev=""
+for arg in "$@"; do
+    if [ "$prev" = "--cwd" ]; then
+        cwd="$arg"
+    fi
+    if [ "$prev" = "-r" ]; then
+        effort="$arg"
+    fi
+    if [ "$prev" = "--session-id" ]; then
+        session="$arg"
+    fi
+    prev="$arg"
+    if [ "$arg" = "--list-tools" ]; then
+        if [ -n "$FAKE_DROID_NO_LIST_TOOLS" ]; then
+            echo "error: unknown option '--list-tools'" >&2
+            exit 1
+        fi
+        # Shaped like droid's help text: usage and options around the tools section
+        echo "Usage: droid exec [options] [prompt]"
+        echo ""
+        echo "Available tools:"
+        echo "  - Read: read files"
+        echo "  - Edit: edit files"
+        echo "  - Execute   run shell commands"
+        echo ""
+        echo "Options:"
+        echo "  --list-tools      List available tools"
+        echo "  -h, --help        Show help"
+        exit 0
+    fi
+    prompt="$arg"
+done
+
+# Resuming a session whose id starts with "expired-" fails like droid does
+case "$session" in
+    expired-*)
+        echo "Error: session $session not found or has expired" >&2
+        exit 1
+        ;;
+esac
+
+case "$prompt" in
+    *scenario:error*)
+        echo '{"type":"system","session_id":"fake-session-error"}'
+        echo '{"type":"error","message":"simulated failure"}'
+        ;;
+    *scenario:structured-error*)
+        echo '{"type":"system","session_id":"fake-session-structured-error"}'
+        echo '{"type":"error","error":{"code":"rate_limit_exceeded","message":"slow down","details":{"retry_after":30}}}'
+        ;;
+    *scenario:stderr-429*)
+        echo '{"type":"system","session_id":"fake-session-stderr-429"}'
+        echo 'error: unexpected token at src/main.rs:429 (429 tokens used)' >&2
+        exit 1
+        ;;
+    *scenario:stderr-http-429*)
+        echo '{"type":"system","session_id":"fake-session-stderr-http-429"}'
+        echo 'request failed: HTTP 429' >&2
+        exit 1
+        ;;
+    *scenario:exit*)
+        echo '{"type":"system","session_id":"fake-session-exit"}'
+        echo 'fatal: simulated crash' >&2
+        exit 3
+        ;;
+    *scenario:mixed*)
+        echo '{"type":"system","session_id":"fake-session-mixed"}'
+        echo '{"type":"warning","message":"heads up"}'
+        echo '{"type":"message","role":"user","text":"hi"}'
+        echo '{"type":"message","role":"assistant","text":"step one"}'
+        echo '{"type":"tool_call","name":"Read"}'
+        echo '{broken'
+        echo '{"type":"completion","finalText":"finished"}'
+        ;;
+    *scenario:multi-completion*)
+        echo '{"type":"system","session_id":"fake-session-multi"}'
+        echo '{"type":"completion","finalText":"first part","step":1}'
+        echo '{"type":"completion","finalText":"second part","step":"verify"}'
+        ;;
+    *scenario:echo-len*)
+        echo '{"type":"system","session_id":"fake-session-echo"}'
+        echo "{\"type\":\"completion\",\"finalText\":\"${#prompt}\"}"
+        ;;
+    *scenario:summary-fail*)
+        echo '{"type":"system","session_id":"fake-session-summary"}'
+        echo '{"type":"completion","finalText":"all done"}'
+        echo '{"type":"result","success":false,"summary":"2 tests failed","usage":{"input_tokens":10,"output_tokens":5},"duration_ms":1234}'
+        ;;
+    *scenario:completion-fail*)
+        echo '{"type":"system","session_id":"fake-session-completion-fail"}'
+        echo '{"type":"completion","finalText":"could not apply the patch","success":false}'
+        ;;
+    *scenario:summary-ok-exit*)
+        echo '{"type":"system","session_id":"fake-session-summary"}'
+        echo '{"type":"completion","finalText":"all done"}'
+        echo '{"type":"summary","status":"success"}'
+        exit 1
+        ;;
+    *scenario:ansi*)
+        echo '{"type":"system","session_id":"fake-session-ansi"}'
+        echo '{"type":"completion","finalText":"\u001b[1;31mred\u001b[0m text"}'
+        printf '\033[33mwarning:\033[0m colored stderr\n' >&2
+        exit 2
+        ;;
+    *scenario:trickle*)
+        # Flush a line a few bytes at a time, stalling mid-line while staying alive
+        echo '{"type":"system","session_id":"fake-session-trickle"}'
+        for chunk in '{"type":"compl' 'etion","final' 'Text":"slow ' 'but steady"}'; do
+            printf '%s' "$chunk"
+            sleep 0.3
+        done
+        echo
+        ;;
+    *scenario:color-env*)
+        echo '{"type":"system","session_id":"fake-session-color"}'
+        echo "{\"type\":\"completion\",\"finalText\":\"NO_COLOR=${NO_COLOR-unset} FORCE_COLOR=${FORCE_COLOR-unset}\"}"
+        ;;
+    *scenario:echo-args*)
+        echo '{"type":"system","session_id":"fake-session-args"}'
+        echo "{\"type\":\"completion\",\"finalText\":\"$all_args\"}"
+        ;;
+    *scenario:echo-effort*)
+        echo '{"type":"system","session_id":"fake-session-effort"}'
+        echo "{\"type\":\"completion\",\"finalText\":\"effort=${effort-unset}\"}"
+        ;;
+    *scenario:stderr-stream*)
+        # Report a diagnostic well before the run finishes
+        echo '{"type":"system","session_id":"fake-session-stderr-stream"}'
+        echo 'early diagnostic' >&2
+        sleep 2
+        echo '{"type":"completion","finalText":"finished late"}'
+        ;;
+    *scenario:silent*)
+        # A clean run that never produces assistant text
+        echo '{"type":"system","session_id":"fake-session-silent"}'
+        ;;
+    *scenario:stall*)
+        # Go quiet between lines long enough for several heartbeats
+        echo '{"type":"system","session_id":"fake-session-stall"}'
+        sleep 2.5
+        echo '{"type":"completion","finalText":"woke up"}'
+        ;;
+    *scenario:slow*)
+        echo '{"type":"system","session_id":"fake-session-slow"}'
+        sleep 1
+        echo '{"type":"completion","finalText":"slow done"}'
+        ;;
+    *scenario:hang*)
+        echo '{"type":"system","session_id":"fake-session-hang"}'
+        exec sleep 30
+        ;;
+    *scenario:spaced*)
+        echo '{"type":"system","session_id":"fake-session-spaced"}'
+        echo '{"type":"message","role":"assistant","text":"first\n\n\n\n"}'
+        echo '{"type":"completion","finalText":"  \nsecond  \n\n\n"}'
+        ;;
+    *scenario:signal*)
+        # Die the way an OOM-killed droid does
+        echo '{"type":"system","session_id":"fake-session-signal"}'
+        kill -KILL $$
+        ;;
+    *scenario:linger*)
+        # Close stdout after a complete stream, then keep running
+        echo '{"type":"system","session_id":"fake-session-linger"}'
+        echo '{"type":"completion","finalText":"finished early"}'
+        exec >&-
+        exec sleep 30
+        ;;
+    *scenario:stderr-orphan*)
+        # Exit after a complete stream, leaving a background process holding stderr open
+        echo '{"type":"system","session_id":"fake-session-orphan"}'
+        echo '{"type":"completion","finalText":"finished early"}'
+        exec >&-
+        sleep 30 &
+        exit 0
+        ;;
+    *scenario:edit-file*)
+        # Modify a tracked file and add an untracked one in the working directory
+        echo "changed by droid" >> "$cwd/tracked.txt"
+        echo "new" > "$cwd/new.txt"
+        echo '{"type":"system","session_id":"fake-session-edit"}'
+        echo '{"type":"completion","finalText":"edited"}'
+        ;;
+    *scenario:nested-session*)
+        echo '{"type":"system","meta":{"session_id":"fake-session-nested"}}'
+        echo '{"type":"completion","finalText":"nested ok"}'
+        ;;
+    *)
+        echo "{\"type\":\"system\",\"session_id\":\"${session:-fake-session-ok}\"}"
+        echo 'not json, should be skipped'
+        echo '{"type":"message","role":"assistant","text":"working on it"}'
+        echo '{"type":"completion","finalText":"done"}'
+        ;;
+esac
@@ -0,0 +1,53 @@
+//! The `force_no_color` config switch and its `--generate-config` / reload coverage.
+#![cfg(unix)]
+
+mod common;
+
+use droid_mcp_rs::droid::{self, Options};
+
+/// droid's view of its color environment, as reported by the fake
+async fn color_env(dir: &std::path::Path) -> String {
+    let result = droid::run(Options {
+        prompt: Some("scenario:color-env".to_string()),
+        working_dir: dir.to_path_buf(),
+        auto: Some("low".to_string()),
+        ..Default::default()
+    })
+    .await
+    .expect("droid::run failed");
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    result.agent_messages
+}
+
+#[tokio::test]
+async fn color_environment_is_untouched_when_disabled() {
+    let config = common::with_config(serde_json::json!({ "force_no_color": false }));
+    std::env::remove_var("NO_COLOR");
+    std::env::remove_var("FORCE_COLOR");
+
+    assert_eq!(
+        color_env(config.dir()).await,
+        "NO_COLOR=unset FORCE_COLOR=unset"
+    );
+}
+
+#[tokio::test]
+async fn reload_toggles_the_switch() {
+    let config = common::with_config(serde_json::json!({}));
+    std::env::remove_var("NO_COLOR");
+    std::env::remove_var("FORCE_COLOR");
+    assert_eq!(color_env(config.dir()).await, "NO_COLOR=1 FORCE_COLOR=0");
+
+    config.reload(serde_json::json!({ "force_no_color": false }));
+    assert_eq!(
+        color_env(config.dir()).await,
+        "NO_COLOR=unset FORCE_COLOR=unset"
+    );
+}
+
+#[test]
+fn generated_config_enables_the_switch() {
+    let generated: serde_json::Value = serde_json::from_str(&droid::default_config_json()).unwrap();
+
+    assert_eq!(generated["force_no_color"], true);
+}
@@ -0,0 +1,70 @@
+//! `force_readonly` collapses every autonomy request to DEFAULT (server config from a temp file).
+#![cfg(unix)]
+
+mod common;
+
+use droid_mcp_rs::droid::{self, Options};
+
+/// Run with a config setting force_readonly, against the fake droid
+async fn run_readonly(opts: Options) -> droid::DroidResult {
+    let config = common::with_config(serde_json::json!({
+        "force_readonly": true,
+        "max_auto": "low"
+    }));
+    let result = droid::run(Options {
+        prompt: Some("say hello".to_string()),
+        working_dir: config.dir().to_path_buf(),
+        ..opts
+    })
+    .await
+    .expect("droid::run failed");
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    result
+}
+
+fn assert_overridden(result: &droid::DroidResult, what: &str) {
+    assert_eq!(result.effective_auto, None);
+    assert!(
+        result
+            .warnings
+            .iter()
+            .any(|w| w.contains("force_readonly") && w.contains(what)),
+        "{:?}",
+        result.warnings
+    );
+}
+
+#[tokio::test]
+async fn every_auto_level_collapses_to_default() {
+    // max_auto would reject high; force_readonly takes precedence
+    for level in ["high", "medium", "low"] {
+        let result = run_readonly(Options {
+            auto: Some(level.to_string()),
+            ..Default::default()
+        })
+        .await;
+        assert_overridden(&result, &format!("auto '{}'", level));
+    }
+}
+
+#[tokio::test]
+async fn skip_permissions_collapses_to_default() {
+    let result = run_readonly(Options {
+        skip_permissions_unsafe: true,
+        ..Default::default()
+    })
+    .await;
+    assert_overridden(&result, "skip_permissions_unsafe");
+}
+
+#[tokio::test]
+async fn omitted_auto_runs_read_only_without_warning() {
+    let result = run_readonly(Options::default()).await;
+
+    assert_eq!(result.effective_auto, None);
+    assert!(
+        !result.warnings.iter().any(|w| w.contains("force_readonly")),
+        "{:?}",
+        result.warnings
+    );
+}
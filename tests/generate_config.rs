@@ -0,0 +1,41 @@
+//! `--generate-config` output round-trips through the server config loader.
+
+use droid_mcp_rs::droid;
+
+#[test]
+fn generated_config_parses_back_to_same_config() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let path = dir.path().join("droid-mcp.config.json");
+
+    droid::write_default_config(&path, false).expect("failed to write default config");
+    assert!(
+        droid::write_default_config(&path, false).is_err(),
+        "existing file must not be overwritten without force"
+    );
+    droid::write_default_config(&path, true).expect("force should overwrite");
+
+    std::env::set_var("DROID_MCP_CONFIG_PATH", &path);
+    droid::reload_config().expect("generated config failed to parse");
+
+    let written: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    let effective: serde_json::Value =
+        serde_json::from_str(&droid::effective_config_json()).unwrap();
+    assert_eq!(written, effective);
+    assert_eq!(
+        written,
+        serde_json::from_str::<serde_json::Value>(&droid::default_config_json()).unwrap()
+    );
+
+    // Omitted switches take the same defaults as the generated file (allow_high_autonomy
+    // included); only the numeric limits are left unset
+    std::fs::write(&path, "{}").unwrap();
+    droid::reload_config().expect("empty config failed to parse");
+    let effective: serde_json::Value =
+        serde_json::from_str(&droid::effective_config_json()).unwrap();
+    for (key, value) in written.as_object().unwrap() {
+        if value.is_boolean() {
+            assert_eq!(&effective[key], value, "{}", key);
+        }
+    }
+}
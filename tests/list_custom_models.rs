@@ -0,0 +1,29 @@
+//! `list_custom_models` ordering and labels for duplicate display names.
+
+mod common;
+
+use droid_mcp_rs::droid;
+use serde_json::json;
+
+#[test]
+fn duplicate_display_names_are_disambiguated_in_config_order() {
+    let config = common::with_config(json!({}));
+    config.use_home(json!({ "custom_models": [
+        { "model_display_name": "Sonnet", "model": "claude-sonnet", "provider": "anthropic" },
+        { "model_display_name": "GPT 5", "model": "gpt-5", "provider": "openai" },
+        { "model_display_name": "Sonnet", "model": "claude-sonnet-beta", "provider": "anthropic" }
+    ] }));
+
+    let entries: Vec<(String, String)> = droid::list_custom_models()
+        .into_iter()
+        .map(|entry| (entry.label, entry.reference))
+        .collect();
+    assert_eq!(
+        entries,
+        [
+            ("Sonnet #1".to_string(), "custom:Sonnet-0".to_string()),
+            ("GPT 5".to_string(), "custom:GPT-5-1".to_string()),
+            ("Sonnet #2".to_string(), "custom:Sonnet-2".to_string()),
+        ]
+    );
+}
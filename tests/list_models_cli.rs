@@ -0,0 +1,51 @@
+//! `--list-models` prints the Factory custom models with their references and the default.
+
+use std::process::Command;
+
+#[test]
+fn list_models_prints_references_and_default() {
+    let home = tempfile::tempdir().expect("failed to create temp dir");
+    std::fs::create_dir(home.path().join(".factory")).unwrap();
+    std::fs::write(
+        home.path().join(".factory").join("config.json"),
+        r#"{"custom_models": [
+            {"model_display_name": "Claude Sonnet", "model": "claude-sonnet"},
+            {"model_display_name": "GPT 5", "model": "gpt-5"}
+        ]}"#,
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_droid-mcp-rs"))
+        .arg("--list-models")
+        .env("HOME", home.path())
+        .env("USERPROFILE", home.path())
+        .env("DROID_MCP_CONFIG_PATH", home.path().join("missing.json"))
+        .output()
+        .expect("failed to run server binary");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success(), "{}", stdout);
+    assert_eq!(
+        stdout,
+        "Custom models from ~/.factory/config.json:\n\
+         \x20 Claude Sonnet: custom:Claude-Sonnet-0\n\
+         \x20 GPT 5: custom:GPT-5-1 (default)\n"
+    );
+}
+
+#[test]
+fn list_models_without_factory_config() {
+    let home = tempfile::tempdir().expect("failed to create temp dir");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_droid-mcp-rs"))
+        .arg("--list-models")
+        .env("HOME", home.path())
+        .env("USERPROFILE", home.path())
+        .env("DROID_MCP_CONFIG_PATH", home.path().join("missing.json"))
+        .output()
+        .expect("failed to run server binary");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.starts_with("No custom models"), "{}", stdout);
+}
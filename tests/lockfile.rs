@@ -0,0 +1,84 @@
+//! `.droid-mcp.lock` acquisition, stale-lock reclaim and conflicts.
+#![cfg(unix)]
+
+use droid_mcp_rs::lockfile::{self, AcquireError, LockInfo, LOCK_FILE};
+use std::process::Command;
+
+fn write_lock(dir: &std::path::Path, pid: u32) {
+    let info = LockInfo {
+        pid,
+        started_at: 1_700_000_000,
+    };
+    std::fs::write(dir.join(LOCK_FILE), serde_json::to_string(&info).unwrap()).unwrap();
+}
+
+#[test]
+fn acquire_writes_pid_and_removes_lock_on_drop() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let (guard, warning) = lockfile::acquire(dir.path()).expect("lock should be free");
+
+    assert!(warning.is_none());
+    assert_eq!(guard.path(), dir.path().join(LOCK_FILE));
+    let info: LockInfo =
+        serde_json::from_str(&std::fs::read_to_string(guard.path()).unwrap()).unwrap();
+    assert_eq!(info.pid, std::process::id());
+    assert!(info.started_at > 0);
+    // The temp file the lock was published from is gone
+    let names: Vec<_> = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(names, vec![std::ffi::OsString::from(LOCK_FILE)]);
+
+    drop(guard);
+    assert!(!dir.path().join(LOCK_FILE).exists());
+}
+
+#[test]
+fn lock_of_exited_process_is_reclaimed() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let mut exited = Command::new("true").spawn().unwrap();
+    exited.wait().unwrap();
+    write_lock(dir.path(), exited.id());
+
+    let (guard, warning) = lockfile::acquire(dir.path()).expect("stale lock should be reclaimed");
+
+    let warning = warning.expect("expected a reclaim warning");
+    assert!(warning.contains("stale lockfile"), "{}", warning);
+    let info: LockInfo =
+        serde_json::from_str(&std::fs::read_to_string(guard.path()).unwrap()).unwrap();
+    assert_eq!(info.pid, std::process::id());
+}
+
+#[test]
+fn lock_of_live_process_is_refused() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let mut live = Command::new("sleep").arg("30").spawn().unwrap();
+    write_lock(dir.path(), live.id());
+
+    let error = lockfile::acquire(dir.path()).expect_err("live lock must not be taken");
+    let _ = live.kill();
+    let _ = live.wait();
+
+    assert!(matches!(error, AcquireError::Held(_)), "{:?}", error);
+    assert!(
+        error.to_string().contains(&format!("pid {}", live.id())),
+        "{}",
+        error
+    );
+    // The other process's lock is left in place
+    assert!(dir.path().join(LOCK_FILE).exists());
+}
+
+#[test]
+fn lock_of_process_we_cannot_signal_is_refused() {
+    // init is always alive; unprivileged test runs get EPERM signalling it
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    write_lock(dir.path(), 1);
+
+    let error = lockfile::acquire(dir.path()).expect_err("live lock must not be taken");
+
+    assert!(matches!(error, AcquireError::Held(_)), "{:?}", error);
+    assert!(error.to_string().contains("pid 1,"), "{}", error);
+    assert!(dir.path().join(LOCK_FILE).exists());
+}
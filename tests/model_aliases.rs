@@ -0,0 +1,66 @@
+//! `model_aliases` expansion and dangling-alias detection.
+#![cfg(unix)]
+
+mod common;
+
+use droid_mcp_rs::droid::{self, Options};
+use serde_json::json;
+
+// One test so the HOME change doesn't race
+#[tokio::test]
+async fn aliases_expand_and_dangling_targets_are_reported() {
+    let config = common::with_config(json!({
+        "model_aliases": {
+            "fast": "custom:Fast-Model-0",
+            "gone": "custom:Removed-Model-7",
+            "plain": "gpt-5"
+        },
+        "denied_models": ["gpt-5"]
+    }));
+    config.use_home(json!({ "custom_models": [
+        { "model_display_name": "Fast Model", "model": "fast-1", "provider": "test" }
+    ] }));
+
+    assert_eq!(
+        droid::resolve_model(Some("fast".to_string())).as_deref(),
+        Some("custom:Fast-Model-0")
+    );
+    assert_eq!(
+        droid::resolve_model(Some("plain".to_string())).as_deref(),
+        Some("gpt-5")
+    );
+    assert_eq!(
+        droid::resolve_model(Some("not-an-alias".to_string())).as_deref(),
+        Some("not-an-alias")
+    );
+
+    let warnings = droid::model_alias_warnings();
+    assert_eq!(warnings.len(), 1, "{:?}", warnings);
+    assert!(warnings[0].contains("'gone'"), "{:?}", warnings);
+
+    let result = droid::run(Options {
+        prompt: Some("say hello".to_string()),
+        working_dir: config.dir().to_path_buf(),
+        model: Some("fast".to_string()),
+        auto: Some("low".to_string()),
+        ..Default::default()
+    })
+    .await
+    .expect("droid::run failed");
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    let model_info = result.model_info.expect("missing model info");
+    assert!(model_info.contains("Fast Model"), "{}", model_info);
+    assert_eq!(result.model.as_deref(), Some("custom:Fast-Model-0"));
+
+    // The deny list applies to the alias target, not the name the client sent
+    let err = droid::run(Options {
+        prompt: Some("say hello".to_string()),
+        working_dir: config.dir().to_path_buf(),
+        model: Some("plain".to_string()),
+        auto: Some("low".to_string()),
+        ..Default::default()
+    })
+    .await
+    .expect_err("denied alias target should be rejected");
+    assert!(err.to_string().contains("'gpt-5' is denied"), "{}", err);
+}
@@ -0,0 +1,71 @@
+//! `allowed_models` / `denied_models` checks, including the default model.
+#![cfg(unix)]
+
+mod common;
+
+use droid_mcp_rs::droid::{self, Options};
+use droid_mcp_rs::server;
+use rmcp::model::ErrorCode;
+use serde_json::json;
+
+fn options(dir: &std::path::Path, model: Option<&str>) -> Options {
+    Options {
+        prompt: Some("say hello".to_string()),
+        working_dir: dir.to_path_buf(),
+        model: model.map(str::to_string),
+        auto: Some("low".to_string()),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn allowed_model_runs() {
+    let config = common::with_config(json!({ "allowed_models": ["gpt-5"] }));
+
+    assert!(droid::check_model_allowed(Some("gpt-5")).is_ok());
+    let result = droid::run(options(config.dir(), Some("gpt-5")))
+        .await
+        .expect("allowed model should run");
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert_eq!(result.model.as_deref(), Some("gpt-5"));
+}
+
+#[tokio::test]
+async fn denied_model_is_rejected_as_invalid_params() {
+    let config = common::with_config(json!({ "denied_models": ["gpt-5"] }));
+
+    let err = droid::check_model_allowed(Some("gpt-5")).unwrap_err();
+    assert!(err.contains("'gpt-5' is denied"), "{}", err);
+    assert!(droid::check_model_allowed(Some("claude-opus")).is_ok());
+
+    let err = droid::run(options(config.dir(), Some("gpt-5")))
+        .await
+        .expect_err("denied model should be rejected");
+    assert!(err.downcast_ref::<droid::Rejected>().is_some(), "{:#}", err);
+    let err = server::run_error(err);
+    assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+    assert!(err.message.contains("'gpt-5' is denied"), "{}", err.message);
+}
+
+#[tokio::test]
+async fn default_model_must_be_allowed() {
+    let config = common::with_config(json!({ "allowed_models": ["gpt-5"] }));
+    // The GPT custom model becomes the default
+    config.use_home(json!({ "custom_models": [
+        { "model_display_name": "GPT Mini", "model": "gpt-mini", "provider": "test" }
+    ] }));
+
+    let default_model = droid::resolve_model(None).expect("expected a default model");
+    let err = droid::check_model_allowed(Some(&default_model)).unwrap_err();
+    assert!(err.contains("is not allowed"), "{}", err);
+
+    let err = droid::run(options(config.dir(), None))
+        .await
+        .expect_err("disallowed default model should be rejected");
+    assert_eq!(server::run_error(err).code, ErrorCode::INVALID_PARAMS);
+
+    // Without custom models droid picks its own default, which no allow list can name
+    config.use_home(json!({ "custom_models": [] }));
+    let err = droid::check_model_allowed(droid::resolve_model(None).as_deref()).unwrap_err();
+    assert!(err.contains("Factory default model"), "{}", err);
+}
@@ -0,0 +1,24 @@
+//! Blank-line and trailing-whitespace normalization of agent messages.
+
+use droid_mcp_rs::droid::normalize_message;
+
+#[test]
+fn collapses_runs_of_blank_lines() {
+    assert_eq!(normalize_message("a\n\n\n\nb\n\n\nc"), "a\n\nb\n\nc");
+}
+
+#[test]
+fn treats_whitespace_only_lines_as_blank() {
+    assert_eq!(normalize_message("a\n  \n\t\n\nb"), "a\n\nb");
+}
+
+#[test]
+fn trims_trailing_whitespace() {
+    assert_eq!(normalize_message("done \n\n \t"), "done");
+}
+
+#[test]
+fn keeps_single_blank_lines_and_indentation() {
+    let text = "list:\n\n  - one\n  - two";
+    assert_eq!(normalize_message(text), text);
+}
@@ -0,0 +1,71 @@
+//! `--once` runs droid a single time from the command line, without MCP.
+#![cfg(unix)]
+
+mod common;
+
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn run_once(prompt: &str) -> Output {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    Command::new(env!("CARGO_BIN_EXE_droid-mcp-rs"))
+        .args(["--once", "--auto", "low", "--prompt", prompt, "--cwd"])
+        .arg(dir.path())
+        .env("DROID_BIN", common::fake_droid())
+        .env("DROID_MCP_CONFIG_PATH", dir.path().join("missing.json"))
+        .output()
+        .expect("failed to run server binary")
+}
+
+#[test]
+fn successful_run_prints_result_and_exits_zero() {
+    let output = run_once("say hello");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("success: true"), "{}", stdout);
+    assert!(stdout.contains("fake-session-ok"), "{}", stdout);
+    assert!(stdout.contains("effective_auto: low"), "{}", stdout);
+}
+
+#[test]
+fn failed_run_exits_non_zero() {
+    let output = run_once("scenario:error");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert_eq!(output.status.code(), Some(1), "{}", stdout);
+    assert!(stdout.contains("success: false"), "{}", stdout);
+}
+
+#[test]
+fn prompt_requires_once() {
+    let output = Command::new(env!("CARGO_BIN_EXE_droid-mcp-rs"))
+        .args(["--prompt", "say hello"])
+        .output()
+        .expect("failed to run server binary");
+    assert!(!output.status.success());
+}
+
+#[test]
+fn output_reports_canonical_cwd_for_relative_input() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    std::fs::create_dir(dir.path().join("repo")).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_droid-mcp-rs"))
+        .args(["--once", "--auto", "low", "--prompt", "say hello"])
+        .args(["--cwd", "./repo/../repo"])
+        .current_dir(dir.path())
+        .env("DROID_BIN", common::fake_droid())
+        .env("DROID_MCP_CONFIG_PATH", dir.path().join("missing.json"))
+        .output()
+        .expect("failed to run server binary");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+
+    let cwd = stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("cwd: "))
+        .unwrap_or_else(|| panic!("no cwd in output: {}", stdout))
+        .trim_matches('"');
+    let expected = dir.path().join("repo").canonicalize().unwrap();
+    assert_eq!(PathBuf::from(cwd), expected);
+}
@@ -0,0 +1,72 @@
+//! `response_format` encoders against a sample tool output.
+
+use droid_mcp_rs::droid::DroidResult;
+use droid_mcp_rs::output::{self, JsonEncoder, OutputEncoder, TextEncoder, ToonEncoder};
+use droid_mcp_rs::server::DroidOutput;
+
+fn sample_output() -> DroidOutput {
+    let result = DroidResult {
+        success: true,
+        session_id: "sess-1".to_string(),
+        agent_messages: "all done".to_string(),
+        warnings: vec!["first warning".to_string(), "second warning".to_string()],
+        duration_ms: Some(1234),
+        ..Default::default()
+    };
+    DroidOutput::from_result(&result, "req-1".to_string(), false)
+}
+
+#[test]
+fn toon_encoder_matches_toon_format() {
+    let output = sample_output();
+    let encoded = ToonEncoder.encode(&output).unwrap();
+
+    assert_eq!(encoded, toon_format::encode_default(&output).unwrap());
+    assert!(encoded.contains("SESSION_ID: sess-1"), "{}", encoded);
+}
+
+#[test]
+fn json_encoder_produces_parseable_json() {
+    let encoded = JsonEncoder.encode(&sample_output()).unwrap();
+    let json: serde_json::Value = serde_json::from_str(&encoded).unwrap();
+
+    assert_eq!(json["SESSION_ID"], "sess-1");
+    assert_eq!(json["message"], "all done");
+    assert_eq!(json["request_id"], "req-1");
+    assert_eq!(json["duration_ms"], 1234);
+    assert_eq!(json["warnings"][1], "second warning");
+}
+
+#[test]
+fn text_encoder_renders_message_and_metadata() {
+    let encoded = TextEncoder.encode(&sample_output()).unwrap();
+
+    assert_eq!(
+        encoded,
+        "all done\n\nSESSION_ID: sess-1\nWarnings:\nfirst warning\nsecond warning"
+    );
+}
+
+#[test]
+fn text_encoder_includes_error_without_message() {
+    let result = DroidResult {
+        session_id: "sess-2".to_string(),
+        error: Some("boom".to_string()),
+        stderr: Some("trace line".to_string()),
+        ..Default::default()
+    };
+    let output = DroidOutput::from_result(&result, "req-2".to_string(), false);
+
+    assert_eq!(
+        TextEncoder.encode(&output).unwrap(),
+        "SESSION_ID: sess-2\nError: boom\nstderr:\ntrace line"
+    );
+}
+
+#[test]
+fn encoder_lookup_covers_every_format() {
+    for format in output::RESPONSE_FORMATS {
+        assert!(output::encoder(format).is_some(), "{}", format);
+    }
+    assert!(output::encoder("yaml").is_none());
+}
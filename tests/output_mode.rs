@@ -0,0 +1,32 @@
+//! `output_mode: text` responses, driven over MCP stdio.
+#![cfg(unix)]
+
+mod common;
+
+use common::{tool_text, McpSession};
+use serde_json::json;
+
+#[test]
+fn text_mode_returns_agent_messages_only() {
+    let mut server = McpSession::start(json!({}));
+
+    let response = server.call(
+        "droid",
+        json!({ "PROMPT": "say hello", "auto": "low", "output_mode": "text" }),
+    );
+    assert_eq!(tool_text(&response), "working on it\ndone");
+    assert_ne!(response["result"]["isError"], true, "{}", response);
+}
+
+#[test]
+fn text_mode_flags_failed_runs() {
+    let mut server = McpSession::start(json!({}));
+
+    let response = server.call(
+        "droid",
+        json!({ "PROMPT": "scenario:error", "auto": "low", "output_mode": "text" }),
+    );
+    assert_eq!(response["result"]["isError"], true, "{}", response);
+    // Without agent messages the error takes their place
+    assert_eq!(tool_text(&response), "droid error: simulated failure");
+}
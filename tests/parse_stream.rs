@@ -0,0 +1,358 @@
+//! `droid::parse_stream` against synthetic stream-json input.
+
+use droid_mcp_rs::droid::{parse_stream, serialized_size, Limits, RECEIVED_MS_KEY};
+use droid_mcp_rs::server::{text_message, DroidOutput};
+use std::collections::HashMap;
+
+#[tokio::test]
+async fn first_session_id_wins() {
+    let stream = concat!(
+        r#"{"type":"system","meta":{"session_id":"nested-first"}}"#,
+        "\n",
+        r#"{"type":"system","session_id":"later"}"#,
+        "\n",
+    );
+    let result = parse_stream(stream.as_bytes(), Limits::default()).await;
+
+    assert_eq!(result.session_id, "nested-first");
+    assert!(result.success);
+}
+
+#[tokio::test]
+async fn error_line_records_message_and_fails() {
+    let stream = concat!(
+        r#"{"type":"system","session_id":"s1"}"#,
+        "\n",
+        r#"{"type":"error","message":"boom"}"#,
+        "\n",
+    );
+    let result = parse_stream(stream.as_bytes(), Limits::default()).await;
+
+    assert!(!result.success);
+    assert_eq!(result.error.as_deref(), Some("droid error: boom"));
+    assert_eq!(result.line_stats.error, 1);
+}
+
+#[tokio::test]
+async fn agent_messages_are_truncated_at_limit() {
+    let stream = concat!(
+        r#"{"type":"message","role":"assistant","text":"0123456789"}"#,
+        "\n",
+        r#"{"type":"completion","finalText":"this no longer fits"}"#,
+        "\n",
+        r#"{"type":"completion","finalText":"neither does this"}"#,
+        "\n",
+    );
+    let limits = Limits {
+        agent_messages: 16,
+        ..Limits::default()
+    };
+    let result = parse_stream(stream.as_bytes(), limits).await;
+
+    assert!(result.agent_messages_truncated);
+    assert_eq!(result.agent_messages, "0123456789");
+    assert!(result.completions.is_empty());
+}
+
+#[tokio::test]
+async fn all_messages_are_truncated_at_limit() {
+    let stream = concat!(
+        r#"{"type":"message","role":"assistant","text":"a"}"#,
+        "\n",
+        r#"{"type":"message","role":"assistant","text":"b"}"#,
+        "\n",
+    );
+    let limits = Limits {
+        all_messages: 60,
+        ..Limits::default()
+    };
+    let result = parse_stream(stream.as_bytes(), limits).await;
+
+    assert_eq!(result.all_messages.len(), 1);
+    assert!(result.all_messages_truncated);
+    assert_eq!(result.agent_messages, "a\nb");
+}
+
+#[tokio::test]
+async fn all_messages_size_tracking_matches_serialized_length() {
+    let lines: Vec<String> = (0..5000)
+        .map(|i| {
+            format!(
+                r#"{{"type":"message","role":"assistant","text":"line {} \u00e9 \"quoted\"","step": {}}}"#,
+                i, i
+            )
+        })
+        .collect();
+    let stream = lines.join("\n");
+    let sizes: Vec<usize> = lines
+        .iter()
+        .map(|line| {
+            let map: HashMap<String, serde_json::Value> = serde_json::from_str(line).unwrap();
+            let size = serde_json::to_string(&map).unwrap().len();
+            assert_eq!(serialized_size(&map), size);
+            size
+        })
+        .collect();
+
+    // A limit of exactly the first 3000 entries keeps those and nothing more
+    let limits = Limits {
+        all_messages: sizes[..3000].iter().sum(),
+        ..Limits::default()
+    };
+    let result = parse_stream(stream.as_bytes(), limits).await;
+
+    assert_eq!(result.all_messages.len(), 3000);
+    assert!(result.all_messages_truncated);
+    assert_eq!(result.line_stats.message, 5000);
+
+    let limits = Limits {
+        all_messages: sizes.iter().sum(),
+        ..Limits::default()
+    };
+    let result = parse_stream(stream.as_bytes(), limits).await;
+
+    assert_eq!(result.all_messages.len(), 5000);
+    assert!(!result.all_messages_truncated);
+}
+
+#[tokio::test]
+async fn oversized_line_is_skipped_and_parsing_continues() {
+    let huge = format!(
+        r#"{{"type":"message","role":"assistant","text":"{}"}}"#,
+        "x".repeat(10_000)
+    );
+    let stream = format!(
+        "{}\n{}\n{}\n",
+        r#"{"type":"system","session_id":"s1"}"#,
+        huge,
+        r#"{"type":"completion","finalText":"after the big line"}"#
+    );
+    let limits = Limits {
+        max_line_bytes: 1024,
+        ..Limits::default()
+    };
+    let result = parse_stream(stream.as_bytes(), limits).await;
+
+    assert!(result.success);
+    assert_eq!(result.session_id, "s1");
+    assert_eq!(result.agent_messages, "after the big line");
+    assert_eq!(result.all_messages.len(), 2);
+    assert_eq!(result.line_stats.oversized, 1);
+    assert_eq!(result.line_stats.message, 0);
+    assert!(
+        result
+            .warnings
+            .iter()
+            .any(|w| w.contains("Skipped 1 stream-json line(s) longer than max_line_bytes")),
+        "{:?}",
+        result.warnings
+    );
+
+    // The same stream within the default limit parses every line
+    let result = parse_stream(stream.as_bytes(), Limits::default()).await;
+    assert_eq!(result.line_stats.oversized, 0);
+    assert_eq!(result.all_messages.len(), 3);
+}
+
+#[tokio::test]
+async fn oversized_final_line_without_newline_is_skipped() {
+    let stream = format!(
+        "{}\n{}",
+        r#"{"type":"completion","finalText":"kept"}"#,
+        "y".repeat(4096)
+    );
+    let limits = Limits {
+        max_line_bytes: 100,
+        ..Limits::default()
+    };
+    let result = parse_stream(stream.as_bytes(), limits).await;
+
+    assert_eq!(result.agent_messages, "kept");
+    assert_eq!(result.line_stats.oversized, 1);
+}
+
+#[tokio::test]
+async fn truncation_flags_reach_tool_output() {
+    let stream = concat!(
+        r#"{"type":"message","role":"assistant","text":"0123456789"}"#,
+        "\n",
+        r#"{"type":"completion","finalText":"this no longer fits"}"#,
+        "\n",
+    );
+    let limits = Limits {
+        agent_messages: 16,
+        all_messages: 60,
+        ..Limits::default()
+    };
+    let result = parse_stream(stream.as_bytes(), limits).await;
+
+    let output = DroidOutput::from_result(&result, "req".to_string(), false);
+    let json = serde_json::to_value(&output).unwrap();
+    // Structured output reports truncation through the flag, keeping message clean
+    assert_eq!(json["message"], "0123456789");
+    assert_eq!(json["message_truncated"], true);
+    assert_eq!(json["all_messages_truncated"], true);
+
+    let untruncated = parse_stream(stream.as_bytes(), Limits::default()).await;
+    let json = serde_json::to_value(DroidOutput::from_result(
+        &untruncated,
+        "req".to_string(),
+        false,
+    ))
+    .unwrap();
+    assert_eq!(json["message_truncated"], false);
+    assert_eq!(json["all_messages_truncated"], false);
+}
+
+#[tokio::test]
+async fn transcript_ndjson_round_trips_messages_in_order() {
+    let stream = concat!(
+        r#"{"type":"system","session_id":"s1"}"#,
+        "\n",
+        r#"{"type":"message","role":"assistant","text":"a"}"#,
+        "\n",
+        r#"{"type":"completion","finalText":"done","step":2}"#,
+        "\n",
+    );
+    let result = parse_stream(stream.as_bytes(), Limits::default()).await;
+
+    let ndjson = result.transcript_ndjson();
+    let replayed: Vec<HashMap<String, serde_json::Value>> = ndjson
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(replayed, result.all_messages);
+    assert_eq!(replayed.len(), 3);
+    assert_eq!(replayed[2]["type"], "completion");
+}
+
+#[tokio::test]
+async fn transcript_ndjson_ends_with_truncation_marker() {
+    let stream = concat!(
+        r#"{"type":"message","role":"assistant","text":"a"}"#,
+        "\n",
+        r#"{"type":"message","role":"assistant","text":"b"}"#,
+        "\n",
+    );
+    let limits = Limits {
+        all_messages: 60,
+        ..Limits::default()
+    };
+    let result = parse_stream(stream.as_bytes(), limits).await;
+
+    let lines: Vec<serde_json::Value> = result
+        .transcript_ndjson()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["text"], "a");
+    assert_eq!(lines[1]["type"], "truncated");
+}
+
+#[tokio::test]
+async fn text_mode_marks_truncated_messages_inline() {
+    let stream = concat!(
+        r#"{"type":"message","role":"assistant","text":"0123456789"}"#,
+        "\n",
+        r#"{"type":"completion","finalText":"this no longer fits"}"#,
+        "\n",
+    );
+    let limits = Limits {
+        agent_messages: 16,
+        ..Limits::default()
+    };
+    let result = parse_stream(stream.as_bytes(), limits).await;
+
+    assert_eq!(
+        text_message(&result),
+        "0123456789\n[... Agent messages truncated due to size limit ...]"
+    );
+
+    let untruncated = parse_stream(stream.as_bytes(), Limits::default()).await;
+    assert_eq!(
+        text_message(&untruncated),
+        "0123456789\nthis no longer fits"
+    );
+}
+
+#[tokio::test]
+async fn all_messages_are_stamped_with_receive_time() {
+    let (mut writer, reader) = tokio::io::duplex(1024);
+    let feeder = tokio::spawn(async move {
+        use tokio::io::AsyncWriteExt;
+        for line in [
+            r#"{"type":"system","session_id":"s1"}"#,
+            r#"{"type":"message","role":"assistant","text":"a","_mcp_received_ms":"droid's own"}"#,
+            r#"{"type":"completion","finalText":"done"}"#,
+        ] {
+            writer.write_all(line.as_bytes()).await.unwrap();
+            writer.write_all(b"\n").await.unwrap();
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+    });
+    let result = parse_stream(tokio::io::BufReader::new(reader), Limits::default()).await;
+    feeder.await.unwrap();
+
+    assert_eq!(result.all_messages.len(), 3);
+    let first = result.all_messages[0][RECEIVED_MS_KEY].as_u64().unwrap();
+    let last = result.all_messages[2][RECEIVED_MS_KEY].as_u64().unwrap();
+    assert!(last >= first + 50, "{} -> {}", first, last);
+    // An existing field of the same name is left alone
+    assert_eq!(result.all_messages[1][RECEIVED_MS_KEY], "droid's own");
+    assert_eq!(result.all_messages[2]["finalText"], "done");
+}
+
+#[tokio::test]
+async fn size_budget_bytes_match_produced_content() {
+    let stream = concat!(
+        r#"{"type":"system","session_id":"s1"}"#,
+        "\n",
+        r#"{"type":"message","role":"assistant","text":"héllo"}"#,
+        "\n",
+        r#"{"type":"completion","finalText":"done"}"#,
+        "\n",
+    );
+    let result = parse_stream(stream.as_bytes(), Limits::default()).await;
+
+    let raw_bytes: usize = result
+        .all_messages
+        .iter()
+        .map(|message| {
+            let mut message = message.clone();
+            message.remove(RECEIVED_MS_KEY);
+            serialized_size(&message)
+        })
+        .sum();
+    assert_eq!(result.all_messages_bytes, raw_bytes);
+
+    let json =
+        serde_json::to_value(DroidOutput::from_result(&result, "req".to_string(), false)).unwrap();
+    assert_eq!(json["message"], "héllo\ndone");
+    assert_eq!(json["agent_messages_bytes"], "héllo\ndone".len());
+    assert_eq!(json["all_messages_bytes"], raw_bytes);
+}
+
+#[tokio::test]
+async fn spec_and_summary_lines_have_their_own_counters() {
+    let stream = concat!(
+        r#"{"type":"system","session_id":"s1"}"#,
+        "\n",
+        r#"{"type":"spec","text":"1. do it"}"#,
+        "\n",
+        r#"{"type":"plan","text":"2. check it"}"#,
+        "\n",
+        r#"{"type":"result","success":true}"#,
+        "\n",
+        r#"{"type":"summary","summary":"done"}"#,
+        "\n",
+        r#"{"type":"tool_call","name":"Read"}"#,
+        "\n",
+    );
+    let result = parse_stream(stream.as_bytes(), Limits::default()).await;
+
+    assert_eq!(result.line_stats.system, 1);
+    assert_eq!(result.line_stats.spec, 2);
+    assert_eq!(result.line_stats.summary, 2);
+    assert_eq!(result.line_stats.unknown, 1);
+}
@@ -0,0 +1,30 @@
+//! The post-run command's exit status is reported in `post_run_output`.
+#![cfg(unix)]
+
+mod common;
+
+use droid_mcp_rs::droid::{self, Options};
+
+#[tokio::test]
+async fn post_run_output_shows_plain_exit_code() {
+    let config = common::with_config(serde_json::json!({
+        "enable_post_run": true,
+        "post_run_command": ["sh", "-c", "exit 3"]
+    }));
+    let result = droid::run(Options {
+        prompt: Some("say hello".to_string()),
+        working_dir: config.dir().to_path_buf(),
+        auto: Some("low".to_string()),
+        ..Default::default()
+    })
+    .await
+    .expect("droid::run failed");
+
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    let output = result.post_run_output.expect("no post-run output");
+    assert!(
+        output.starts_with("$ sh -c exit 3 (exit code 3)\n"),
+        "{}",
+        output
+    );
+}
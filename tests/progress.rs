@@ -0,0 +1,162 @@
+//! Progress heartbeats during a stalled run, driven over MCP stdio.
+#![cfg(unix)]
+
+mod common;
+
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+
+/// Call the droid tool on a stalling fake run and collect the messages up to its response
+fn call_stalled_run(meta: Option<serde_json::Value>) -> Vec<serde_json::Value> {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let config_path = dir.path().join("droid-mcp.config.json");
+    std::fs::write(&config_path, r#"{"progress_interval_secs": 1}"#).unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_droid-mcp-rs"))
+        .current_dir(dir.path())
+        .env("DROID_BIN", common::fake_droid())
+        .env("DROID_MCP_CONFIG_PATH", &config_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("failed to start server");
+
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut send = |message: serde_json::Value| {
+        writeln!(stdin, "{}", message).expect("failed to write request");
+    };
+    let mut read_until = |id: u64| {
+        let mut seen = Vec::new();
+        let mut line = String::new();
+        loop {
+            line.clear();
+            assert!(
+                stdout.read_line(&mut line).unwrap() > 0,
+                "server closed stdout"
+            );
+            let message: serde_json::Value = serde_json::from_str(&line).unwrap();
+            let done = message["id"] == id;
+            seen.push(message);
+            if done {
+                return seen;
+            }
+        }
+    };
+
+    send(serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "initialize",
+        "params": {
+            "protocolVersion": "2024-11-05",
+            "capabilities": {},
+            "clientInfo": { "name": "test", "version": "0" }
+        }
+    }));
+    read_until(1);
+    send(serde_json::json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }));
+
+    let mut params = serde_json::json!({
+        "name": "droid",
+        "arguments": {
+            "PROMPT": "scenario:stall",
+            "raw_model": "test-raw-model",
+            "auto": "low",
+            "timeout_secs": 10
+        }
+    });
+    if let Some(meta) = meta {
+        params["_meta"] = meta;
+    }
+    send(serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 2,
+        "method": "tools/call",
+        "params": params
+    }));
+    let messages = read_until(2);
+
+    drop(stdin);
+    let _ = child.kill();
+    let _ = child.wait();
+    messages
+}
+
+fn progress_notifications(messages: &[serde_json::Value]) -> Vec<&serde_json::Value> {
+    messages
+        .iter()
+        .filter(|m| m["method"] == "notifications/progress")
+        .map(|m| &m["params"])
+        .collect()
+}
+
+#[test]
+fn heartbeats_fire_while_droid_is_silent() {
+    let messages = call_stalled_run(Some(serde_json::json!({ "progressToken": "tok-1" })));
+    let response = messages.last().unwrap();
+    assert_eq!(response["result"]["isError"], false, "{}", response);
+
+    let heartbeats = progress_notifications(&messages);
+    assert!(heartbeats.len() >= 2, "{:?}", messages);
+    for heartbeat in &heartbeats {
+        assert_eq!(heartbeat["progressToken"], "tok-1");
+    }
+    let last = heartbeats.last().unwrap();
+    assert!(last["progress"].as_f64().unwrap() >= 2.0, "{}", last);
+    assert!(
+        last["message"]
+            .as_str()
+            .unwrap()
+            .contains("last line: system"),
+        "{}",
+        last
+    );
+}
+
+#[test]
+fn no_heartbeats_without_a_progress_token() {
+    let messages = call_stalled_run(None);
+
+    assert!(
+        progress_notifications(&messages).is_empty(),
+        "{:?}",
+        messages
+    );
+}
+
+/// Parse the `est. N%` estimate out of a heartbeat message
+fn estimate(heartbeat: &serde_json::Value) -> u64 {
+    let message = heartbeat["message"].as_str().unwrap();
+    let start = message.find("est. ").expect("heartbeat without estimate") + 5;
+    let end = start + message[start..].find('%').unwrap();
+    message[start..end].parse().unwrap()
+}
+
+#[test]
+fn heartbeat_estimate_grows_with_elapsed_time() {
+    let messages = call_stalled_run(Some(serde_json::json!({ "progressToken": "tok-2" })));
+    let heartbeats = progress_notifications(&messages);
+    assert!(heartbeats.len() >= 2, "{:?}", messages);
+
+    let estimates: Vec<u64> = heartbeats.iter().map(|h| estimate(h)).collect();
+    assert!(estimates.windows(2).all(|w| w[0] < w[1]), "{:?}", estimates);
+    // One heartbeat a second against a 10 second timeout
+    assert_eq!(estimates[0], 10);
+    assert!(heartbeats[0]["message"]
+        .as_str()
+        .unwrap()
+        .contains("of the 10s timeout"));
+}
+
+#[test]
+fn estimate_is_capped_below_completion() {
+    assert_eq!(droid_mcp_rs::droid::estimated_percent(0, 600), 0);
+    assert_eq!(droid_mcp_rs::droid::estimated_percent(300, 600), 50);
+    assert_eq!(droid_mcp_rs::droid::estimated_percent(600, 600), 99);
+    assert_eq!(droid_mcp_rs::droid::estimated_percent(5000, 600), 99);
+    let series: Vec<u8> = (0..=600)
+        .map(|s| droid_mcp_rs::droid::estimated_percent(s, 600))
+        .collect();
+    assert!(series.windows(2).all(|w| w[0] <= w[1]));
+}
@@ -0,0 +1,75 @@
+//! `prompt_env`: prompts read from allowlisted server environment variables.
+
+mod common;
+
+use common::TestConfig;
+use droid_mcp_rs::server::{resolve_prompt_env, DroidArgs};
+
+/// Load a config allowing the test variables (and nothing else) as `prompt_env`
+fn load_config() -> TestConfig {
+    common::with_config(serde_json::json!({ "allowed_prompt_env": [
+        "DROID_MCP_TEST_PROMPT_PRESENT",
+        "DROID_MCP_TEST_PROMPT_MISSING",
+        "DROID_MCP_TEST_PROMPT_EMPTY"
+    ] }))
+}
+
+fn droid_args(json: serde_json::Value) -> DroidArgs {
+    serde_json::from_value(json).expect("invalid droid args")
+}
+
+#[test]
+fn prompt_env_reads_the_named_variable() {
+    let _config = load_config();
+    std::env::set_var("DROID_MCP_TEST_PROMPT_PRESENT", "fix the tests");
+    let mut args = droid_args(serde_json::json!({ "prompt_env": "DROID_MCP_TEST_PROMPT_PRESENT" }));
+
+    resolve_prompt_env(&mut args).unwrap();
+    assert_eq!(args.prompt.as_deref(), Some("fix the tests"));
+    assert_eq!(args.prompt_env, None);
+}
+
+#[test]
+fn prompt_env_rejects_missing_or_empty_variable() {
+    let _config = load_config();
+    std::env::set_var("DROID_MCP_TEST_PROMPT_EMPTY", "  ");
+    for (name, expected) in [
+        ("DROID_MCP_TEST_PROMPT_MISSING", "is not set"),
+        ("DROID_MCP_TEST_PROMPT_EMPTY", "is empty"),
+    ] {
+        let mut args = droid_args(serde_json::json!({ "prompt_env": name }));
+        let err = resolve_prompt_env(&mut args).unwrap_err();
+        assert!(err.message.contains(expected), "{}", err.message);
+    }
+}
+
+#[test]
+fn prompt_env_is_exclusive_with_prompt_and_file() {
+    let _config = load_config();
+    for extra in [
+        serde_json::json!({ "PROMPT": "hi" }),
+        serde_json::json!({ "file": "task.md" }),
+    ] {
+        let mut json = serde_json::json!({ "prompt_env": "DROID_MCP_TEST_PROMPT_PRESENT" });
+        json.as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        let err = resolve_prompt_env(&mut droid_args(json)).unwrap_err();
+        assert!(
+            err.message.contains("mutually exclusive"),
+            "{}",
+            err.message
+        );
+    }
+}
+
+#[test]
+fn unlisted_variable_is_refused_even_when_set() {
+    let _config = load_config();
+    std::env::set_var("DROID_MCP_TEST_SECRET", "sk-not-for-prompts");
+    let mut args = droid_args(serde_json::json!({ "prompt_env": "DROID_MCP_TEST_SECRET" }));
+
+    let err = resolve_prompt_env(&mut args).unwrap_err();
+    assert!(err.message.contains("is not allowed"), "{}", err.message);
+    assert_eq!(args.prompt, None);
+}
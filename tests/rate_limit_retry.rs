@@ -0,0 +1,70 @@
+//! Rate-limit retries re-run droid with the same options on every attempt.
+#![cfg(unix)]
+
+mod common;
+
+use common::{with_config, TestConfig};
+use droid_mcp_rs::droid::{self, DroidResult, ErrorCode, Options};
+
+/// Config retrying rate limits twice without backoff
+fn setup() -> TestConfig {
+    with_config(serde_json::json!({
+        "retry_on_rate_limit": true,
+        "rate_limit_max_retries": 2,
+        "rate_limit_backoff_secs": 0
+    }))
+}
+
+async fn run_scenario(config: &TestConfig, prompt: &str) -> DroidResult {
+    droid::run(Options {
+        prompt: Some(prompt.to_string()),
+        working_dir: config.dir().to_path_buf(),
+        ..Default::default()
+    })
+    .await
+    .expect("droid::run failed")
+}
+
+fn retry_warnings(result: &DroidResult) -> Vec<&String> {
+    result
+        .warnings
+        .iter()
+        .filter(|w| w.starts_with("Droid reported a rate limit"))
+        .collect()
+}
+
+#[tokio::test]
+async fn every_retry_reruns_the_same_request() {
+    let config = setup();
+    let result = run_scenario(&config, "scenario:structured-error").await;
+
+    // The fake rate-limits every attempt, so each retry saw the same prompt and options
+    assert!(!result.success);
+    assert!(result.rate_limited);
+    assert_eq!(result.error_code, Some(ErrorCode::RateLimited));
+    assert_eq!(result.error.as_deref(), Some("droid error: slow down"));
+    let retries = retry_warnings(&result);
+    assert_eq!(retries.len(), 2, "{:?}", result.warnings);
+    assert!(retries[1].contains("retry 2/2"), "{}", retries[1]);
+    assert_eq!(result.working_dir, config.dir());
+}
+
+#[tokio::test]
+async fn unrelated_429_in_stderr_is_not_retried() {
+    let config = setup();
+    let result = run_scenario(&config, "scenario:stderr-429").await;
+
+    assert!(!result.success);
+    assert!(!result.rate_limited);
+    assert_eq!(result.error_code, Some(ErrorCode::NonZeroExit));
+    assert!(retry_warnings(&result).is_empty(), "{:?}", result.warnings);
+}
+
+#[tokio::test]
+async fn http_429_in_stderr_is_retried() {
+    let config = setup();
+    let result = run_scenario(&config, "scenario:stderr-http-429").await;
+
+    assert!(result.rate_limited);
+    assert_eq!(retry_warnings(&result).len(), 2, "{:?}", result.warnings);
+}
@@ -0,0 +1,67 @@
+//! `raw_model` passthrough, which skips custom model resolution and defaults.
+#![cfg(unix)]
+
+mod common;
+
+use common::McpSession;
+use droid_mcp_rs::droid::{self, Options};
+use serde_json::json;
+
+#[tokio::test]
+async fn raw_model_is_passed_verbatim() {
+    let config = common::with_config(json!({}));
+    // A custom model that would otherwise become the default
+    config.use_home(json!({ "custom_models": [
+        { "model_display_name": "GPT Mini", "model": "gpt-mini", "provider": "test" }
+    ] }));
+
+    let opts = Options {
+        prompt: Some("scenario:echo-args".to_string()),
+        working_dir: config.dir().to_path_buf(),
+        auto: Some("low".to_string()),
+        raw_model: Some("custom:Not-A-Model-7".to_string()),
+        ..Default::default()
+    };
+    let result = droid::run(opts).await.expect("droid::run failed");
+
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert!(
+        result
+            .agent_messages
+            .contains(" --model custom:Not-A-Model-7 "),
+        "{}",
+        result.agent_messages
+    );
+    assert!(
+        !result.agent_messages.contains("GPT-Mini"),
+        "{}",
+        result.agent_messages
+    );
+    assert_eq!(
+        result.model_info.as_deref(),
+        Some("Model: custom:Not-A-Model-7 (raw passthrough)")
+    );
+}
+
+#[test]
+fn raw_model_and_model_are_mutually_exclusive() {
+    let mut server = McpSession::start(json!({}));
+
+    let response = server.call(
+        "droid",
+        json!({
+            "PROMPT": "say hello",
+            "auto": "low",
+            "model": "gpt-5",
+            "raw_model": "gpt-5"
+        }),
+    );
+    // invalid_params
+    assert_eq!(response["error"]["code"], -32602, "{}", response);
+    let message = response["error"]["message"].as_str().unwrap();
+    assert!(
+        message.contains("model and raw_model are mutually exclusive"),
+        "{}",
+        message
+    );
+}
@@ -0,0 +1,40 @@
+//! Precedence of per-call, per-model and global reasoning effort defaults.
+#![cfg(unix)]
+
+mod common;
+
+use droid_mcp_rs::droid::{self, Options};
+
+/// The reasoning effort droid was started with, as echoed by the fake, under a config
+/// with a global and a gpt-5 reasoning default
+async fn effort_for(model: &str, reasoning_effort: Option<&str>) -> String {
+    let config = common::with_config(serde_json::json!({
+        "default_reasoning_effort": "low",
+        "model_reasoning_defaults": { "gpt-5": "high" }
+    }));
+    let opts = Options {
+        prompt: Some("scenario:echo-effort".to_string()),
+        working_dir: config.dir().to_path_buf(),
+        model: Some(model.to_string()),
+        reasoning_effort: reasoning_effort.map(str::to_string),
+        ..Default::default()
+    };
+    let result = droid::run(opts).await.expect("droid::run failed");
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    result.agent_messages
+}
+
+#[tokio::test]
+async fn per_call_effort_wins_over_defaults() {
+    assert_eq!(effort_for("gpt-5", Some("medium")).await, "effort=medium");
+}
+
+#[tokio::test]
+async fn model_default_applies_when_call_omits_effort() {
+    assert_eq!(effort_for("gpt-5", None).await, "effort=high");
+}
+
+#[tokio::test]
+async fn global_default_applies_to_models_without_an_entry() {
+    assert_eq!(effort_for("claude-sonnet-4", None).await, "effort=low");
+}
@@ -0,0 +1,53 @@
+//! `reasoning_effort_models` gating (server config loaded from a temp file).
+#![cfg(unix)]
+
+mod common;
+
+use common::{with_config, TestConfig};
+use droid_mcp_rs::droid::{self, Options};
+
+/// Load a config that only lets gpt-5 take reasoning effort, and use the fake droid
+fn setup() -> TestConfig {
+    with_config(serde_json::json!({ "reasoning_effort_models": ["gpt-5"] }))
+}
+
+#[test]
+fn listed_model_supports_reasoning_effort() {
+    let _config = setup();
+    assert!(droid::supports_reasoning_effort(Some("gpt-5")));
+}
+
+#[test]
+fn unlisted_model_does_not_support_reasoning_effort() {
+    let _config = setup();
+    assert!(!droid::supports_reasoning_effort(Some("claude-sonnet-4")));
+}
+
+#[test]
+fn unknown_default_model_passes_through() {
+    let _config = setup();
+    assert!(droid::supports_reasoning_effort(None));
+}
+
+#[tokio::test]
+async fn unsupported_model_drops_reasoning_effort_with_warning() {
+    let config = setup();
+    let opts = Options {
+        prompt: Some("say hello".to_string()),
+        working_dir: config.dir().to_path_buf(),
+        model: Some("claude-sonnet-4".to_string()),
+        reasoning_effort: Some("high".to_string()),
+        ..Default::default()
+    };
+    let result = droid::run(opts).await.expect("droid::run failed");
+
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert!(
+        result
+            .warnings
+            .iter()
+            .any(|w| w.contains("does not support reasoning_effort")),
+        "{:?}",
+        result.warnings
+    );
+}
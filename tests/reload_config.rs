@@ -0,0 +1,43 @@
+//! Reloading picks up custom models added to the Factory config.
+
+use droid_mcp_rs::droid;
+use serde_json::json;
+
+#[test]
+fn reload_reflects_added_custom_model() {
+    let home = tempfile::tempdir().expect("failed to create temp dir");
+    let factory_dir = home.path().join(".factory");
+    std::fs::create_dir(&factory_dir).unwrap();
+    let factory_config = factory_dir.join("config.json");
+    let model =
+        |name: &str| json!({ "model_display_name": name, "model": name, "provider": "test" });
+    std::fs::write(
+        &factory_config,
+        json!({ "custom_models": [model("First")] }).to_string(),
+    )
+    .unwrap();
+    std::env::set_var("HOME", home.path());
+    std::env::set_var("USERPROFILE", home.path());
+    std::env::set_var("DROID_MCP_CONFIG_PATH", home.path().join("missing.json"));
+
+    assert_eq!(droid::list_custom_models().len(), 1);
+
+    std::fs::write(
+        &factory_config,
+        json!({ "custom_models": [model("First"), model("Second")] }).to_string(),
+    )
+    .unwrap();
+    // Cached until reloaded
+    assert_eq!(droid::list_custom_models().len(), 1);
+
+    let summary = droid::reload_config().expect("reload failed");
+    assert_eq!(summary.custom_models_before, 1);
+    assert_eq!(summary.custom_models_after, 2);
+    assert!(!summary.server_config_changed);
+
+    let labels: Vec<String> = droid::list_custom_models()
+        .into_iter()
+        .map(|m| m.label)
+        .collect();
+    assert_eq!(labels, ["First", "Second"]);
+}
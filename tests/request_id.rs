@@ -0,0 +1,91 @@
+//! Per-call `request_id` in droid tool output, driven over MCP stdio.
+#![cfg(unix)]
+
+mod common;
+
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+
+/// Extract the `request_id` field from a TOON-encoded droid tool result
+fn request_id(response: &serde_json::Value) -> String {
+    let text = response["result"]["content"][0]["text"]
+        .as_str()
+        .unwrap_or_else(|| panic!("no text content: {}", response));
+    text.lines()
+        .find_map(|line| line.strip_prefix("request_id: "))
+        .unwrap_or_else(|| panic!("no request_id in output: {}", text))
+        .trim_matches('"')
+        .to_string()
+}
+
+#[test]
+fn each_call_gets_a_unique_request_id() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_droid-mcp-rs"))
+        .current_dir(dir.path())
+        .env("DROID_BIN", common::fake_droid())
+        .env("DROID_MCP_CONFIG_PATH", dir.path().join("missing.json"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to start server");
+
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut send = |message: serde_json::Value| {
+        writeln!(stdin, "{}", message).expect("failed to write request");
+    };
+    let mut wait_for = |id: u64| {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            assert!(
+                stdout.read_line(&mut line).unwrap() > 0,
+                "server closed stdout"
+            );
+            let response: serde_json::Value = serde_json::from_str(&line).unwrap();
+            if response["id"] == id {
+                return response;
+            }
+        }
+    };
+
+    send(serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "initialize",
+        "params": {
+            "protocolVersion": "2024-11-05",
+            "capabilities": {},
+            "clientInfo": { "name": "test", "version": "0" }
+        }
+    }));
+    wait_for(1);
+    send(serde_json::json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }));
+
+    let mut ids = Vec::new();
+    for id in [2, 3] {
+        send(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": {
+                "name": "droid",
+                "arguments": { "PROMPT": "say hello", "raw_model": "test-raw-model", "auto": "low" }
+            }
+        }));
+        ids.push(request_id(&wait_for(id)));
+    }
+
+    drop(send);
+    drop(stdin);
+    let output = child.wait_with_output().expect("server did not exit");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(ids[0].len(), 36, "not a UUID: {}", ids[0]);
+    assert_ne!(ids[0], ids[1]);
+    for id in &ids {
+        assert!(stderr.contains(&format!("[request {}]", id)), "{}", stderr);
+    }
+}
@@ -0,0 +1,48 @@
+//! Runs without agent messages: a warning by default, a failure with `require_agent_messages`.
+#![cfg(unix)]
+
+mod common;
+
+use droid_mcp_rs::droid::{self, DroidResult, ErrorCode, Options};
+use serde_json::json;
+
+async fn run_silent(dir: &std::path::Path) -> DroidResult {
+    let opts = Options {
+        prompt: Some("scenario:silent".to_string()),
+        working_dir: dir.to_path_buf(),
+        auto: Some("low".to_string()),
+        ..Default::default()
+    };
+    droid::run(opts).await.expect("droid::run failed")
+}
+
+#[tokio::test]
+async fn empty_messages_succeed_with_a_warning() {
+    let config = common::with_config(json!({}));
+
+    let result = run_silent(config.dir()).await;
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert_eq!(result.session_id, "fake-session-silent");
+    assert!(result.agent_messages.is_empty());
+    assert!(
+        result
+            .warnings
+            .iter()
+            .any(|w| w.contains("without producing any agent messages")),
+        "{:?}",
+        result.warnings
+    );
+}
+
+#[tokio::test]
+async fn strict_mode_fails_empty_runs() {
+    let config = common::with_config(json!({ "require_agent_messages": true }));
+
+    let result = run_silent(config.dir()).await;
+    assert!(!result.success);
+    assert_eq!(result.error_code, Some(ErrorCode::NoAgentMessages));
+    assert_eq!(
+        result.error.as_deref(),
+        Some("No agent messages received from droid")
+    );
+}
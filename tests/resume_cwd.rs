@@ -0,0 +1,109 @@
+//! Resuming a session from a different working directory, driven over MCP stdio.
+#![cfg(unix)]
+
+mod common;
+
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Run the droid tool once per `cwd` against the same session and return the response texts
+fn resume_in(session_id: &str, cwds: &[&Path]) -> Vec<String> {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_droid-mcp-rs"))
+        .current_dir(dir.path())
+        .env("DROID_BIN", common::fake_droid())
+        .env("DROID_MCP_CONFIG_PATH", dir.path().join("missing.json"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("failed to start server");
+
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut send = |message: serde_json::Value| {
+        writeln!(stdin, "{}", message).expect("failed to write request");
+    };
+    let mut read_response = |id: u64| loop {
+        let mut line = String::new();
+        assert!(
+            stdout.read_line(&mut line).unwrap() > 0,
+            "server closed stdout"
+        );
+        let message: serde_json::Value = serde_json::from_str(&line).unwrap();
+        if message["id"] == id {
+            return message;
+        }
+    };
+
+    send(serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "initialize",
+        "params": {
+            "protocolVersion": "2024-11-05",
+            "capabilities": {},
+            "clientInfo": { "name": "test", "version": "0" }
+        }
+    }));
+    read_response(1);
+    send(serde_json::json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }));
+
+    let mut texts = Vec::new();
+    for (id, cwd) in (2..).zip(cwds) {
+        send(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": {
+                "name": "droid",
+                "arguments": {
+                    "PROMPT": "say hello",
+                    "SESSION_ID": session_id,
+                    "cwd": cwd,
+                    "raw_model": "test-raw-model",
+                    "auto": "low"
+                }
+            }
+        }));
+        let response = read_response(id);
+        assert_eq!(response["result"]["isError"], false, "{}", response);
+        texts.push(
+            response["result"]["content"][0]["text"]
+                .as_str()
+                .unwrap()
+                .to_string(),
+        );
+    }
+
+    drop(stdin);
+    let _ = child.kill();
+    let _ = child.wait();
+    texts
+}
+
+#[test]
+fn resume_in_other_cwd_warns() {
+    let first = tempfile::tempdir().unwrap();
+    let second = tempfile::tempdir().unwrap();
+    let texts = resume_in("resume-1", &[first.path(), second.path()]);
+
+    assert!(!texts[0].contains("last ran in"), "{}", texts[0]);
+    assert!(
+        texts[1].contains(&format!(
+            "but it last ran in {}",
+            first.path().canonicalize().unwrap().display()
+        )),
+        "{}",
+        texts[1]
+    );
+}
+
+#[test]
+fn resume_in_same_cwd_does_not_warn() {
+    let cwd = tempfile::tempdir().unwrap();
+    let texts = resume_in("resume-2", &[cwd.path(), cwd.path()]);
+
+    assert!(!texts[1].contains("last ran in"), "{}", texts[1]);
+}
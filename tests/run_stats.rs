@@ -0,0 +1,70 @@
+//! Aggregates computed by the `run_stats` tool's run log.
+
+use droid_mcp_rs::stats::{RunLog, RunMetric, RunWindow};
+use std::time::{Duration, Instant};
+
+fn metric(age_secs: u64, success: bool, duration_ms: u64, model: &str) -> RunMetric {
+    RunMetric {
+        finished_at: Instant::now() - Duration::from_secs(age_secs),
+        success,
+        duration: Duration::from_millis(duration_ms),
+        model: model.to_string(),
+    }
+}
+
+/// Four runs, oldest first
+fn seeded_log() -> RunLog {
+    let log = RunLog::new();
+    log.record(metric(600, false, 4000, "gpt-5"));
+    log.record(metric(30, true, 1000, "gpt-5"));
+    log.record(metric(20, true, 2000, "custom:Sonnet-0"));
+    log.record(metric(10, false, 3000, "gpt-5"));
+    log
+}
+
+#[test]
+fn summarizes_every_run_by_default() {
+    let stats = seeded_log().summarize(RunWindow::default());
+
+    assert_eq!(stats.total_runs, 4);
+    assert_eq!(stats.successful_runs, 2);
+    assert_eq!(stats.success_rate, Some(0.5));
+    assert_eq!(stats.average_duration_ms, Some(2500));
+    assert_eq!(stats.models["gpt-5"], 3);
+    assert_eq!(stats.models["custom:Sonnet-0"], 1);
+}
+
+#[test]
+fn last_limits_to_most_recent_runs() {
+    let stats = seeded_log().summarize(RunWindow {
+        last: Some(2),
+        ..Default::default()
+    });
+
+    assert_eq!(stats.total_runs, 2);
+    assert_eq!(stats.successful_runs, 1);
+    assert_eq!(stats.average_duration_ms, Some(2500));
+    assert_eq!(stats.models.len(), 2);
+}
+
+#[test]
+fn within_drops_old_runs() {
+    let stats = seeded_log().summarize(RunWindow {
+        within: Some(Duration::from_secs(60)),
+        ..Default::default()
+    });
+
+    assert_eq!(stats.total_runs, 3);
+    assert_eq!(stats.average_duration_ms, Some(2000));
+    assert_eq!(stats.success_rate, Some(2.0 / 3.0));
+}
+
+#[test]
+fn empty_window_has_no_rates() {
+    let stats = RunLog::new().summarize(RunWindow::default());
+
+    assert_eq!(stats.total_runs, 0);
+    assert_eq!(stats.success_rate, None);
+    assert_eq!(stats.average_duration_ms, None);
+    assert!(stats.models.is_empty());
+}
@@ -0,0 +1,49 @@
+//! The droid tool schema only advertises enabled autonomy levels, following config reloads.
+
+mod common;
+
+use common::with_config;
+use droid_mcp_rs::server::DroidServer;
+
+/// The `auto` levels in the server's current droid tool schema
+fn auto_levels(server: &DroidServer) -> Vec<serde_json::Value> {
+    let schema = server
+        .droid_input_schema()
+        .expect("droid tool schema is not registered");
+    schema["properties"]["auto"]["enum"]
+        .as_array()
+        .expect("auto has no enum")
+        .clone()
+}
+
+#[test]
+fn high_is_absent_from_auto_enum_when_disabled() {
+    let _config = with_config(serde_json::json!({ "allow_high_autonomy": false }));
+
+    let schema = DroidServer::new()
+        .droid_input_schema()
+        .expect("droid tool schema is not registered");
+    let levels = schema["properties"]["auto"]["enum"]
+        .as_array()
+        .expect("auto has no enum");
+
+    assert!(levels.contains(&serde_json::json!("low")));
+    assert!(levels.contains(&serde_json::json!("medium")));
+    assert!(!levels.contains(&serde_json::json!("high")), "{:?}", levels);
+    assert_eq!(
+        schema["properties"]["spec_auto"]["enum"],
+        schema["properties"]["auto"]["enum"]
+    );
+}
+
+#[test]
+fn auto_enum_follows_config_reload() {
+    let config = with_config(serde_json::json!({ "allow_high_autonomy": false }));
+    let server = DroidServer::new();
+    assert!(!auto_levels(&server).contains(&serde_json::json!("high")));
+
+    config.reload(serde_json::json!({ "allow_high_autonomy": true }));
+
+    let levels = auto_levels(&server);
+    assert!(levels.contains(&serde_json::json!("high")), "{:?}", levels);
+}
@@ -0,0 +1,82 @@
+//! `scope_paths` validation and rendering with a configured `scope_flag`.
+#![cfg(unix)]
+
+mod common;
+
+use common::{with_config, TestConfig};
+use droid_mcp_rs::droid::{self, validate_scope_paths, Options};
+
+/// Config naming a scope flag
+fn setup() -> TestConfig {
+    with_config(serde_json::json!({ "scope_flag": "--allow-path" }))
+}
+
+fn scope(paths: &[&str]) -> Vec<String> {
+    paths.iter().map(|p| p.to_string()).collect()
+}
+
+#[test]
+fn relative_paths_inside_the_working_dir_are_accepted() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    std::fs::create_dir(dir.path().join("src")).unwrap();
+
+    assert_eq!(
+        validate_scope_paths(dir.path(), &scope(&["src", "src/new.rs", "README.md"])),
+        Ok(())
+    );
+}
+
+#[test]
+fn escaping_paths_are_rejected() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+
+    for (entry, expected) in [
+        ("/etc", "must be relative"),
+        ("src/../../etc", "must not contain '..'"),
+        (" ", "must not be empty"),
+    ] {
+        let error =
+            validate_scope_paths(dir.path(), &scope(&[entry])).expect_err("expected a rejection");
+        assert!(error.contains(expected), "{}: {}", entry, error);
+    }
+}
+
+#[test]
+fn symlink_out_of_the_working_dir_is_rejected() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let outside = tempfile::tempdir().expect("failed to create temp dir");
+    std::os::unix::fs::symlink(outside.path(), dir.path().join("link")).unwrap();
+
+    let error =
+        validate_scope_paths(dir.path(), &scope(&["link"])).expect_err("expected a rejection");
+
+    assert!(error.contains("resolves outside"), "{}", error);
+}
+
+#[tokio::test]
+async fn scope_paths_render_with_configured_flag() {
+    let config = setup();
+    let result = droid::run(Options {
+        prompt: Some("scenario:echo-args".to_string()),
+        working_dir: config.dir().to_path_buf(),
+        auto: Some("low".to_string()),
+        scope_paths: scope(&["src", "docs/guide.md"]),
+        ..Default::default()
+    })
+    .await
+    .expect("droid::run failed");
+
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert!(
+        result
+            .agent_messages
+            .contains("--allow-path src --allow-path docs/guide.md"),
+        "{}",
+        result.agent_messages
+    );
+    assert!(
+        !result.warnings.iter().any(|w| w.contains("scope_paths")),
+        "{:?}",
+        result.warnings
+    );
+}
@@ -0,0 +1,50 @@
+//! `--selftest` runs the full pipeline against a generated fake droid.
+#![cfg(unix)]
+
+use std::process::Command;
+
+#[test]
+fn selftest_passes() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let output = Command::new(env!("CARGO_BIN_EXE_droid-mcp-rs"))
+        .arg("--selftest")
+        .env_remove("DROID_BIN")
+        .env("DROID_MCP_CONFIG_PATH", dir.path().join("missing.json"))
+        .output()
+        .expect("failed to run server binary");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.starts_with("PASS"), "{}", stdout);
+}
+
+#[test]
+fn selftest_ignores_the_user_config() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let audit = dir.path().join("audit.jsonl");
+    let hook_marker = dir.path().join("post-run-ran");
+    let config = dir.path().join("droid-mcp.config.json");
+    std::fs::write(
+        &config,
+        serde_json::json!({
+            "audit_log_path": audit,
+            "enable_post_run": true,
+            "post_run_command": ["touch", hook_marker],
+            "allowed_models": ["nothing-matches-this"]
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_droid-mcp-rs"))
+        .arg("--selftest")
+        .env_remove("DROID_BIN")
+        .env("DROID_MCP_CONFIG_PATH", &config)
+        .output()
+        .expect("failed to run server binary");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success(), "{}", stdout);
+    assert!(!audit.exists(), "selftest wrote the audit log");
+    assert!(!hook_marker.exists(), "selftest ran the post-run hook");
+}
@@ -0,0 +1,183 @@
+//! Tests of `DroidServer` behavior that does not need a droid binary.
+
+use droid_mcp_rs::droid::AttachmentLimits;
+use droid_mcp_rs::server::{
+    check_attachment_limits, validate_passthrough_flags, DroidServer, ValidatePathsArgs,
+};
+
+#[test]
+fn omitted_cwd_uses_server_default() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let default_cwd = dir.path().canonicalize().unwrap();
+    let server = DroidServer::new().with_default_cwd(default_cwd.clone());
+
+    assert_eq!(server.resolve_working_dir(None).unwrap(), default_cwd);
+}
+
+#[test]
+fn explicit_cwd_overrides_server_default() {
+    let default_dir = tempfile::tempdir().expect("failed to create temp dir");
+    let client_dir = tempfile::tempdir().expect("failed to create temp dir");
+    let server = DroidServer::new().with_default_cwd(default_dir.path().canonicalize().unwrap());
+
+    assert_eq!(
+        server
+            .resolve_working_dir(Some(client_dir.path().to_path_buf()))
+            .unwrap(),
+        client_dir.path().canonicalize().unwrap()
+    );
+}
+
+#[test]
+fn too_many_attachments_are_rejected() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let files: Vec<_> = ["a.txt", "b.txt"]
+        .iter()
+        .map(|name| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, "x").unwrap();
+            path
+        })
+        .collect();
+    let limits = AttachmentLimits {
+        max_files: Some(1),
+        max_bytes: None,
+    };
+
+    let err = check_attachment_limits(&files, &limits).expect_err("expected rejection");
+    assert!(err.message.contains("max_attachments"), "{}", err.message);
+    assert!(check_attachment_limits(&files[..1], &limits).is_ok());
+}
+
+#[test]
+fn oversized_attachments_are_rejected() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let first = dir.path().join("a.txt");
+    let second = dir.path().join("b.txt");
+    std::fs::write(&first, "x".repeat(60)).unwrap();
+    std::fs::write(&second, "x".repeat(60)).unwrap();
+    let limits = AttachmentLimits {
+        max_files: None,
+        max_bytes: Some(100),
+    };
+
+    // Each file fits on its own; together they are over the limit
+    assert!(check_attachment_limits(std::slice::from_ref(&first), &limits).is_ok());
+    let err = check_attachment_limits(&[first, second], &limits).expect_err("expected rejection");
+    assert!(err.message.contains("120 bytes"), "{}", err.message);
+    assert!(
+        err.message.contains("max_attachment_bytes"),
+        "{}",
+        err.message
+    );
+}
+
+#[test]
+fn validate_paths_resolves_valid_inputs() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let root = dir.path().canonicalize().unwrap();
+    std::fs::write(root.join("task.md"), "do it").unwrap();
+    std::fs::write(root.join("notes.txt"), "context").unwrap();
+
+    let output = DroidServer::new().validate_paths(ValidatePathsArgs {
+        cwd: Some(root.clone()),
+        file: Some("task.md".into()),
+        context_file: Some("notes.txt".into()),
+        output_file: Some("out.jsonl".into()),
+        context_globs: Some(vec!["*.txt".to_string()]),
+        scope_paths: Some(vec!["src".to_string()]),
+    });
+
+    assert!(output.valid, "{:?}", output.errors);
+    assert_eq!(output.resolved["cwd"], root);
+    assert_eq!(output.resolved["file"], root.join("task.md"));
+    assert_eq!(output.resolved["context_file"], root.join("notes.txt"));
+    assert_eq!(output.resolved["output_file"], root.join("out.jsonl"));
+}
+
+#[test]
+fn validate_paths_reports_missing_cwd() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let output = DroidServer::new().validate_paths(ValidatePathsArgs {
+        cwd: Some(dir.path().join("missing")),
+        file: Some("task.md".into()),
+        ..Default::default()
+    });
+
+    assert!(!output.valid);
+    assert_eq!(output.errors.len(), 1, "{:?}", output.errors);
+    assert!(output.errors[0].starts_with("cwd:"), "{:?}", output.errors);
+    assert!(output.resolved.is_empty());
+}
+
+#[test]
+fn validate_paths_collects_every_invalid_path() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let root = dir.path().canonicalize().unwrap();
+    std::fs::create_dir(root.join("subdir")).unwrap();
+
+    let output = DroidServer::new().validate_paths(ValidatePathsArgs {
+        cwd: Some(root.clone()),
+        file: Some("missing.md".into()),
+        context_file: Some("subdir".into()),
+        output_file: Some("../escape.jsonl".into()),
+        context_globs: Some(vec!["../*".to_string()]),
+        scope_paths: Some(vec!["../other".to_string()]),
+    });
+
+    assert!(!output.valid);
+    let errors = output.errors.join("\n");
+    assert!(errors.contains("file: File does not exist"), "{}", errors);
+    assert!(
+        errors.contains("context_file: File path is not a file"),
+        "{}",
+        errors
+    );
+    assert!(
+        errors.contains("output_file: output_file must be inside"),
+        "{}",
+        errors
+    );
+    assert!(errors.contains("context_globs:"), "{}", errors);
+    assert!(errors.contains("scope_paths:"), "{}", errors);
+    assert_eq!(output.errors.len(), 4, "{}", errors);
+    assert_eq!(output.resolved.keys().collect::<Vec<_>>(), ["cwd"]);
+}
+
+#[test]
+fn passthrough_flag_keys_are_validated() {
+    for key in [
+        "",
+        "-dash-first",
+        "has space",
+        "eq=sign",
+        "under_score",
+        "ünicode",
+    ] {
+        let flags = std::collections::HashMap::from([(key.to_string(), "1".to_string())]);
+        let err = validate_passthrough_flags(Some(flags)).unwrap_err();
+        assert!(
+            err.message.contains("Invalid passthrough_flags key"),
+            "{}",
+            err.message
+        );
+    }
+}
+
+#[test]
+fn passthrough_flags_may_not_set_server_flags() {
+    for key in ["model", "auto", "cwd", "session-id"] {
+        let flags = std::collections::HashMap::from([(key.to_string(), "x".to_string())]);
+        let err = validate_passthrough_flags(Some(flags)).unwrap_err();
+        assert!(
+            err.message.contains("managed by the server"),
+            "{}",
+            err.message
+        );
+    }
+}
+
+#[test]
+fn omitted_passthrough_flags_are_empty() {
+    assert!(validate_passthrough_flags(None).unwrap().is_empty());
+}
@@ -0,0 +1,175 @@
+//! Stored session transcripts: `session_last_message`, `get_messages` and history.
+#![cfg(unix)]
+
+mod common;
+
+use common::{tool_text, McpSession};
+use droid_mcp_rs::droid::DroidResult;
+use droid_mcp_rs::sessions::SessionStore;
+use serde_json::json;
+use std::collections::HashMap;
+
+fn turn(session_id: &str, message: &str) -> DroidResult {
+    DroidResult {
+        success: true,
+        session_id: session_id.to_string(),
+        agent_messages: message.to_string(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn last_message_is_the_latest_turn() {
+    let store = SessionStore::new();
+    store.record(&turn("sess-a", "first answer"));
+    store.record(&turn("sess-a", "second answer"));
+    store.record(&turn("sess-b", "other session"));
+
+    assert_eq!(store.get("sess-a").unwrap().last_message, "second answer");
+    assert_eq!(store.get("sess-b").unwrap().last_message, "other session");
+    assert!(store.get("sess-unknown").is_none());
+}
+
+#[test]
+fn session_last_message_reads_a_previous_run() {
+    let mut server = McpSession::start(json!({}));
+
+    let run = server.call(
+        "droid",
+        json!({ "PROMPT": "say hello", "SESSION_ID": "sess-last", "auto": "low" }),
+    );
+    assert!(run.get("error").is_none(), "{}", run);
+
+    let response = server.call("session_last_message", json!({ "SESSION_ID": "sess-last" }));
+    let text = tool_text(&response);
+    assert!(text.contains("sess-last"), "{}", text);
+    assert!(text.contains("working on it"), "{}", text);
+    assert!(text.contains("done"), "{}", text);
+}
+
+#[test]
+fn session_last_message_reports_unknown_sessions() {
+    let mut server = McpSession::start(json!({}));
+
+    let response = server.call(
+        "session_last_message",
+        json!({ "SESSION_ID": "sess-never-run" }),
+    );
+    // resource_not_found
+    assert_eq!(response["error"]["code"], -32002, "{}", response);
+    let message = response["error"]["message"].as_str().unwrap();
+    assert!(message.contains("No stored transcript"), "{}", message);
+    assert!(message.contains("resume the session"), "{}", message);
+}
+
+fn with_messages(session_id: &str, count: usize) -> DroidResult {
+    DroidResult {
+        all_messages: (0..count)
+            .map(|i| HashMap::from([("index".to_string(), json!(i))]))
+            .collect(),
+        ..turn(session_id, "ok")
+    }
+}
+
+fn indices(page: &[HashMap<String, serde_json::Value>]) -> Vec<u64> {
+    page.iter().map(|m| m["index"].as_u64().unwrap()).collect()
+}
+
+#[test]
+fn messages_page_boundaries() {
+    let store = SessionStore::new();
+    store.record(&with_messages("sess-a", 5));
+
+    let (page, total) = store.messages_page("sess-a", 0, 2).unwrap();
+    assert_eq!((indices(&page), total), (vec![0, 1], 5));
+    // The last page is short
+    let (page, _) = store.messages_page("sess-a", 4, 2).unwrap();
+    assert_eq!(indices(&page), vec![4]);
+    // Exactly at the end, and past it, pages are empty but still report the total
+    for offset in [5, 6, usize::MAX] {
+        let (page, total) = store.messages_page("sess-a", offset, 2).unwrap();
+        assert!(page.is_empty(), "offset {}", offset);
+        assert_eq!(total, 5);
+    }
+    assert!(store.messages_page("sess-unknown", 0, 2).is_none());
+}
+
+#[test]
+fn get_messages_reports_has_more() {
+    let mut server = McpSession::start(json!({}));
+
+    // The fake's default run stores three messages
+    let run = server.call(
+        "droid",
+        json!({ "PROMPT": "say hello", "SESSION_ID": "sess-pages", "auto": "low" }),
+    );
+    assert!(run.get("error").is_none(), "{}", run);
+
+    let page = |server: &mut McpSession, offset: usize, limit: usize| {
+        let response = server.call(
+            "get_messages",
+            json!({ "SESSION_ID": "sess-pages", "offset": offset, "limit": limit }),
+        );
+        tool_text(&response).to_string()
+    };
+
+    let text = page(&mut server, 0, 2);
+    assert!(text.contains("total: 3"), "{}", text);
+    assert!(text.contains("has_more: true"), "{}", text);
+
+    let text = page(&mut server, 2, 2);
+    assert!(text.contains("has_more: false"), "{}", text);
+
+    let text = page(&mut server, 10, 2);
+    assert!(text.contains("offset: 10"), "{}", text);
+    assert!(text.contains("total: 3"), "{}", text);
+    assert!(text.contains("has_more: false"), "{}", text);
+
+    let response = server.call("get_messages", json!({ "SESSION_ID": "sess-unknown" }));
+    assert_eq!(response["error"]["code"], -32002, "{}", response);
+}
+
+#[test]
+fn history_accumulates_and_drops_oldest_beyond_cap() {
+    // MAX_AGENT_MESSAGES_SIZE
+    const CAP: usize = 10 * 1024 * 1024;
+    let store = SessionStore::new();
+    store.record(&turn("sess-a", "first"));
+    store.record(&turn("sess-a", ""));
+    store.record(&turn("sess-a", "second"));
+    assert_eq!(store.history("sess-a").unwrap(), "first\nsecond");
+
+    let old = "o".repeat(CAP / 2);
+    let new = "n".repeat(CAP / 2 + 10);
+    store.record(&turn("sess-a", &old));
+    store.record(&turn("sess-a", &new));
+    let history = store.history("sess-a").unwrap();
+    assert_eq!(history.len(), CAP);
+    assert!(history.ends_with(&format!("\n{}", new)));
+    assert!(!history.contains("second"));
+}
+
+#[test]
+fn accumulate_history_returns_the_running_conversation() {
+    let mut server = McpSession::start(json!({}));
+    let resume = |accumulate: bool| {
+        json!({
+            "PROMPT": "say hello",
+            "SESSION_ID": "sess-history",
+            "auto": "low",
+            "output_mode": "text",
+            "accumulate_history": accumulate
+        })
+    };
+
+    let first = server.call("droid", resume(true));
+    assert_eq!(tool_text(&first), "working on it\ndone");
+    let second = server.call("droid", resume(true));
+    assert_eq!(
+        tool_text(&second),
+        "working on it\ndone\nworking on it\ndone"
+    );
+    // Without the flag only the current turn is returned
+    let third = server.call("droid", resume(false));
+    assert_eq!(tool_text(&third), "working on it\ndone");
+}
@@ -0,0 +1,39 @@
+//! `min_session_interval_ms` throttling of back-to-back calls to one session.
+#![cfg(unix)]
+
+mod common;
+
+use common::McpSession;
+use serde_json::json;
+
+fn resume(session_id: &str) -> serde_json::Value {
+    json!({ "PROMPT": "say hello", "SESSION_ID": session_id, "auto": "low" })
+}
+
+#[test]
+fn second_call_within_interval_is_rejected() {
+    let mut server = McpSession::start(json!({ "min_session_interval_ms": 60000 }));
+
+    let first = server.call("droid", resume("sess-throttle"));
+    assert!(first.get("error").is_none(), "{}", first);
+
+    let second = server.call("droid", resume("sess-throttle"));
+    // invalid_request
+    assert_eq!(second["error"]["code"], -32600, "{}", second);
+    let message = second["error"]["message"].as_str().unwrap();
+    assert!(message.contains("wait at least 60000ms"), "{}", message);
+
+    // Other sessions are throttled independently
+    let other = server.call("droid", resume("sess-other"));
+    assert!(other.get("error").is_none(), "{}", other);
+}
+
+#[test]
+fn calls_are_not_throttled_by_default() {
+    let mut server = McpSession::start(json!({}));
+
+    for _ in 0..2 {
+        let response = server.call("droid", resume("sess-unthrottled"));
+        assert!(response.get("error").is_none(), "{}", response);
+    }
+}
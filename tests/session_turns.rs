@@ -0,0 +1,50 @@
+//! Per-session turn numbers kept by the session store and reported in tool output.
+
+use droid_mcp_rs::droid::DroidResult;
+use droid_mcp_rs::server::DroidOutput;
+use droid_mcp_rs::sessions::SessionStore;
+
+fn turn(session_id: &str) -> DroidResult {
+    DroidResult {
+        success: true,
+        session_id: session_id.to_string(),
+        agent_messages: "ok".to_string(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn three_calls_are_numbered_in_order() {
+    let store = SessionStore::new();
+    let first = store.record(&turn("sess-a"));
+    let second = store.record(&turn("sess-a"));
+    let third = store.record(&turn("sess-a"));
+
+    assert_eq!((first, second, third), (Some(1), Some(2), Some(3)));
+    assert_eq!(store.get("sess-a").unwrap().turns, 3);
+
+    let output =
+        DroidOutput::from_result(&turn("sess-a"), "req".to_string(), false).with_turn_number(third);
+    let json = serde_json::to_value(&output).unwrap();
+    assert_eq!(json["turn_number"], 3);
+}
+
+#[test]
+fn sessions_are_numbered_independently() {
+    let store = SessionStore::new();
+    store.record(&turn("sess-a"));
+    store.record(&turn("sess-a"));
+
+    assert_eq!(store.record(&turn("sess-b")), Some(1));
+    assert_eq!(store.record(&turn("sess-a")), Some(3));
+}
+
+#[test]
+fn runs_without_session_have_no_turn_number() {
+    let store = SessionStore::new();
+
+    assert_eq!(store.record(&turn("")), None);
+    let output = DroidOutput::from_result(&turn(""), "req".to_string(), false);
+    let json = serde_json::to_value(&output).unwrap();
+    assert!(json.get("turn_number").is_none());
+}
@@ -0,0 +1,73 @@
+//! `skip_permissions_unsafe` and high `spec_auto` are refused while `allow_high_autonomy` is false.
+#![cfg(unix)]
+
+mod common;
+
+use common::{with_config, TestConfig};
+use droid_mcp_rs::droid::{self, Options};
+
+/// Config disabling high autonomy
+fn setup() -> TestConfig {
+    with_config(serde_json::json!({ "allow_high_autonomy": false }))
+}
+
+#[test]
+fn skip_permissions_is_rejected_without_high_autonomy() {
+    let _config = setup();
+    let error = droid::check_skip_permissions_allowed().expect_err("expected a rejection");
+
+    assert!(error.contains("allow_high_autonomy is false"), "{}", error);
+}
+
+#[tokio::test]
+async fn run_refuses_skip_permissions_without_high_autonomy() {
+    let config = setup();
+    let error = droid::run(Options {
+        prompt: Some("say hello".to_string()),
+        working_dir: config.dir().to_path_buf(),
+        skip_permissions_unsafe: true,
+        ..Default::default()
+    })
+    .await
+    .expect_err("run must not start droid");
+
+    assert!(
+        error
+            .to_string()
+            .contains("skip_permissions_unsafe is disabled"),
+        "{}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn lower_autonomy_still_runs() {
+    let config = setup();
+    let result = droid::run(Options {
+        prompt: Some("say hello".to_string()),
+        working_dir: config.dir().to_path_buf(),
+        auto: Some("medium".to_string()),
+        ..Default::default()
+    })
+    .await
+    .expect("droid::run failed");
+
+    assert!(result.success, "{:?}", result.error);
+}
+
+#[tokio::test]
+async fn run_refuses_high_spec_auto_without_high_autonomy() {
+    let config = setup();
+    let error = droid::run(Options {
+        prompt: Some("say hello".to_string()),
+        working_dir: config.dir().to_path_buf(),
+        auto: Some("low".to_string()),
+        use_spec: true,
+        spec_auto: Some("high".to_string()),
+        ..Default::default()
+    })
+    .await
+    .expect_err("run must not start droid");
+
+    assert!(error.to_string().contains("spec_auto"), "{}", error);
+}
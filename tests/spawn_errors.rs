@@ -0,0 +1,41 @@
+//! Spawn failures are reported as typed errors instead of opaque run failures.
+#![cfg(unix)]
+
+use droid_mcp_rs::droid::{self, ErrorCode, Options};
+use std::os::unix::fs::PermissionsExt;
+
+async fn run_with_bin(bin: &std::path::Path) -> droid::DroidResult {
+    std::env::set_var("DROID_BIN", bin);
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    droid::run(Options {
+        prompt: Some("say hello".to_string()),
+        working_dir: dir.path().to_path_buf(),
+        auto: Some("low".to_string()),
+        ..Default::default()
+    })
+    .await
+    .expect("spawn failures should be reported in the result")
+}
+
+// One test so the DROID_BIN changes don't race
+#[tokio::test]
+async fn not_found_and_permission_denied_are_distinguished() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+
+    let missing = dir.path().join("no-such-droid");
+    let result = run_with_bin(&missing).await;
+    assert!(!result.success);
+    assert_eq!(result.error_code, Some(ErrorCode::DroidNotFound));
+    let error = result.error.unwrap();
+    assert!(error.contains("DROID_BIN"), "{}", error);
+    assert!(error.contains("PATH"), "{}", error);
+
+    let not_executable = dir.path().join("droid");
+    std::fs::write(&not_executable, "#!/bin/sh\n").unwrap();
+    std::fs::set_permissions(&not_executable, std::fs::Permissions::from_mode(0o644)).unwrap();
+    let result = run_with_bin(&not_executable).await;
+    assert!(!result.success);
+    assert_eq!(result.error_code, Some(ErrorCode::SpawnFailed));
+    let error = result.error.unwrap();
+    assert!(error.contains("Failed to start droid"), "{}", error);
+}
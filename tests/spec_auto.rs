@@ -0,0 +1,66 @@
+//! `spec_auto` placement in droid's argv and validation, driven over MCP stdio.
+#![cfg(unix)]
+
+mod common;
+
+use common::{tool_text, McpSession};
+use serde_json::json;
+
+fn spec_call(use_spec: bool, spec_auto: &str) -> serde_json::Value {
+    json!({
+        "PROMPT": "scenario:echo-args",
+        "auto": "low",
+        "raw_model": "exec-model",
+        "use_spec": use_spec,
+        "spec_model": "plan-model",
+        "spec_auto": spec_auto,
+        "output_mode": "text"
+    })
+}
+
+#[test]
+fn spec_auto_follows_spec_model() {
+    let mut server = McpSession::start(json!({}));
+
+    let response = server.call("droid", spec_call(true, "medium"));
+    let args = tool_text(&response);
+    assert!(
+        args.contains(" --use-spec --spec-model plan-model --spec-auto medium --model exec-model "),
+        "{}",
+        args
+    );
+}
+
+#[test]
+fn spec_auto_needs_spec_mode() {
+    let mut server = McpSession::start(json!({}));
+
+    let response = server.call("droid", spec_call(false, "medium"));
+    let args = tool_text(&response);
+    assert!(!args.contains("--spec-auto"), "{}", args);
+}
+
+#[test]
+fn invalid_spec_auto_is_rejected() {
+    let mut server = McpSession::start(json!({}));
+
+    let response = server.call("droid", spec_call(true, "extreme"));
+    // invalid_params
+    assert_eq!(response["error"]["code"], -32602, "{}", response);
+    let message = response["error"]["message"].as_str().unwrap();
+    assert!(
+        message.contains("Invalid spec_auto level: 'extreme'"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn high_spec_auto_follows_allow_high_autonomy() {
+    let mut server = McpSession::start(json!({ "allow_high_autonomy": false }));
+
+    let response = server.call("droid", spec_call(true, "high"));
+    assert_eq!(response["error"]["code"], -32602, "{}", response);
+    let message = response["error"]["message"].as_str().unwrap();
+    assert!(message.contains("(spec_auto)"), "{}", message);
+}
@@ -0,0 +1,21 @@
+//! ANSI escape stripping of captured droid text.
+
+use droid_mcp_rs::droid::strip_ansi;
+
+#[test]
+fn removes_color_codes() {
+    assert_eq!(strip_ansi("\u{1b}[1;31mred\u{1b}[0m plain"), "red plain");
+}
+
+#[test]
+fn removes_osc_and_two_byte_escapes() {
+    assert_eq!(
+        strip_ansi("\u{1b}]0;title\u{7}a\u{1b}]8;;link\u{1b}\\b\u{1b}cc"),
+        "abc"
+    );
+}
+
+#[test]
+fn leaves_plain_text_untouched() {
+    assert_eq!(strip_ansi("naïve [brackets] ~"), "naïve [brackets] ~");
+}
@@ -0,0 +1,69 @@
+//! Clamping client `timeout_secs` to `max_timeout_secs`, and `allow_client_timeout_override`.
+#![cfg(unix)]
+
+mod common;
+
+use droid_mcp_rs::droid::{self, DroidResult, ErrorCode, Options};
+use serde_json::json;
+
+async fn run(dir: &std::path::Path, prompt: &str, timeout_secs: u64) -> DroidResult {
+    let opts = Options {
+        prompt: Some(prompt.to_string()),
+        working_dir: dir.to_path_buf(),
+        auto: Some("low".to_string()),
+        timeout_secs: Some(timeout_secs),
+        ..Default::default()
+    };
+    droid::run(opts).await.expect("droid::run failed")
+}
+
+fn timeout_warning(result: &DroidResult) -> Option<&String> {
+    result
+        .warnings
+        .iter()
+        .find(|w| w.starts_with("Requested timeout of"))
+}
+
+#[tokio::test]
+async fn timeout_is_clamped_to_server_max() {
+    let config = common::with_config(json!({ "max_timeout_secs": 60 }));
+
+    let result = run(config.dir(), "say hello", 120).await;
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert_eq!(
+        timeout_warning(&result).map(String::as_str),
+        Some("Requested timeout of 120 seconds exceeds the server max_timeout_secs of 60 seconds; effective timeout is 60 seconds")
+    );
+
+    let result = run(config.dir(), "say hello", 60).await;
+    assert_eq!(timeout_warning(&result), None);
+}
+
+#[tokio::test]
+async fn clamped_timeout_is_enforced() {
+    let config = common::with_config(json!({ "max_timeout_secs": 1 }));
+
+    let started = std::time::Instant::now();
+    let result = run(config.dir(), "scenario:hang", 60).await;
+    assert!(started.elapsed() < std::time::Duration::from_secs(20));
+    assert_eq!(result.error_code, Some(ErrorCode::Timeout));
+    assert_eq!(result.error.as_deref(), Some("Timeout after 1 seconds"));
+}
+
+#[tokio::test]
+async fn override_lifts_server_max_up_to_absolute_max() {
+    let config = common::with_config(json!({
+        "max_timeout_secs": 60,
+        "allow_client_timeout_override": true
+    }));
+
+    let result = run(config.dir(), "say hello", 120).await;
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert_eq!(timeout_warning(&result), None);
+
+    let result = run(config.dir(), "say hello", 7200).await;
+    assert_eq!(
+        timeout_warning(&result).map(String::as_str),
+        Some("Requested timeout of 7200 seconds exceeds the absolute maximum of 3600 seconds; effective timeout is 3600 seconds")
+    );
+}
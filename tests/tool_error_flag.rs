@@ -0,0 +1,37 @@
+//! `isError` on droid tool results tracks the run's success, driven over MCP stdio.
+#![cfg(unix)]
+
+mod common;
+
+use common::{tool_text, McpSession};
+use serde_json::json;
+
+fn call(server: &mut McpSession, prompt: &str) -> (serde_json::Value, serde_json::Value) {
+    let response = server.call(
+        "droid",
+        json!({ "PROMPT": prompt, "auto": "low", "response_format": "json" }),
+    );
+    let body = serde_json::from_str(tool_text(&response)).expect("body is not JSON");
+    (response, body)
+}
+
+#[test]
+fn successful_run_is_not_flagged() {
+    let mut server = McpSession::start(json!({}));
+
+    let (response, body) = call(&mut server, "say hello");
+    assert_eq!(body["success"], true, "{}", body);
+    assert_ne!(response["result"]["isError"], true, "{}", response);
+}
+
+#[test]
+fn failed_run_is_flagged_and_keeps_structured_body() {
+    let mut server = McpSession::start(json!({}));
+
+    for prompt in ["scenario:error", "scenario:exit"] {
+        let (response, body) = call(&mut server, prompt);
+        assert_eq!(response["result"]["isError"], true, "{}", response);
+        assert_eq!(body["success"], false, "{}", body);
+        assert!(body["error"].is_string(), "{}", body);
+    }
+}
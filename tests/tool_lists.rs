@@ -0,0 +1,71 @@
+//! `enabled_tools` / `disabled_tools` normalization, driven over MCP stdio.
+#![cfg(unix)]
+
+mod common;
+
+use common::{tool_text, McpSession};
+use serde_json::json;
+
+fn echo_args(server: &mut McpSession, tools: serde_json::Value) -> serde_json::Value {
+    let mut arguments = json!({
+        "PROMPT": "scenario:echo-args",
+        "auto": "low",
+        "output_mode": "text"
+    });
+    arguments
+        .as_object_mut()
+        .unwrap()
+        .extend(tools.as_object().unwrap().clone());
+    server.call("droid", arguments)
+}
+
+#[test]
+fn mixed_separators_are_normalized_to_commas() {
+    let mut server = McpSession::start(json!({}));
+
+    let response = echo_args(
+        &mut server,
+        json!({ "enabled_tools": " Read, Edit  Execute,,", "disabled_tools": "WebSearch\tmcp:fetch" }),
+    );
+    let args = tool_text(&response);
+    assert!(
+        args.contains(" --enabled-tools Read,Edit,Execute "),
+        "{}",
+        args
+    );
+    assert!(
+        args.contains(" --disabled-tools WebSearch,mcp:fetch "),
+        "{}",
+        args
+    );
+}
+
+#[test]
+fn lists_without_entries_are_omitted() {
+    let mut server = McpSession::start(json!({}));
+
+    let response = echo_args(&mut server, json!({ "enabled_tools": " , ," }));
+    let args = tool_text(&response);
+    assert!(!args.contains("--enabled-tools"), "{}", args);
+}
+
+#[test]
+fn malformed_entries_are_rejected() {
+    let mut server = McpSession::start(json!({}));
+
+    for (field, value) in [
+        ("enabled_tools", "Read;rm -rf"),
+        ("disabled_tools", "Edit,$(whoami)"),
+        ("enabled_tools", "Read|Edit"),
+    ] {
+        let response = echo_args(&mut server, json!({ field: value }));
+        // invalid_params
+        assert_eq!(response["error"]["code"], -32602, "{}", response);
+        let message = response["error"]["message"].as_str().unwrap();
+        assert!(
+            message.contains(&format!("Invalid tool name in {}", field)),
+            "{}",
+            message
+        );
+    }
+}
@@ -0,0 +1,99 @@
+//! `--transport uds`: MCP over a Unix domain socket.
+#![cfg(unix)]
+
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::UnixStream;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+#[test]
+fn serves_mcp_over_socket_and_cleans_up() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let socket = dir.path().join("droid-mcp.sock");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_droid-mcp-rs"))
+        .args(["--transport", "uds", "--socket"])
+        .arg(&socket)
+        .env("DROID_MCP_CONFIG_PATH", dir.path().join("missing.json"))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to start server");
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !socket.exists() {
+        assert!(Instant::now() < deadline, "socket was never created");
+        std::thread::sleep(Duration::from_millis(50));
+    }
+
+    let mode = std::fs::metadata(&socket).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600, "socket mode {:o}", mode);
+    // Only the socket is left next to it, not the staging directory it was bound in
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+    let mut stream = UnixStream::connect(&socket).expect("failed to connect");
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    let request = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "initialize",
+        "params": {
+            "protocolVersion": "2024-11-05",
+            "capabilities": {},
+            "clientInfo": { "name": "test", "version": "0" }
+        }
+    });
+    writeln!(stream, "{}", request).expect("failed to write request");
+    let mut line = String::new();
+    BufReader::new(&stream)
+        .read_line(&mut line)
+        .expect("failed to read response");
+    let response: serde_json::Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(response["id"], 1, "{}", response);
+    assert!(response["result"]["serverInfo"].is_object(), "{}", response);
+    drop(stream);
+
+    // A second server refuses to replace the live socket without --force
+    let second = Command::new(env!("CARGO_BIN_EXE_droid-mcp-rs"))
+        .args(["--transport", "uds", "--socket"])
+        .arg(&socket)
+        .env("DROID_MCP_CONFIG_PATH", dir.path().join("missing.json"))
+        .output()
+        .expect("failed to run second server");
+    assert!(!second.status.success());
+    assert!(
+        String::from_utf8_lossy(&second.stderr).contains("--force"),
+        "{}",
+        String::from_utf8_lossy(&second.stderr)
+    );
+
+    let status = Command::new("kill")
+        .args(["-TERM", &child.id().to_string()])
+        .status()
+        .expect("failed to send SIGTERM");
+    assert!(status.success());
+    child.wait().expect("server did not exit");
+    assert!(!socket.exists(), "socket file was not removed");
+}
+
+#[test]
+fn force_does_not_replace_a_regular_file() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let path = dir.path().join("notes.txt");
+    std::fs::write(&path, "keep me").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_droid-mcp-rs"))
+        .args(["--transport", "uds", "--force", "--socket"])
+        .arg(&path)
+        .env("DROID_MCP_CONFIG_PATH", dir.path().join("missing.json"))
+        .output()
+        .expect("failed to run server");
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("is not a socket"), "{}", stderr);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "keep me");
+}